@@ -0,0 +1,51 @@
+use std::hint::black_box;
+use console_totp::{base32_encode, HashAlgorithm, Totp, TotpEntry};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+// Codes per second for a whole vault at once, as `loop` and `get --all` produce them.
+// `rebuild` decodes the secret and expands the HMAC key for every code; `prebuilt` reuses a
+// `Totp` per entry, so each code only hashes the counter.
+
+const VAULT_SIZES: [usize; 2] = [10, 200];
+const TIME: u64 = 1_700_000_000;
+
+fn vault(size: usize) -> Vec<TotpEntry> {
+    let algorithms = [HashAlgorithm::Sha1, HashAlgorithm::Sha256, HashAlgorithm::Sha512];
+    (0..size)
+        .map(|index| TotpEntry {
+            name: format!("entry-{}", index),
+            secret: base32_encode(&[index as u8; 20]),
+            algorithm: algorithms[index % algorithms.len()],
+            ..TotpEntry::default()
+        })
+        .collect()
+}
+
+fn generate_all(c: &mut Criterion) {
+    let mut group = c.benchmark_group("generate_all");
+    for size in VAULT_SIZES {
+        let entries = vault(size);
+        group.throughput(Throughput::Elements(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("rebuild", size), &entries, |b, entries| {
+            b.iter(|| {
+                for entry in entries {
+                    black_box(Totp::from_entry(entry).unwrap().generate_at_time(TIME).unwrap());
+                }
+            })
+        });
+
+        let totps: Vec<Totp> = entries.iter().map(|entry| Totp::from_entry(entry).unwrap()).collect();
+        group.bench_with_input(BenchmarkId::new("prebuilt", size), &totps, |b, totps| {
+            b.iter(|| {
+                for totp in totps {
+                    black_box(totp.generate_at_time(TIME).unwrap());
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, generate_all);
+criterion_main!(benches);
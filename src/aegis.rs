@@ -0,0 +1,133 @@
+use serde::Deserialize;
+use crate::TotpEntry;
+use crate::totp::{validate_params, HashAlgorithm, DEFAULT_DIGITS, DEFAULT_PERIOD};
+
+// Aegis Authenticator plaintext backup (https://github.com/beemdevelopment/Aegis/blob/master/docs/vault.md)
+#[derive(Deserialize)]
+struct AegisBackup {
+    db: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct AegisDb {
+    entries: Vec<AegisEntry>,
+}
+
+#[derive(Deserialize)]
+struct AegisEntry {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    issuer: String,
+    info: AegisInfo,
+}
+
+#[derive(Deserialize)]
+struct AegisInfo {
+    secret: String,
+    algo: Option<String>,
+    digits: Option<u32>,
+    period: Option<u64>,
+}
+
+/// Parses an Aegis JSON export. Each entry either converts to a [`TotpEntry`] or explains why
+/// it was skipped, e.g. because it's an HOTP token.
+pub fn read_aegis(json: &str) -> anyhow::Result<Vec<Result<TotpEntry, String>>> {
+    let backup: AegisBackup = serde_json::from_str(json)?;
+
+    // Encrypted vaults store `db` as a base64 string instead of an object
+    if backup.db.is_string() {
+        anyhow::bail!("Encrypted Aegis backups are not supported; export a plaintext backup from Aegis instead");
+    }
+    let db: AegisDb = serde_json::from_value(backup.db)?;
+
+    Ok(db.entries.into_iter().map(convert_entry).collect())
+}
+
+fn convert_entry(entry: AegisEntry) -> Result<TotpEntry, String> {
+    // Aegis allows an empty name as long as there is an issuer
+    let account = (!entry.name.is_empty()).then(|| entry.name.clone());
+    let name = if entry.name.is_empty() { entry.issuer.clone() } else { entry.name };
+    if name.is_empty() {
+        return Err("(unnamed) (missing name)".to_string());
+    }
+
+    if !entry.kind.eq_ignore_ascii_case("totp") {
+        return Err(format!("{} (unsupported type: {})", name, entry.kind));
+    }
+
+    let algorithm = match entry.info.algo.as_deref() {
+        Some(algo) => HashAlgorithm::parse(algo).ok_or_else(|| format!("{} (unsupported algorithm: {})", name, algo))?,
+        None => HashAlgorithm::default(),
+    };
+    let digits = entry.info.digits.unwrap_or(DEFAULT_DIGITS);
+    let period = entry.info.period.unwrap_or(DEFAULT_PERIOD);
+    validate_params(digits, period).map_err(|reason| format!("{} ({})", name, reason))?;
+
+    Ok(TotpEntry {
+        name,
+        secret: entry.info.secret,
+        issuer: if entry.issuer.is_empty() { None } else { Some(entry.issuer) },
+        algorithm,
+        digits,
+        period,
+        account,
+        ..TotpEntry::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BACKUP: &str = r#"{
+        "version": 1,
+        "header": { "slots": null, "params": null },
+        "db": {
+            "version": 2,
+            "entries": [
+                {
+                    "type": "totp", "uuid": "1", "name": "alice@example.com", "issuer": "GitHub",
+                    "note": "", "icon": null,
+                    "info": { "secret": "JBSWY3DPEHPK3PXP", "algo": "SHA256", "digits": 8, "period": 60 }
+                },
+                {
+                    "type": "hotp", "uuid": "2", "name": "counter", "issuer": "",
+                    "info": { "secret": "JBSWY3DPEHPK3PXP", "algo": "SHA1", "digits": 6, "counter": 3 }
+                },
+                {
+                    "type": "totp", "uuid": "3", "name": "", "issuer": "Example",
+                    "info": { "secret": "JBSWY3DPEHPK3PXP" }
+                }
+            ]
+        }
+    }"#;
+
+    #[test]
+    fn test_read_aegis_plaintext() {
+        let results = read_aegis(BACKUP).unwrap();
+        assert_eq!(results.len(), 3);
+
+        let github = results[0].as_ref().unwrap();
+        assert_eq!(github.name, "alice@example.com");
+        assert_eq!(github.issuer.as_deref(), Some("GitHub"));
+        assert_eq!(github.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(github.digits, 8);
+        assert_eq!(github.period, 60);
+
+        assert_eq!(results[1].as_ref().unwrap_err(), "counter (unsupported type: hotp)");
+
+        let example = results[2].as_ref().unwrap();
+        assert_eq!(example.name, "Example");
+        assert_eq!(example.digits, DEFAULT_DIGITS);
+        assert_eq!(example.period, DEFAULT_PERIOD);
+    }
+
+    #[test]
+    fn test_read_aegis_encrypted_is_rejected() {
+        let encrypted = r#"{ "version": 1, "header": { "slots": [], "params": {} }, "db": "c2VjcmV0" }"#;
+        assert!(read_aegis(encrypted).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use serde::Deserialize;
+use crate::TotpEntry;
+use crate::totp::{validate_params, HashAlgorithm, DEFAULT_DIGITS, DEFAULT_PERIOD};
+
+// andOTP plaintext backup (accounts.json): a flat array of accounts
+#[derive(Deserialize)]
+struct AndOtpEntry {
+    secret: String,
+    #[serde(default)]
+    label: String,
+    #[serde(default)]
+    issuer: String,
+    #[serde(rename = "type", default = "default_type")]
+    kind: String,
+    algorithm: Option<String>,
+    digits: Option<u32>,
+    period: Option<u64>,
+}
+
+fn default_type() -> String {
+    "TOTP".to_string()
+}
+
+/// Parses an andOTP `accounts.json` export. Each account either converts to a [`TotpEntry`] or
+/// explains why it was skipped.
+pub fn read_andotp(json: &str) -> anyhow::Result<Vec<Result<TotpEntry, String>>> {
+    let accounts: Vec<AndOtpEntry> = serde_json::from_str(json)
+        .map_err(|e| anyhow::anyhow!("Not a plaintext andOTP backup ({}); encrypted backups are not supported", e))?;
+
+    Ok(accounts.into_iter().map(convert_entry).collect())
+}
+
+fn convert_entry(account: AndOtpEntry) -> Result<TotpEntry, String> {
+    let label = (!account.label.is_empty()).then(|| account.label.clone());
+    let name = if account.label.is_empty() { account.issuer.clone() } else { account.label };
+    if name.is_empty() {
+        return Err("(unnamed) (missing label)".to_string());
+    }
+
+    if !account.kind.eq_ignore_ascii_case("totp") {
+        return Err(format!("{} (unsupported type: {})", name, account.kind));
+    }
+
+    let algorithm = match account.algorithm.as_deref() {
+        Some(algorithm) => HashAlgorithm::parse(algorithm).ok_or_else(|| format!("{} (unsupported algorithm: {})", name, algorithm))?,
+        None => HashAlgorithm::default(),
+    };
+    let digits = account.digits.unwrap_or(DEFAULT_DIGITS);
+    let period = account.period.unwrap_or(DEFAULT_PERIOD);
+    validate_params(digits, period).map_err(|reason| format!("{} ({})", name, reason))?;
+
+    Ok(TotpEntry {
+        name,
+        secret: account.secret,
+        issuer: if account.issuer.is_empty() { None } else { Some(account.issuer) },
+        algorithm,
+        digits,
+        period,
+        account: label,
+        ..TotpEntry::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_andotp() {
+        let json = r#"[
+            {
+                "secret": "JBSWY3DPEHPK3PXP", "issuer": "GitHub", "label": "alice", "digits": 8,
+                "type": "TOTP", "algorithm": "SHA512", "thumbnail": "Default", "last_used": 0,
+                "used_frequency": 0, "period": 60, "tags": []
+            },
+            { "secret": "JBSWY3DPEHPK3PXP", "label": "steam", "type": "STEAM", "digits": 5 },
+            { "secret": "JBSWY3DPEHPK3PXP", "label": "minimal" }
+        ]"#;
+
+        let results = read_andotp(json).unwrap();
+        assert_eq!(results.len(), 3);
+
+        let github = results[0].as_ref().unwrap();
+        assert_eq!(github.name, "alice");
+        assert_eq!(github.issuer.as_deref(), Some("GitHub"));
+        assert_eq!(github.algorithm, HashAlgorithm::Sha512);
+        assert_eq!(github.digits, 8);
+        assert_eq!(github.period, 60);
+
+        assert_eq!(results[1].as_ref().unwrap_err(), "steam (unsupported type: STEAM)");
+
+        let minimal = results[2].as_ref().unwrap();
+        assert_eq!(minimal.issuer, None);
+        assert_eq!(minimal.algorithm, HashAlgorithm::Sha1);
+        assert_eq!(minimal.period, DEFAULT_PERIOD);
+    }
+}
@@ -1,13 +1,54 @@
+use zeroize::{Zeroize, Zeroizing};
+use crate::error::{Error, Result};
+
 // Base32 decoder
-pub fn base32_decode(input: &str) -> anyhow::Result<Vec<u8>> {
-    let alphabet = "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
-    let input = input.to_uppercase().replace('=', "");
+// The two RFC 4648 alphabets. They share most characters, so decoding with the wrong one often
+// "succeeds" with a different key; callers must know which one a secret uses.
+const BASE32_ALPHABET: &str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const BASE32HEX_ALPHABET: &str = "0123456789ABCDEFGHIJKLMNOPQRSTUV";
+
+/// Decodes a base32 secret. The result, and every copy made along the way, is wiped from memory
+/// when dropped.
+pub fn base32_decode(input: &str) -> Result<Zeroizing<Vec<u8>>> {
+    decode(input, BASE32_ALPHABET, "base32")
+}
+
+/// Decodes a secret in the base32hex alphabet (`0-9A-V`, RFC 4648 section 7), accepting the same
+/// grouping, case and padding as [`base32_decode`].
+pub fn base32hex_decode(input: &str) -> Result<Zeroizing<Vec<u8>>> {
+    decode(input, BASE32HEX_ALPHABET, "base32hex")
+}
+
+fn decode(input: &str, alphabet: &str, variant: &'static str) -> Result<Zeroizing<Vec<u8>>> {
+    let invalid = |reason| Error::InvalidBase32 { alphabet: variant, reason };
+
+    // Secrets copied from providers are often grouped with spaces or hyphens. Sized up front so
+    // growing the buffer never leaves a stray copy behind.
+    let mut normalized = Zeroizing::new(String::with_capacity(input.len()));
+    for c in input.chars().filter(|c| !c.is_whitespace() && *c != '-') {
+        normalized.push(c.to_ascii_uppercase());
+    }
+    let input = normalized.as_str();
+
+    // Padding is optional, but when present it must only trail the data and complete the last block
+    let (input, padding) = input.split_at(input.find('=').unwrap_or(input.len()));
+    if padding.chars().any(|c| c != '=') {
+        return Err(invalid("bad padding"));
+    }
+    if !padding.is_empty() && (padding.len() >= 8 || (input.len() + padding.len()) % 8 != 0) {
+        return Err(invalid("bad padding"));
+    }
 
     if input.chars().any(|c| !alphabet.contains(c)) {
-        anyhow::bail!("Invalid base32 character");
+        return Err(invalid("unexpected character"));
     }
 
-    let mut result = Vec::new();
+    // RFC 4648 only produces final blocks of 2, 4, 5 or 7 characters
+    if matches!(input.len() % 8, 1 | 3 | 6) {
+        return Err(invalid("impossible length"));
+    }
+
+    let mut result = Zeroizing::new(Vec::with_capacity(input.len() * 5 / 8));
     let mut buffer = 0u64;
     let mut bits = 0;
 
@@ -22,5 +63,37 @@ pub fn base32_decode(input: &str) -> anyhow::Result<Vec<u8>> {
         }
     }
 
+    // Bits that don't complete a byte must be zero in a canonical encoding
+    let trailing = buffer & ((1 << bits) - 1);
+    buffer.zeroize();
+    if trailing != 0 {
+        return Err(invalid("non-zero trailing bits"));
+    }
+
     Ok(result)
 }
+
+// Base32 encoder (unpadded, as expected by authenticator apps)
+pub fn base32_encode(input: &[u8]) -> String {
+    let alphabet = BASE32_ALPHABET.as_bytes();
+
+    let mut result = String::with_capacity(input.len().div_ceil(5) * 8);
+    let mut buffer = 0u64;
+    let mut bits = 0;
+
+    for &byte in input {
+        buffer = (buffer << 8) | byte as u64;
+        bits += 8;
+
+        while bits >= 5 {
+            result.push(alphabet[((buffer >> (bits - 5)) & 0x1f) as usize] as char);
+            bits -= 5;
+        }
+    }
+
+    if bits > 0 {
+        result.push(alphabet[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+
+    result
+}
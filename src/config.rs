@@ -0,0 +1,375 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use anyhow::Context;
+use console_totp::keychain;
+use console_totp::kv::{self, CloudflareKV, RequestOptions};
+use console_totp::totp::validate_params;
+use console_totp::{HashAlgorithm, TotpEntry};
+use serde::Deserialize;
+use crate::style::ColorChoice;
+
+// Persistent defaults from `config.toml`. Command-line flags always win over the file.
+
+/// Database used when the config file doesn't name one, relative to the working directory
+pub(crate) const DEFAULT_DATABASE: &str = "totp.db";
+
+/// The profile used without `--profile` or `$TOTP_PROFILE`. It is the configured `database`, so
+/// vaults from before profiles existed keep working.
+pub(crate) const DEFAULT_PROFILE: &str = "default";
+
+/// Settings read from `config.toml`, already validated. Everything is optional.
+#[derive(Debug, Default)]
+pub(crate) struct Config {
+    /// Where the file was read from, if it exists
+    pub(crate) path: Option<PathBuf>,
+    database: Option<PathBuf>,
+    /// Name and database set by [`Config::use_profile`]; None is the default profile
+    profile: Option<(String, PathBuf)>,
+    pub(crate) color: Option<ColorChoice>,
+    /// Record generated and copied codes in the usage log. Off unless the file turns it on.
+    pub(crate) history: bool,
+    algorithm: Option<HashAlgorithm>,
+    digits: Option<u32>,
+    period: Option<u64>,
+    kv: Option<KvAccount>,
+    kv_timeout: Option<Duration>,
+    kv_retries: Option<u32>,
+    kv_key: Option<String>,
+}
+
+// `[kv]` credentials; the token may instead come from the keychain (`kv-login`)
+#[derive(Debug)]
+struct KvAccount {
+    account_id: String,
+    namespace_id: String,
+    api_token: Option<String>,
+}
+
+// The file as written; unknown keys are rejected so typos don't go unnoticed
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawConfig {
+    database: Option<PathBuf>,
+    color: Option<String>,
+    #[serde(default)]
+    history: bool,
+    #[serde(default)]
+    defaults: RawDefaults,
+    #[serde(default)]
+    kv: RawKv,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawDefaults {
+    algorithm: Option<String>,
+    digits: Option<u32>,
+    period: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawKv {
+    account_id: Option<String>,
+    namespace_id: Option<String>,
+    api_token: Option<String>,
+    timeout: Option<u64>,
+    retries: Option<u32>,
+    key: Option<String>,
+}
+
+/// `$TOTP_CONFIG` if set, otherwise `config.toml` under the platform config directory
+/// (`~/.config/totp-console/` on Linux).
+pub(crate) fn default_path() -> Option<PathBuf> {
+    match std::env::var_os("TOTP_CONFIG") {
+        Some(path) => Some(PathBuf::from(path)),
+        None => dirs::config_dir().map(|dir| dir.join("totp-console").join("config.toml")),
+    }
+}
+
+/// Where profiles other than the default one keep their databases:
+/// `$XDG_DATA_HOME/totp-console/` on Linux (`~/.local/share/totp-console/` if it isn't set).
+pub(crate) fn profile_dir() -> Option<PathBuf> {
+    dirs::data_dir().map(|dir| dir.join("totp-console"))
+}
+
+/// Profile names become file names, so they are limited to letters, digits, `-` and `_`.
+pub(crate) fn validate_profile(name: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("profile name is empty".to_string());
+    }
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("profile names may only contain letters, digits, - and _, got {}", name));
+    }
+    Ok(())
+}
+
+/// The profiles with a database in `dir`, sorted by name. The default profile isn't stored
+/// there and isn't included. A missing directory just means no profiles yet.
+pub(crate) fn list_profiles(dir: &Path) -> anyhow::Result<Vec<String>> {
+    let read = match std::fs::read_dir(dir) {
+        Ok(read) => read,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(anyhow::Error::from(e).context(format!("Failed to read {}", dir.display()))),
+    };
+    let mut profiles: Vec<String> = read
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let profile = name.strip_suffix(".db")?;
+            (profile != DEFAULT_PROFILE && validate_profile(profile).is_ok()).then(|| profile.to_string())
+        })
+        .collect();
+    profiles.sort();
+    Ok(profiles)
+}
+
+/// Loads the config file. A missing file means all defaults; an unreadable or invalid one is
+/// an error naming the file.
+pub(crate) fn load() -> anyhow::Result<Config> {
+    let Some(path) = default_path() else {
+        return Ok(Config::default());
+    };
+    if !path.exists() {
+        return Ok(Config::default());
+    }
+    load_from(&path)
+}
+
+fn load_from(path: &Path) -> anyhow::Result<Config> {
+    let content = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut config = parse(&content).with_context(|| format!("Invalid config file {}", path.display()))?;
+    log::debug!("Read config from {}", path.display());
+    config.path = Some(path.to_path_buf());
+    Ok(config)
+}
+
+fn parse(content: &str) -> anyhow::Result<Config> {
+    let raw: RawConfig = toml::from_str(content)?;
+
+    let color = match raw.color {
+        Some(value) => Some(
+            ColorChoice::parse(&value)
+                .with_context(|| format!("color must be auto, always or never, got {}", value))?,
+        ),
+        None => None,
+    };
+    let algorithm = match raw.defaults.algorithm {
+        Some(value) => Some(
+            HashAlgorithm::parse(&value).with_context(|| format!("unknown algorithm in [defaults]: {}", value))?,
+        ),
+        None => None,
+    };
+    validate_params(
+        raw.defaults.digits.unwrap_or(TotpEntry::default().digits),
+        raw.defaults.period.unwrap_or(TotpEntry::default().period),
+    )
+    .map_err(|reason| anyhow::anyhow!("[defaults] {}", reason))?;
+
+    let kv = match (raw.kv.account_id, raw.kv.namespace_id, raw.kv.api_token) {
+        (Some(account_id), Some(namespace_id), api_token) => Some(KvAccount { account_id, namespace_id, api_token }),
+        (None, None, None) => None,
+        _ => anyhow::bail!("[kv] needs account_id and namespace_id, plus api_token unless it is stored with kv-login"),
+    };
+    if raw.kv.timeout == Some(0) {
+        anyhow::bail!("[kv] timeout must be at least 1 second");
+    }
+    if let Some(key) = &raw.kv.key {
+        kv::validate_key(key).map_err(|reason| anyhow::anyhow!("[kv] {}", reason))?;
+    }
+
+    Ok(Config {
+        path: None,
+        database: raw.database,
+        profile: None,
+        color,
+        history: raw.history,
+        algorithm,
+        digits: raw.defaults.digits,
+        period: raw.defaults.period,
+        kv,
+        kv_timeout: raw.kv.timeout.map(Duration::from_secs),
+        kv_retries: raw.kv.retries,
+        kv_key: raw.kv.key,
+    })
+}
+
+impl Config {
+    /// The database of the selected profile.
+    pub(crate) fn database(&self) -> &Path {
+        match &self.profile {
+            Some((_, path)) => path,
+            None => self.default_database(),
+        }
+    }
+
+    /// The database of the default profile: `database` from the file, or `totp.db`.
+    pub(crate) fn default_database(&self) -> &Path {
+        self.database.as_deref().unwrap_or(Path::new(DEFAULT_DATABASE))
+    }
+
+    /// The selected profile.
+    pub(crate) fn profile(&self) -> &str {
+        self.profile.as_ref().map_or(DEFAULT_PROFILE, |(name, _)| name)
+    }
+
+    /// Switches to the database of `profile`, creating the profile directory if needed. The
+    /// default profile keeps the configured database.
+    pub(crate) fn use_profile(&mut self, profile: &str) -> anyhow::Result<()> {
+        validate_profile(profile).map_err(anyhow::Error::msg)?;
+        if profile == DEFAULT_PROFILE {
+            return Ok(());
+        }
+        let dir = profile_dir().context("No data directory to keep profiles in on this platform")?;
+        std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        self.profile = Some((profile.to_string(), dir.join(format!("{}.db", profile))));
+        Ok(())
+    }
+
+    /// An empty entry carrying the configured algorithm, digits and period, for `add` and `gen`
+    /// to fill in with struct update syntax.
+    pub(crate) fn new_entry(&self) -> TotpEntry {
+        let defaults = TotpEntry::default();
+        TotpEntry {
+            algorithm: self.algorithm.unwrap_or(defaults.algorithm),
+            digits: self.digits.unwrap_or(defaults.digits),
+            period: self.period.unwrap_or(defaults.period),
+            ..defaults
+        }
+    }
+
+    /// Credentials from `[kv]`, falling back to `kv.json` and then the CF_* environment variables.
+    /// A token in the keychain wins over `api_token`.
+    pub(crate) fn cloudflare_kv(&self) -> Option<CloudflareKV> {
+        match &self.kv {
+            Some(kv) => {
+                let api_token = keychain::get_token().or_else(|| kv.api_token.clone())?;
+                Some(CloudflareKV::new(kv.account_id.clone(), kv.namespace_id.clone(), api_token))
+            }
+            None => kv::get_cloudflare_kv(),
+        }
+    }
+
+    /// The vault key in Cloudflare KV: `key` from `[kv]`, otherwise one per profile, `totp` for
+    /// the default profile and `totp-<profile>` for the others.
+    pub(crate) fn kv_key(&self) -> String {
+        match (&self.kv_key, &self.profile) {
+            (Some(key), _) => key.clone(),
+            (None, Some((profile, _))) => format!("{}-{}", kv::DEFAULT_KEY, profile),
+            (None, None) => kv::DEFAULT_KEY.to_string(),
+        }
+    }
+
+    /// Request options from `[kv]`, before CF_TIMEOUT/CF_RETRIES and flags are applied.
+    pub(crate) fn request_options(&self) -> RequestOptions {
+        let defaults = RequestOptions::default();
+        RequestOptions {
+            timeout: self.kv_timeout.unwrap_or(defaults.timeout),
+            retries: self.kv_retries.unwrap_or(defaults.retries),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_full_config() {
+        let config = parse(
+            r#"
+            database = "/tmp/vault.db"
+            color = "never"
+            history = true
+
+            [defaults]
+            algorithm = "sha256"
+            digits = 8
+            period = 60
+
+            [kv]
+            account_id = "account"
+            namespace_id = "namespace"
+            api_token = "token"
+            timeout = 30
+            retries = 0
+            key = "shared"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.database(), Path::new("/tmp/vault.db"));
+        assert_eq!(config.color, Some(ColorChoice::Never));
+        assert!(config.history);
+        let entry = config.new_entry();
+        assert_eq!(entry.algorithm, HashAlgorithm::Sha256);
+        assert_eq!((entry.digits, entry.period), (8, 60));
+        let kv = config.kv.as_ref().unwrap();
+        assert_eq!((kv.account_id.as_str(), kv.namespace_id.as_str()), ("account", "namespace"));
+        assert_eq!(kv.api_token.as_deref(), Some("token"));
+        assert_eq!(config.request_options(), RequestOptions { timeout: Duration::from_secs(30), retries: 0 });
+        assert_eq!(config.kv_key(), "shared");
+    }
+
+    #[test]
+    fn test_empty_config_keeps_defaults() {
+        let config = parse("").unwrap();
+        assert_eq!(config.database(), Path::new(DEFAULT_DATABASE));
+        assert_eq!(config.color, None);
+        assert!(!config.history);
+        assert_eq!(config.new_entry().digits, TotpEntry::default().digits);
+        assert_eq!(config.request_options(), RequestOptions::default());
+        assert_eq!(config.kv_key(), kv::DEFAULT_KEY);
+    }
+
+    #[test]
+    fn test_kv_token_may_live_in_the_keychain() {
+        let config = parse("[kv]\naccount_id = \"account\"\nnamespace_id = \"namespace\"").unwrap();
+        let kv = config.kv.unwrap();
+        assert_eq!((kv.account_id.as_str(), kv.namespace_id.as_str()), ("account", "namespace"));
+        assert_eq!(kv.api_token, None);
+    }
+
+    #[test]
+    fn test_profile_names() {
+        assert!(validate_profile("work").is_ok());
+        assert!(validate_profile("side_project-2").is_ok());
+        assert!(validate_profile("").is_err());
+        assert!(validate_profile("../work").is_err());
+        assert!(validate_profile("my work").is_err());
+
+        let mut config = Config::default();
+        config.use_profile(DEFAULT_PROFILE).unwrap();
+        assert_eq!((config.profile(), config.database()), (DEFAULT_PROFILE, Path::new(DEFAULT_DATABASE)));
+
+        // Each profile syncs to its own KV vault unless [kv] names one
+        let work = Config { profile: Some(("work".to_string(), PathBuf::from("work.db"))), ..Config::default() };
+        assert_eq!(work.kv_key(), "totp-work");
+    }
+
+    #[test]
+    fn test_list_profiles() {
+        let dir = std::env::temp_dir().join(format!("totp-profiles-{}", std::process::id()));
+        assert!(list_profiles(&dir).unwrap().is_empty());
+
+        std::fs::create_dir_all(&dir).unwrap();
+        for file in ["work.db", "personal.db", "default.db", "notes.txt", "work.db-wal"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        let profiles = list_profiles(&dir);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(profiles.unwrap(), ["personal", "work"]);
+    }
+
+    #[test]
+    fn test_invalid_config_is_rejected() {
+        assert!(parse("colour = \"never\"").is_err());
+        assert!(parse("color = \"sometimes\"").is_err());
+        assert!(parse("[defaults]\nalgorithm = \"md5\"").is_err());
+        assert!(parse("[defaults]\ndigits = 12").is_err());
+        assert!(parse("[kv]\naccount_id = \"account\"").is_err());
+        assert!(parse("[kv]\napi_token = \"token\"").is_err());
+        assert!(parse("[kv]\ntimeout = 0").is_err());
+        assert!(parse("[kv]\nkey = \"a:b\"").is_err());
+    }
+}
@@ -0,0 +1,188 @@
+use std::io::{Read, Write};
+use crate::TotpEntry;
+use crate::totp::{validate_params, HashAlgorithm, DEFAULT_DIGITS, DEFAULT_PERIOD};
+
+// CSV import/export for spreadsheet users
+const HEADER: [&str; 7] = ["name", "secret", "issuer", "algorithm", "digits", "period", "account"];
+
+/// A data row that couldn't be turned into an entry, with its 1-based line number.
+#[derive(Debug)]
+pub struct RowError {
+    pub line: u64,
+    pub reason: String,
+}
+
+pub fn write_csv<W: Write>(writer: W, entries: &[TotpEntry]) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(HEADER)?;
+
+    for entry in entries {
+        writer.write_record([
+            entry.name.as_str(),
+            entry.secret.as_str(),
+            entry.issuer.as_deref().unwrap_or(""),
+            entry.algorithm.as_str(),
+            &entry.digits.to_string(),
+            &entry.period.to_string(),
+            entry.account.as_deref().unwrap_or(""),
+        ])?;
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads entries from CSV with a header row. Columns may appear in any order; only `name` and
+/// `secret` are required, and empty optional cells fall back to the defaults. Rows that can't be
+/// parsed are returned separately so the caller can report and skip them.
+pub fn read_csv<R: Read>(reader: R) -> anyhow::Result<(Vec<TotpEntry>, Vec<RowError>)> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .trim(csv::Trim::All)
+        .from_reader(reader);
+
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| headers.iter().position(|header| header.eq_ignore_ascii_case(name));
+    let (Some(name_column), Some(secret_column)) = (column("name"), column("secret")) else {
+        anyhow::bail!("CSV header must contain at least 'name' and 'secret' columns");
+    };
+    let issuer_column = column("issuer");
+    let algorithm_column = column("algorithm");
+    let digits_column = column("digits");
+    let period_column = column("period");
+    let account_column = column("account");
+
+    let mut entries = Vec::new();
+    let mut errors = Vec::new();
+
+    for record in reader.records() {
+        let record = match record {
+            Ok(record) => record,
+            Err(e) => {
+                let line = e.position().map(|position| position.line()).unwrap_or_default();
+                errors.push(RowError { line, reason: e.to_string() });
+                continue;
+            }
+        };
+        let line = record.position().map(|position| position.line()).unwrap_or_default();
+        let cell = |index: Option<usize>| index.and_then(|index| record.get(index)).filter(|value| !value.is_empty());
+
+        match parse_row(
+            cell(Some(name_column)),
+            cell(Some(secret_column)),
+            cell(issuer_column),
+            cell(algorithm_column),
+            cell(digits_column),
+            cell(period_column),
+            cell(account_column),
+        ) {
+            Ok(entry) => entries.push(entry),
+            Err(reason) => errors.push(RowError { line, reason }),
+        }
+    }
+
+    Ok((entries, errors))
+}
+
+fn parse_row(
+    name: Option<&str>,
+    secret: Option<&str>,
+    issuer: Option<&str>,
+    algorithm: Option<&str>,
+    digits: Option<&str>,
+    period: Option<&str>,
+    account: Option<&str>,
+) -> Result<TotpEntry, String> {
+    let name = name.ok_or("missing name")?;
+    let secret = secret.ok_or("missing secret")?;
+
+    let algorithm = match algorithm {
+        Some(value) => HashAlgorithm::parse(value).ok_or_else(|| format!("unknown algorithm: {}", value))?,
+        None => HashAlgorithm::default(),
+    };
+    let digits = match digits {
+        Some(value) => value.parse().map_err(|_| format!("invalid digits: {}", value))?,
+        None => DEFAULT_DIGITS,
+    };
+    let period = match period {
+        Some(value) => value.parse().map_err(|_| format!("invalid period: {}", value))?,
+        None => DEFAULT_PERIOD,
+    };
+    validate_params(digits, period)?;
+
+    Ok(TotpEntry {
+        name: name.to_string(),
+        secret: secret.to_string(),
+        issuer: issuer.map(str::to_string),
+        algorithm,
+        digits,
+        period,
+        account: account.map(str::to_string),
+        ..TotpEntry::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv_round_trip_with_quoting() {
+        let entries = vec![
+            TotpEntry {
+                name: "Smith, John".to_string(),
+                secret: "JBSWY3DPEHPK3PXP".to_string(),
+                issuer: Some("Acme \"Corp\"".to_string()),
+                algorithm: HashAlgorithm::Sha256,
+                digits: 8,
+                period: 60,
+                account: Some("john@example.com".to_string()),
+                ..TotpEntry::default()
+            },
+            TotpEntry {
+                name: "github".to_string(),
+                secret: "JBSWY3DPEHPK3PXP".to_string(),
+                ..TotpEntry::default()
+            },
+        ];
+
+        let mut buffer = Vec::new();
+        write_csv(&mut buffer, &entries).unwrap();
+        let (parsed, errors) = read_csv(buffer.as_slice()).unwrap();
+
+        assert!(errors.is_empty());
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].name, "Smith, John");
+        assert_eq!(parsed[0].issuer.as_deref(), Some("Acme \"Corp\""));
+        assert_eq!(parsed[0].algorithm, HashAlgorithm::Sha256);
+        assert_eq!(parsed[0].digits, 8);
+        assert_eq!(parsed[0].period, 60);
+        assert_eq!(parsed[0].account.as_deref(), Some("john@example.com"));
+        assert_eq!(parsed[1].issuer, None);
+        assert_eq!(parsed[1].account, None);
+        assert_eq!(parsed[1].digits, DEFAULT_DIGITS);
+    }
+
+    #[test]
+    fn test_csv_invalid_rows_report_line_numbers() {
+        let input = "name,secret,issuer,algorithm,digits,period\n\
+                     good,JBSWY3DPEHPK3PXP,,,,\n\
+                     ,JBSWY3DPEHPK3PXP,,,,\n\
+                     bad-algo,JBSWY3DPEHPK3PXP,,MD5,,\n\
+                     bad-digits,JBSWY3DPEHPK3PXP,,,twelve,\n\
+                     short-row,JBSWY3DPEHPK3PXP\n";
+
+        let (entries, errors) = read_csv(input.as_bytes()).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].name, "short-row");
+        let lines: Vec<u64> = errors.iter().map(|error| error.line).collect();
+        assert_eq!(lines, vec![3, 4, 5]);
+        assert_eq!(errors[0].reason, "missing name");
+    }
+
+    #[test]
+    fn test_csv_requires_name_and_secret_columns() {
+        assert!(read_csv("name,issuer\ngithub,GitHub\n".as_bytes()).is_err());
+    }
+}
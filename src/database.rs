@@ -1,24 +1,356 @@
-use rusqlite::{Connection, Result as SqliteResult};
+use std::path::Path;
+use std::time::Duration;
+use rusqlite::limits::Limit;
+use rusqlite::{params, Connection, ToSql, OptionalExtension, Result as SqliteResult, Row, Transaction, TransactionBehavior};
 use serde::{Deserialize, Serialize};
+use crate::error::{Error, Result};
+use crate::totp::{HashAlgorithm, DEFAULT_DIGITS, DEFAULT_PERIOD};
 
+/// A stored TOTP account. `secret` is kept in base32, as authenticator apps display it.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TotpEntry {
-    pub(crate) id: Option<i64>,
-    pub(crate) name: String,
-    pub(crate) secret: String,
-    pub(crate) issuer: Option<String>,
-    pub(crate) created_at: String,
+    /// Row id in the local database. It means nothing to another database, so it is left out
+    /// of serialized entries once cleared.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<i64>,
+    pub name: String,
+    pub secret: String,
+    pub issuer: Option<String>,
+    pub created_at: String,
+    // Older exports predate these fields, so they fall back to the RFC 6238 defaults
+    #[serde(default)]
+    pub algorithm: HashAlgorithm,
+    #[serde(default = "default_digits")]
+    pub digits: u32,
+    #[serde(default = "default_period")]
+    pub period: u64,
+    /// The account label from the otpauth URL (usually an email), when it differs from `name`
+    #[serde(default)]
+    pub account: Option<String>,
+    /// Fixed HMAC byte offset for legacy tokens; `None` means RFC 4226 dynamic truncation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncation_offset: Option<u8>,
+    /// Pinned to the top of every list
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub favorite: bool,
+    /// The provider's sign-in page, opened by `open`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub login_url: Option<String>,
+    /// One-time recovery codes kept with the token, in the order they were given
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backup_codes: Vec<String>,
+    /// RFC 6238 T0, the Unix time the first time step starts at; almost always 0
+    #[serde(default, skip_serializing_if = "is_zero")]
+    pub epoch: u64,
+    /// When a temporary token stops being needed; `list` hides the entry from then on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-// Database management
+impl Default for TotpEntry {
+    fn default() -> Self {
+        Self {
+            id: None,
+            name: String::new(),
+            secret: String::new(),
+            issuer: None,
+            created_at: String::new(),
+            algorithm: HashAlgorithm::default(),
+            digits: DEFAULT_DIGITS,
+            period: DEFAULT_PERIOD,
+            account: None,
+            truncation_offset: None,
+            favorite: false,
+            login_url: None,
+            backup_codes: Vec::new(),
+            epoch: 0,
+            expires_at: None,
+        }
+    }
+}
+
+impl TotpEntry {
+    /// Whether the entry's expiration time has come by `time`.
+    pub fn is_expired_at(&self, time: chrono::DateTime<chrono::Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= time)
+    }
+}
+
+fn default_digits() -> u32 {
+    DEFAULT_DIGITS
+}
+
+fn default_period() -> u64 {
+    DEFAULT_PERIOD
+}
+
+fn is_zero(value: &u64) -> bool {
+    *value == 0
+}
+
+/// Which part of a sorted result list to return: at most `limit` entries after skipping `offset`.
+/// The default is everything.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Page {
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+/// Which entries [`TotpDatabase::get_entries_page`] returns. The default is all of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EntryFilter<'a> {
+    /// Only entries from this issuer, ignoring case
+    pub issuer: Option<&'a str>,
+    /// Only entries created at or after this time
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    /// Only entries that haven't expired by this time
+    pub unexpired_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// How [`TotpDatabase::search_entries`] compares the query with names and issuers. Every mode
+/// ignores ASCII case.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SearchMode {
+    /// The query appears anywhere, so `git` also finds `digit-bank`
+    #[default]
+    Contains,
+    /// The name or issuer starts with the query
+    Prefix,
+    /// The whole name or issuer equals the query
+    Exact,
+}
+
+/// The order [`TotpDatabase::get_entries_page`] returns entries in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    #[default]
+    Name,
+    /// Oldest first
+    Created,
+    /// The manual order set with [`TotpDatabase::move_entry`]
+    Position,
+}
+
+impl SortOrder {
+    pub const ALL: [Self; 3] = [Self::Name, Self::Created, Self::Position];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|order| order.as_str().eq_ignore_ascii_case(value))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Created => "created",
+            Self::Position => "position",
+        }
+    }
+
+    // Favorites always come first
+    fn order_by(self) -> &'static str {
+        match self {
+            Self::Name => "is_favorite DESC, name",
+            Self::Created => "is_favorite DESC, created_at, id",
+            Self::Position => "is_favorite DESC, position, name",
+        }
+    }
+}
+
+/// Where [`TotpDatabase::move_entry`] puts an entry in the manual order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Move {
+    Up,
+    Down,
+    /// A 1-based position; values past the end move the entry to the end
+    To(usize),
+}
+
+/// A page of entries together with how many matched in total.
+#[derive(Debug, Clone)]
+pub struct EntryPage {
+    pub entries: Vec<TotpEntry>,
+    pub total: usize,
+}
+
+// Columns read by `entry_from_row`, in order
+const ENTRY_COLUMNS: &str = "id, name, secret, issuer, created_at, algorithm, digits, period, account, truncation_offset, is_favorite, login_url, backup_codes, epoch, expires_at";
+
+/// An entry moved to the recycle bin by [`TotpDatabase::delete_entry`].
+#[derive(Debug, Clone)]
+pub struct DeletedEntry {
+    pub entry: TotpEntry,
+    pub deleted_at: String,
+}
+
+/// An overview of the vault, as returned by [`TotpDatabase::get_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VaultStats {
+    pub count: usize,
+    /// Name and `created_at` of the first and the most recently added entry
+    pub oldest: Option<(String, String)>,
+    pub newest: Option<(String, String)>,
+    /// How many entries use each algorithm, digit count and period, most common first
+    pub algorithms: Vec<(HashAlgorithm, usize)>,
+    pub digits: Vec<(u32, usize)>,
+    pub periods: Vec<(u64, usize)>,
+}
+
+/// One row of the opt-in usage log written by [`TotpDatabase::log_usage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UsageRecord {
+    pub name: String,
+    /// The command that produced the code, e.g. `get` or `copy`
+    pub action: String,
+    pub used_at: String,
+}
+
+// Schema changes applied in order on top of the original `totp_entries` table.
+// `PRAGMA user_version` records how many have run, so each one executes exactly once.
+const MIGRATIONS: &[&str] = &[
+    // 1: recycle bin for soft-deleted entries
+    "CREATE TABLE deleted_entries (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        secret TEXT NOT NULL,
+        issuer TEXT,
+        created_at TEXT NOT NULL,
+        deleted_at TEXT NOT NULL
+    )",
+    // 2: per-entry code parameters
+    "ALTER TABLE totp_entries ADD COLUMN algorithm TEXT NOT NULL DEFAULT 'SHA1';
+     ALTER TABLE totp_entries ADD COLUMN digits INTEGER NOT NULL DEFAULT 6;
+     ALTER TABLE totp_entries ADD COLUMN period INTEGER NOT NULL DEFAULT 30;
+     ALTER TABLE deleted_entries ADD COLUMN algorithm TEXT NOT NULL DEFAULT 'SHA1';
+     ALTER TABLE deleted_entries ADD COLUMN digits INTEGER NOT NULL DEFAULT 6;
+     ALTER TABLE deleted_entries ADD COLUMN period INTEGER NOT NULL DEFAULT 30;",
+    // 3: account label, kept apart from the unique name
+    "ALTER TABLE totp_entries ADD COLUMN account TEXT;
+     ALTER TABLE deleted_entries ADD COLUMN account TEXT;",
+    // 4: fixed truncation offset for legacy tokens
+    "ALTER TABLE totp_entries ADD COLUMN truncation_offset INTEGER;
+     ALTER TABLE deleted_entries ADD COLUMN truncation_offset INTEGER;",
+    // 5: opt-in log of generated and copied codes
+    "CREATE TABLE usage_log (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        name TEXT NOT NULL,
+        action TEXT NOT NULL,
+        used_at TEXT NOT NULL
+    )",
+    // 6: manual ordering, starting out alphabetical as entries were listed until now
+    "ALTER TABLE totp_entries ADD COLUMN position INTEGER NOT NULL DEFAULT 0;
+     UPDATE totp_entries SET position = (SELECT COUNT(*) FROM totp_entries AS earlier WHERE earlier.name <= totp_entries.name);",
+    // 7: favorites pinned to the top of lists
+    "ALTER TABLE totp_entries ADD COLUMN is_favorite INTEGER NOT NULL DEFAULT 0;
+     ALTER TABLE deleted_entries ADD COLUMN is_favorite INTEGER NOT NULL DEFAULT 0;",
+    // 8: login page for `open`
+    "ALTER TABLE totp_entries ADD COLUMN login_url TEXT;
+     ALTER TABLE deleted_entries ADD COLUMN login_url TEXT;",
+    // 9: recovery codes, one per line
+    "ALTER TABLE totp_entries ADD COLUMN backup_codes TEXT;
+     ALTER TABLE deleted_entries ADD COLUMN backup_codes TEXT;",
+    // 10: custom T0 for the few systems that don't count from the Unix epoch
+    "ALTER TABLE totp_entries ADD COLUMN epoch INTEGER NOT NULL DEFAULT 0;
+     ALTER TABLE deleted_entries ADD COLUMN epoch INTEGER NOT NULL DEFAULT 0;",
+    // 11: when a temporary entry expires
+    "ALTER TABLE totp_entries ADD COLUMN expires_at TEXT;
+     ALTER TABLE deleted_entries ADD COLUMN expires_at TEXT;",
+];
+
+// The setters below ignore case in `?2` like `get_entry_by_name`, but change only one entry,
+// preferring an exact match, when names differ only in case
+const ONE_ENTRY_BY_NAME: &str =
+    "id = (SELECT id FROM totp_entries WHERE name = ?2 COLLATE NOCASE ORDER BY name = ?2 DESC, id LIMIT 1)";
+
+// New and restored entries go to the end of the manual order
+const NEXT_POSITION: &str = "(SELECT COALESCE(MAX(position), 0) + 1 FROM totp_entries)";
+
+/// The longest entry name accepted for new entries, in characters. Longer names push the code
+/// out of view in `list` and `loop` tables.
+pub const MAX_NAME_LENGTH: usize = 64;
+
+/// Checks a name before a new entry is stored: not blank, at most [`MAX_NAME_LENGTH`]
+/// characters and free of control characters, which break table output.
+pub fn validate_name(name: &str) -> std::result::Result<(), String> {
+    if name.trim().is_empty() {
+        return Err("name is empty".to_string());
+    }
+    if name.chars().any(is_unprintable) {
+        return Err("name contains control characters such as line breaks".to_string());
+    }
+    let length = name.chars().count();
+    if length > MAX_NAME_LENGTH {
+        return Err(format!("name is {} characters long, the limit is {}", length, MAX_NAME_LENGTH));
+    }
+    Ok(())
+}
+
+/// `name` made acceptable to [`validate_name`] where possible: line breaks and tabs become
+/// spaces, other control characters are dropped, and the result is trimmed and shortened to
+/// [`MAX_NAME_LENGTH`] characters. A name with nothing printable stays invalid.
+pub fn sanitize_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .filter_map(|c| match c {
+            c if c.is_whitespace() && c.is_control() => Some(' '),
+            c if is_unprintable(c) => None,
+            c => Some(c),
+        })
+        .collect();
+    cleaned.trim().chars().take(MAX_NAME_LENGTH).collect::<String>().trim_end().to_string()
+}
+
+// Control characters, plus the invisible formatting characters (zero-width spaces, bidi
+// overrides) that can make one name look like another
+fn is_unprintable(c: char) -> bool {
+    c.is_control() || matches!(c, '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' | '\u{FEFF}')
+}
+
+/// Whether an insert failed because an entry with the same name already exists.
+pub fn is_duplicate(error: &Error) -> bool {
+    matches!(error, Error::DuplicateName(_))
+}
+
+// A UNIQUE violation on insert means the name is taken; anything else is passed through
+fn name_taken(error: rusqlite::Error, name: &str) -> Error {
+    match error {
+        rusqlite::Error::SqliteFailure(e, _) if e.code == rusqlite::ErrorCode::ConstraintViolation => {
+            Error::DuplicateName(name.to_string())
+        }
+        error => Error::Database(error),
+    }
+}
+
+/// How long a write waits for another process holding the lock before giving up. SQLite's busy
+/// handler retries with short sleeps during this time.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Database management. This is a pure data layer: it reports outcomes through return values and
+// never prints, so the command layer decides what (if anything) the user sees.
 pub struct TotpDatabase {
     conn: Connection,
 }
 
 impl TotpDatabase {
-    pub(crate) fn new(db_path: &str) -> SqliteResult<Self> {
+    /// Opens (or creates) the database at `db_path` and brings its schema up to date.
+    ///
+    /// The file is switched to WAL mode so a long-running `loop` can keep reading while another
+    /// process writes, and writers wait up to [`BUSY_TIMEOUT`] for each other instead of failing
+    /// with `SQLITE_BUSY`.
+    pub fn new(db_path: impl AsRef<Path>) -> Result<Self> {
+        log::debug!("Opening database {}", db_path.as_ref().display());
         let conn = Connection::open(db_path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+        Self::from_connection(conn)
+    }
+
+    /// Opens a private database that lives only as long as the returned value, for tests and
+    /// throwaway sessions. The schema is set up exactly as for a file.
+    pub fn in_memory() -> Result<Self> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
 
+    fn from_connection(conn: Connection) -> Result<Self> {
         conn.execute(
             "CREATE TABLE IF NOT EXISTS totp_entries (
                 id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -30,36 +362,139 @@ impl TotpDatabase {
             [],
         )?;
 
+        migrate(&conn)?;
+
         Ok(Self { conn })
     }
 
-    pub(crate) fn add_entry(&self, entry: &TotpEntry) -> SqliteResult<()> {
+    /// Inserts `entry`, ignoring its `id` and `created_at`. Fails with
+    /// [`Error::DuplicateName`] if the name is taken.
+    pub fn add_entry(&self, entry: &TotpEntry) -> Result<()> {
         let now = chrono::Utc::now().to_rfc3339();
 
         self.conn.execute(
-            "INSERT INTO totp_entries (name, secret, issuer, created_at) VALUES (?1, ?2, ?3, ?4)",
-            [&entry.name, &entry.secret, &entry.issuer.as_deref().unwrap_or("").to_string(), &now],
-        )?;
+            &format!(
+                "INSERT INTO totp_entries (name, secret, issuer, created_at, algorithm, digits, period, account, truncation_offset, is_favorite, login_url, backup_codes, epoch, expires_at, position)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, {})",
+                NEXT_POSITION
+            ),
+            params![
+                entry.name,
+                entry.secret,
+                entry.issuer.as_deref().unwrap_or(""),
+                now,
+                entry.algorithm.as_str(),
+                entry.digits,
+                entry.period,
+                entry.account,
+                entry.truncation_offset,
+                entry.favorite,
+                entry.login_url,
+                join_codes(&entry.backup_codes),
+                entry.epoch,
+                entry.expires_at.map(|expires_at| expires_at.to_rfc3339()),
+            ],
+        ).map_err(|e| name_taken(e, &entry.name))?;
 
-        println!("✅ Added TOTP entry: {}", entry.name);
         Ok(())
     }
 
-    pub(crate) fn get_all_entries(&self) -> SqliteResult<Vec<TotpEntry>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, name, secret, issuer, created_at FROM totp_entries ORDER BY name"
+    pub fn get_all_entries(&self) -> Result<Vec<TotpEntry>> {
+        Ok(self.get_entries_page(&EntryFilter::default(), SortOrder::Name, Page::default())?.entries)
+    }
+
+    pub fn get_entries_by_issuer(&self, issuer: &str) -> Result<Vec<TotpEntry>> {
+        let filter = EntryFilter { issuer: Some(issuer), ..EntryFilter::default() };
+        Ok(self.get_entries_page(&filter, SortOrder::Name, Page::default())?.entries)
+    }
+
+    /// One page of the entries matching `filter`, in `order`.
+    pub fn get_entries_page(&self, filter: &EntryFilter, order: SortOrder, page: Page) -> Result<EntryPage> {
+        let (condition, values) = filter_condition(filter);
+        let params: Vec<&dyn ToSql> = values.iter().map(|value| value as &dyn ToSql).collect();
+        self.query_page(&condition, &params, order, page)
+    }
+
+    /// How many entries matching `filter` have expired by `at`, such as the ones an
+    /// `unexpired_at` filter of that time leaves out. Counted in one query, so it stays right
+    /// while another process adds or deletes entries.
+    pub fn count_expired(&self, filter: &EntryFilter, at: chrono::DateTime<chrono::Utc>) -> Result<usize> {
+        let (condition, mut values) = filter_condition(&EntryFilter { unexpired_at: None, ..*filter });
+        values.push(at.to_rfc3339());
+        let count: i64 = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM totp_entries WHERE {} AND expires_at IS NOT NULL AND expires_at <= ?{}",
+                condition,
+                values.len()
+            ),
+            rusqlite::params_from_iter(&values),
+            |row| row.get(0),
         )?;
+        Ok(count as usize)
+    }
+
+    pub fn get_entry_by_name(&self, name: &str) -> Result<Option<TotpEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM totp_entries WHERE name COLLATE NOCASE = ?1", ENTRY_COLUMNS
+        ))?;
+
+        let mut entries = stmt.query_map([name], entry_from_row)?;
+
+        match entries.next() {
+            Some(entry) => Ok(Some(entry?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Like [`get_entry_by_name`](Self::get_entry_by_name), but the issuer must match as well
+    /// (both ignoring case).
+    pub fn get_entry_by_name_and_issuer(&self, name: &str, issuer: &str) -> Result<Option<TotpEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM totp_entries WHERE name COLLATE NOCASE = ?1 AND issuer COLLATE NOCASE = ?2", ENTRY_COLUMNS
+        ))?;
+
+        let mut entries = stmt.query_map([name, issuer], entry_from_row)?;
+
+        match entries.next() {
+            Some(entry) => Ok(Some(entry?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Moves an entry to the recycle bin, from where it can be restored or purged. Fails with
+    /// [`Error::EntryNotFound`] if there is no entry called exactly `name`.
+    pub fn delete_entry(&self, name: &str) -> Result<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        let tx = self.conn.unchecked_transaction()?;
+
+        tx.execute(
+            "INSERT INTO deleted_entries (name, secret, issuer, created_at, algorithm, digits, period, account, truncation_offset, is_favorite, login_url, backup_codes, epoch, expires_at, deleted_at)
+             SELECT name, secret, issuer, created_at, algorithm, digits, period, account, truncation_offset, is_favorite, login_url, backup_codes, epoch, expires_at, ?2 FROM totp_entries WHERE name = ?1",
+            [name, &now],
+        )?;
+        let rows_affected = tx.execute(
+            "DELETE FROM totp_entries WHERE name = ?1",
+            [name],
+        )?;
+        if rows_affected == 0 {
+            return Err(Error::EntryNotFound(name.to_string()));
+        }
+
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_deleted_entries(&self) -> Result<Vec<DeletedEntry>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {}, deleted_at FROM deleted_entries
+             ORDER BY name, deleted_at DESC",
+            ENTRY_COLUMNS
+        ))?;
 
         let entries = stmt.query_map([], |row| {
-            Ok(TotpEntry {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                secret: row.get(2)?,
-                issuer: {
-                    let issuer: String = row.get(3)?;
-                    if issuer.is_empty() { None } else { Some(issuer) }
-                },
-                created_at: row.get(4)?,
+            Ok(DeletedEntry {
+                entry: entry_from_row(row)?,
+                deleted_at: row.get("deleted_at")?,
             })
         })?;
 
@@ -71,99 +506,718 @@ impl TotpDatabase {
         Ok(result)
     }
 
-    pub(crate) fn get_entry_by_name(&self, name: &str) -> SqliteResult<Option<TotpEntry>> {
-        let mut stmt = self.conn.prepare(
-            "SELECT id, name, secret, issuer, created_at FROM totp_entries WHERE name COLLATE NOCASE = ?1"
+    /// Moves the most recently deleted entry called `name` back out of the recycle bin and
+    /// returns its stored name. Fails with [`Error::EntryNotFound`] if the recycle bin has no
+    /// such entry, or [`Error::DuplicateName`] if a live entry already uses that name.
+    pub fn restore_entry(&self, name: &str) -> Result<String> {
+        let tx = self.conn.unchecked_transaction()?;
+
+        let deleted: Option<(i64, String)> = tx.query_row(
+            "SELECT id, name FROM deleted_entries WHERE name = ?1 COLLATE NOCASE
+             ORDER BY deleted_at DESC LIMIT 1",
+            [name],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        ).optional()?;
+
+        let Some((id, restored_name)) = deleted else {
+            return Err(Error::EntryNotFound(name.to_string()));
+        };
+
+        tx.execute(
+            &format!(
+                "INSERT INTO totp_entries (name, secret, issuer, created_at, algorithm, digits, period, account, truncation_offset, is_favorite, login_url, backup_codes, epoch, expires_at, position)
+                 SELECT name, secret, issuer, created_at, algorithm, digits, period, account, truncation_offset, is_favorite, login_url, backup_codes, epoch, expires_at, {} FROM deleted_entries WHERE id = ?1",
+                NEXT_POSITION
+            ),
+            [id],
+        ).map_err(|e| name_taken(e, &restored_name))?;
+        tx.execute("DELETE FROM deleted_entries WHERE id = ?1", [id])?;
+
+        tx.commit()?;
+        Ok(restored_name)
+    }
+
+    /// Permanently removes soft-deleted entries, either all of them or those called `name`.
+    pub fn purge_deleted(&self, name: Option<&str>) -> Result<usize> {
+        let purged = match name {
+            Some(name) => self.conn.execute(
+                "DELETE FROM deleted_entries WHERE name = ?1 COLLATE NOCASE",
+                [name],
+            )?,
+            None => self.conn.execute("DELETE FROM deleted_entries", [])?,
+        };
+        Ok(purged)
+    }
+
+    /// Changes the secret and/or issuer of an entry. Fails with [`Error::EntryNotFound`] if
+    /// there is no such entry.
+    pub fn update_entry(&self, name: &str, new_secret: Option<&str>, new_issuer: Option<&str>) -> Result<()> {
+        let entry = self.get_entry_by_name(name)?.ok_or_else(|| Error::EntryNotFound(name.to_string()))?;
+        let secret = new_secret.unwrap_or(&entry.secret);
+        let issuer = new_issuer.or(entry.issuer.as_deref()).unwrap_or("");
+
+        // Use the stored name, since the lookup above ignores case
+        self.conn.execute(
+            "UPDATE totp_entries SET secret = ?1, issuer = ?2 WHERE name = ?3",
+            [secret, issuer, entry.name.as_str()],
         )?;
+        Ok(())
+    }
 
-        let mut entries = stmt.query_map([name], |row| {
-            Ok(TotpEntry {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                secret: row.get(2)?,
-                issuer: {
-                    let issuer: String = row.get(3)?;
-                    if issuer.is_empty() { None } else { Some(issuer) }
-                },
-                created_at: row.get(4)?,
-            })
-        })?;
+    /// Runs `f` in one transaction, so the changes it makes (e.g. several setters for one
+    /// `update`) are kept together, or not at all if it fails.
+    pub fn transaction<T>(&self, f: impl FnOnce(&Self) -> Result<T>) -> Result<T> {
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+        // Dropping `tx` on the error path rolls everything back
+        let result = f(self)?;
+        tx.commit()?;
+        Ok(result)
+    }
 
-        match entries.next() {
-            Some(entry) => Ok(Some(entry?)),
-            None => Ok(None),
+    /// Replaces everything but the id, creation time and list position of the entry named
+    /// exactly `entry.name` with the values from `entry`, as `import --overwrite` does. The
+    /// position is this database's own ordering, so the entry keeps its place. Fails with
+    /// [`Error::EntryNotFound`] if there is no such entry.
+    pub fn overwrite_entry(&self, entry: &TotpEntry) -> Result<()> {
+        let rows_affected = self.conn.execute(
+            "UPDATE totp_entries
+             SET secret = ?1, issuer = ?2, algorithm = ?3, digits = ?4, period = ?5, account = ?6, truncation_offset = ?7, login_url = ?8, backup_codes = ?9, epoch = ?10, expires_at = ?11, is_favorite = ?12
+             WHERE name = ?13",
+            params![
+                entry.secret,
+                entry.issuer.as_deref().unwrap_or(""),
+                entry.algorithm.as_str(),
+                entry.digits,
+                entry.period,
+                entry.account,
+                entry.truncation_offset,
+                entry.login_url,
+                join_codes(&entry.backup_codes),
+                entry.epoch,
+                entry.expires_at.map(|expires_at| expires_at.to_rfc3339()),
+                entry.favorite,
+                entry.name,
+            ],
+        )?;
+        if rows_affected == 0 {
+            return Err(Error::EntryNotFound(entry.name.clone()));
         }
+        Ok(())
     }
 
-    pub(crate) fn delete_entry(&self, name: &str) -> SqliteResult<bool> {
+    /// Sets or clears (`None`) the fixed truncation offset. Fails with [`Error::EntryNotFound`]
+    /// if there is no such entry.
+    pub fn set_truncation_offset(&self, name: &str, offset: Option<u8>) -> Result<()> {
         let rows_affected = self.conn.execute(
-            "DELETE FROM totp_entries WHERE name = ?1",
-            [name],
+            &format!("UPDATE totp_entries SET truncation_offset = ?1 WHERE {}", ONE_ENTRY_BY_NAME),
+            params![offset, name],
         )?;
+        if rows_affected == 0 {
+            return Err(Error::EntryNotFound(name.to_string()));
+        }
+        Ok(())
+    }
 
-        Ok(rows_affected > 0)
+    /// Pins an entry to the top of lists, or unpins it. Fails with [`Error::EntryNotFound`] if
+    /// there is no such entry.
+    pub fn set_favorite(&self, name: &str, favorite: bool) -> Result<()> {
+        let rows_affected = self.conn.execute(
+            &format!("UPDATE totp_entries SET is_favorite = ?1 WHERE {}", ONE_ENTRY_BY_NAME),
+            params![favorite, name],
+        )?;
+        if rows_affected == 0 {
+            return Err(Error::EntryNotFound(name.to_string()));
+        }
+        Ok(())
     }
 
-    pub(crate) fn update_entry(&self, name: &str, new_secret: Option<&str>, new_issuer: Option<&str>) -> SqliteResult<bool> {
-        let entry = self.get_entry_by_name(name)?;
+    /// Sets or clears (`None`) the login page opened by `open`. Fails with
+    /// [`Error::EntryNotFound`] if there is no such entry.
+    pub fn set_login_url(&self, name: &str, url: Option<&str>) -> Result<()> {
+        let rows_affected = self.conn.execute(
+            &format!("UPDATE totp_entries SET login_url = ?1 WHERE {}", ONE_ENTRY_BY_NAME),
+            params![url, name],
+        )?;
+        if rows_affected == 0 {
+            return Err(Error::EntryNotFound(name.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Sets or clears (`None`) when an entry expires. Fails with [`Error::EntryNotFound`] if
+    /// there is no such entry.
+    pub fn set_expires_at(&self, name: &str, expires_at: Option<chrono::DateTime<chrono::Utc>>) -> Result<()> {
+        let rows_affected = self.conn.execute(
+            &format!("UPDATE totp_entries SET expires_at = ?1 WHERE {}", ONE_ENTRY_BY_NAME),
+            params![expires_at.map(|expires_at| expires_at.to_rfc3339()), name],
+        )?;
+        if rows_affected == 0 {
+            return Err(Error::EntryNotFound(name.to_string()));
+        }
+        Ok(())
+    }
 
-        if let Some(entry) = entry {
-            let secret = new_secret.unwrap_or(&entry.secret);
-            let issuer = new_issuer.or(entry.issuer.as_deref()).unwrap_or("");
+    /// Replaces the backup codes of an entry; an empty list removes them. Fails with
+    /// [`Error::EntryNotFound`] if there is no such entry.
+    pub fn set_backup_codes(&self, name: &str, codes: &[String]) -> Result<()> {
+        let rows_affected = self.conn.execute(
+            &format!("UPDATE totp_entries SET backup_codes = ?1 WHERE {}", ONE_ENTRY_BY_NAME),
+            params![join_codes(codes), name],
+        )?;
+        if rows_affected == 0 {
+            return Err(Error::EntryNotFound(name.to_string()));
+        }
+        Ok(())
+    }
 
-            let rows_affected = self.conn.execute(
-                "UPDATE totp_entries SET secret = ?1, issuer = ?2 WHERE name = ?3",
-                [secret, issuer, name],
-            )?;
+    /// Moves an entry within the manual order used by [`SortOrder::Position`] and returns its new
+    /// 1-based position. Moving the first entry up or the last one down leaves it in place.
+    pub fn move_entry(&self, name: &str, to: Move) -> Result<usize> {
+        let tx = self.conn.unchecked_transaction()?;
 
-            Ok(rows_affected > 0)
-        } else {
-            Ok(false)
+        let mut stmt = tx.prepare("SELECT id, name FROM totp_entries ORDER BY position, name")?;
+        let mut order: Vec<(i64, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<SqliteResult<_>>()?;
+        drop(stmt);
+
+        // An exact match wins over one that only differs in case
+        let from = order
+            .iter()
+            .position(|(_, stored)| stored == name)
+            .or_else(|| order.iter().position(|(_, stored)| stored.eq_ignore_ascii_case(name)))
+            .ok_or_else(|| Error::EntryNotFound(name.to_string()))?;
+        let target = match to {
+            Move::Up => from.saturating_sub(1),
+            Move::Down => (from + 1).min(order.len() - 1),
+            Move::To(position) => position.saturating_sub(1).min(order.len() - 1),
+        };
+        let moved = order.remove(from);
+        order.insert(target, moved);
+
+        // Renumbering everything also closes the gaps left by deleted entries
+        for (index, (id, _)) in order.iter().enumerate() {
+            tx.execute("UPDATE totp_entries SET position = ?1 WHERE id = ?2", params![index + 1, id])?;
         }
+        tx.commit()?;
+        Ok(target + 1)
+    }
+
+    pub fn search_entries(&self, query: &str, mode: SearchMode) -> Result<Vec<TotpEntry>> {
+        Ok(self.search_entries_page(query, mode, Page::default())?.entries)
     }
 
-    pub(crate) fn search_entries(&self, query: &str) -> SqliteResult<Vec<TotpEntry>> {
+    /// One page of the entries whose name or issuer matches `query` as `mode` says, sorted by name.
+    pub fn search_entries_page(&self, query: &str, mode: SearchMode, page: Page) -> Result<EntryPage> {
+        // `%` and `_` in the query are literal characters, not wildcards
+        let escaped = query.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_");
+        let like = "name LIKE ?1 ESCAPE '\\' OR issuer LIKE ?1 ESCAPE '\\'";
+        match mode {
+            SearchMode::Contains => self.query_page(like, &[&format!("%{}%", escaped)], SortOrder::Name, page),
+            SearchMode::Prefix => self.query_page(like, &[&format!("{}%", escaped)], SortOrder::Name, page),
+            SearchMode::Exact => {
+                self.query_page("name = ?1 COLLATE NOCASE OR issuer = ?1 COLLATE NOCASE", &[&query], SortOrder::Name, page)
+            }
+        }
+    }
+
+    // Entries matching `filter` (a WHERE clause using ?1.. for `filter_params`), sorted by `order`,
+    // with LIMIT/OFFSET applied in SQL. The total is only counted separately when paging.
+    fn query_page(&self, filter: &str, filter_params: &[&dyn ToSql], order: SortOrder, page: Page) -> Result<EntryPage> {
+        let limit_index = filter_params.len() + 1;
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {} FROM totp_entries
+             WHERE {}
+             ORDER BY {}
+             LIMIT ?{} OFFSET ?{}",
+            ENTRY_COLUMNS, filter, order.order_by(), limit_index, limit_index + 1
+        ))?;
+
+        // A negative LIMIT means no limit in SQLite
+        let limit = page.limit.map_or(-1, |limit| limit as i64);
+        let offset = page.offset as i64;
+        let mut query_params = filter_params.to_vec();
+        query_params.push(&limit);
+        query_params.push(&offset);
+
+        let entries = stmt.query_map(query_params.as_slice(), entry_from_row)?;
+
+        let mut result = Vec::new();
+        for entry in entries {
+            result.push(entry?);
+        }
+
+        let total = if page == Page::default() {
+            result.len()
+        } else {
+            let count: i64 = self.conn.query_row(
+                &format!("SELECT COUNT(*) FROM totp_entries WHERE {}", filter),
+                filter_params,
+                |row| row.get(0),
+            )?;
+            count as usize
+        };
+
+        Ok(EntryPage { entries: result, total })
+    }
+
+    /// Records that a code for `name` was produced by `action`. Callers only do this when the
+    /// user has turned the history on.
+    pub fn log_usage(&self, name: &str, action: &str) -> Result<()> {
+        let now = chrono::Utc::now().to_rfc3339();
+        self.conn.execute(
+            "INSERT INTO usage_log (name, action, used_at) VALUES (?1, ?2, ?3)",
+            [name, action, &now],
+        )?;
+        Ok(())
+    }
+
+    /// The usage log, newest first, optionally for one entry (case-insensitive).
+    pub fn get_usage_log(&self, name: Option<&str>, limit: Option<usize>) -> Result<Vec<UsageRecord>> {
+        // LIMIT -1 means no limit in SQLite
+        let limit = limit.map_or(-1, |limit| limit as i64);
         let mut stmt = self.conn.prepare(
-            "SELECT id, name, secret, issuer, created_at FROM totp_entries
-             WHERE name LIKE ?1 OR issuer LIKE ?1
-             ORDER BY name"
+            "SELECT name, action, used_at FROM usage_log
+             WHERE ?1 IS NULL OR name = ?1 COLLATE NOCASE
+             ORDER BY id DESC LIMIT ?2",
         )?;
 
-        let search_pattern = format!("%{}%", query);
-        let entries = stmt.query_map([&search_pattern], |row| {
-            Ok(TotpEntry {
-                id: Some(row.get(0)?),
-                name: row.get(1)?,
-                secret: row.get(2)?,
-                issuer: {
-                    let issuer: String = row.get(3)?;
-                    if issuer.is_empty() { None } else { Some(issuer) }
-                },
-                created_at: row.get(4)?,
+        let records = stmt.query_map(params![name, limit], |row| {
+            Ok(UsageRecord {
+                name: row.get(0)?,
+                action: row.get(1)?,
+                used_at: row.get(2)?,
             })
         })?;
 
         let mut result = Vec::new();
-        for entry in entries {
-            result.push(entry?);
+        for record in records {
+            result.push(record?);
         }
 
         Ok(result)
     }
 
-    pub(crate) fn get_stats(&self) -> SqliteResult<(i64, Option<String>)> {
+    pub fn get_stats(&self) -> Result<VaultStats> {
         let count: i64 = self.conn.query_row(
             "SELECT COUNT(*) FROM totp_entries",
             [],
             |row| row.get(0),
         )?;
 
-        let oldest: Option<String> = self.conn.query_row(
-            "SELECT MIN(created_at) FROM totp_entries",
-            [],
-            |row| row.get(0),
-        ).ok();
+        // Ties (entries added in the same instant) go to the lower id, i.e. insertion order
+        let dated = |order: &str| -> SqliteResult<Option<(String, String)>> {
+            self.conn.query_row(
+                &format!("SELECT name, created_at FROM totp_entries ORDER BY created_at {0}, id {0} LIMIT 1", order),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            ).optional()
+        };
+
+        let algorithms = self
+            .breakdown::<String>("algorithm")?
+            .into_iter()
+            .map(|(algorithm, count)| match HashAlgorithm::parse(&algorithm) {
+                Some(parsed) => Ok((parsed, count)),
+                None => Err(rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    format!("unknown algorithm: {}", algorithm).into(),
+                )),
+            })
+            .collect::<SqliteResult<_>>()?;
+
+        Ok(VaultStats {
+            count: count as usize,
+            oldest: dated("ASC")?,
+            newest: dated("DESC")?,
+            algorithms,
+            digits: self.breakdown("digits")?,
+            periods: self.breakdown("period")?,
+        })
+    }
+
+    // Number of entries per distinct value of `column`, most common first
+    fn breakdown<T: rusqlite::types::FromSql>(&self, column: &str) -> SqliteResult<Vec<(T, usize)>> {
+        let mut stmt = self.conn.prepare(&format!(
+            "SELECT {0}, COUNT(*) FROM totp_entries GROUP BY {0} ORDER BY COUNT(*) DESC, {0}",
+            column
+        ))?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? as usize)))?;
+
+        let mut result = Vec::new();
+        for row in rows {
+            result.push(row?);
+        }
+
+        Ok(result)
+    }
+
+    /// Writes the whole database (every table, column and row, plus the schema version) as SQL
+    /// text that rebuilds it, either with [`restore_dump`](Self::restore_dump) or with the
+    /// `sqlite3` shell.
+    pub fn dump(&self) -> Result<String> {
+        let version: usize = self.conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        let mut sql = format!("-- totp-console database dump, schema version {}\nBEGIN TRANSACTION;\n", version);
+
+        // `sqlite_sequence` is created along with the first AUTOINCREMENT table, so only its
+        // rows are dumped, after the tables they count for
+        let tables = self.schema_objects("type = 'table' AND name NOT LIKE 'sqlite_%'")?;
+        for (_, name, create) in &tables {
+            sql.push_str(create);
+            sql.push_str(";\n");
+            self.dump_rows(name, &mut sql)?;
+        }
+        if self.conn.query_row("SELECT COUNT(*) FROM sqlite_master WHERE name = 'sqlite_sequence'", [], |row| row.get::<_, i64>(0))? > 0 {
+            sql.push_str("DELETE FROM sqlite_sequence;\n");
+            self.dump_rows("sqlite_sequence", &mut sql)?;
+        }
+        for (_, _, create) in self.schema_objects("type IN ('index', 'trigger', 'view') AND sql IS NOT NULL")? {
+            sql.push_str(&create);
+            sql.push_str(";\n");
+        }
+
+        sql.push_str(&format!("PRAGMA user_version = {};\nCOMMIT;\n", version));
+        Ok(sql)
+    }
+
+    /// Replaces everything in the database with a dump from [`dump`](Self::dump) (or the
+    /// `sqlite3` shell's `.dump`) and returns how many entries it holds. Dumps made by older
+    /// versions are migrated to the current schema.
+    ///
+    /// The dump is first loaded into a scratch database, so one that fails to run, has no
+    /// entries table, comes from a newer version or holds unreadable entries is rejected with
+    /// [`Error::InvalidDump`] and leaves the database untouched. So is one that tries to reach
+    /// other database files with `ATTACH` or `VACUUM INTO`.
+    pub fn restore_dump(&self, sql: &str) -> Result<usize> {
+        let body = dump_body(sql);
+        let invalid = |e: rusqlite::Error| Error::InvalidDump(e.to_string());
+
+        // A dump is only meant to fill this database, never to reach other files: ATTACH is
+        // switched off, and running it inside a transaction also rules out `VACUUM INTO`
+        let scratch = Connection::open_in_memory()?;
+        scratch.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0)?;
+        let tx = Transaction::new_unchecked(&scratch, TransactionBehavior::Immediate)?;
+        tx.execute_batch(body).map_err(invalid)?;
+        tx.commit()?;
+        let has_entries = scratch
+            .query_row("SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'totp_entries'", [], |row| row.get::<_, i64>(0))?;
+        if has_entries == 0 {
+            return Err(Error::InvalidDump("it has no totp_entries table".to_string()));
+        }
+        let version: usize = scratch.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > MIGRATIONS.len() {
+            return Err(Error::InvalidDump(format!(
+                "it comes from a newer version (schema version {}, this one knows up to {})",
+                version,
+                MIGRATIONS.len()
+            )));
+        }
+        // The `sqlite3` shell's `.dump` leaves out `user_version`, so its dumps look like version 0
+        migrate(&scratch).map_err(|e| {
+            log::debug!("Migrating the dump failed: {}", e);
+            Error::InvalidDump(format!(
+                "its tables don't match the schema version it records ({}); a dump without `PRAGMA user_version` counts as version 0",
+                version
+            ))
+        })?;
+        let count = Self { conn: scratch }.get_all_entries().map_err(|e| match e {
+            Error::Database(e) => invalid(e),
+            e => e,
+        })?.len();
+
+        let attached = self.conn.set_limit(Limit::SQLITE_LIMIT_ATTACHED, 0)?;
+        let restored = self.replace_with_dump(body);
+        self.conn.set_limit(Limit::SQLITE_LIMIT_ATTACHED, attached)?;
+        restored?;
+
+        migrate(&self.conn)?;
+        Ok(count)
+    }
+
+    // Swaps every table for the dump's in one transaction; `restore_dump` has checked it already
+    fn replace_with_dump(&self, body: &str) -> Result<()> {
+        let tx = Transaction::new_unchecked(&self.conn, TransactionBehavior::Immediate)?;
+        // Indexes and triggers go with their tables; `sqlite_sequence` can't be dropped, but
+        // loses the rows of the tables dropped here
+        for (kind, name, _) in self.schema_objects("type IN ('table', 'view') AND name NOT LIKE 'sqlite_%'")? {
+            tx.execute_batch(&format!("DROP {} {}", kind.to_uppercase(), quote_identifier(&name)))?;
+        }
+        // As in the scratch database, a dump that doesn't set the version starts from 0
+        tx.pragma_update(None, "user_version", 0)?;
+        tx.execute_batch(body).map_err(|e| Error::InvalidDump(e.to_string()))?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    // `(type, name, sql)` of the schema objects matching `condition`, in creation order
+    fn schema_objects(&self, condition: &str) -> SqliteResult<Vec<(String, String, String)>> {
+        let mut stmt = self.conn.prepare(&format!("SELECT type, name, sql FROM sqlite_master WHERE {} ORDER BY rowid", condition))?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?;
+        rows.collect()
+    }
+
+    // One `INSERT` per row of `table`, with every column in table order
+    fn dump_rows(&self, table: &str, sql: &mut String) -> SqliteResult<()> {
+        let table = quote_identifier(table);
+        let mut stmt = self.conn.prepare(&format!("SELECT * FROM {} ORDER BY rowid", table))?;
+        let columns = stmt.column_count();
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let values = (0..columns).map(|i| row.get_ref(i).map(sql_literal)).collect::<SqliteResult<Vec<_>>>()?;
+            sql.push_str(&format!("INSERT INTO {} VALUES({});\n", table, values.join(",")));
+        }
+        Ok(())
+    }
+}
+
+// A dump's statements without the transaction around them, so `restore_dump` can run them in
+// its own. Both this crate's dumps and the `sqlite3` shell's wrap everything in one.
+fn dump_body(sql: &str) -> &str {
+    let mut body = sql.trim();
+    let mut rest = body;
+    // Only comments and pragmas may come before the `BEGIN`
+    while let Some(line) = rest.lines().next() {
+        let statement = line.trim().to_ascii_uppercase();
+        if statement == "BEGIN TRANSACTION;" || statement == "BEGIN;" {
+            body = &rest[line.len()..];
+            break;
+        }
+        if !(statement.is_empty() || statement.starts_with("--") || statement.starts_with("PRAGMA")) {
+            break;
+        }
+        rest = rest[line.len()..].trim_start();
+    }
+
+    // Comments may follow the `COMMIT` too
+    let mut trimmed = body.trim_end();
+    while let Some((before, last)) = trimmed.rsplit_once('\n')
+        && last.trim_start().starts_with("--")
+    {
+        trimmed = before.trim_end();
+    }
+
+    // Compared as bytes, since the last seven may start inside a multibyte character
+    match trimmed.len().checked_sub("COMMIT;".len()) {
+        Some(end) if trimmed.as_bytes()[end..].eq_ignore_ascii_case(b"COMMIT;") => &trimmed[..end],
+        _ => body,
+    }
+}
+
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+// A value as a SQL literal that reads back as exactly the same value and type
+fn sql_literal(value: rusqlite::types::ValueRef) -> String {
+    use rusqlite::types::ValueRef;
+
+    let hex = |bytes: &[u8]| bytes.iter().map(|byte| format!("{:02X}", byte)).collect::<String>();
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(value) => value.to_string(),
+        ValueRef::Real(value) if value.is_infinite() => if value > 0.0 { "1e999" } else { "-1e999" }.to_string(),
+        ValueRef::Real(value) => format!("{:?}", value),
+        // Text SQL can't spell literally (invalid UTF-8 or NUL) goes through a blob
+        ValueRef::Text(text) => match std::str::from_utf8(text) {
+            Ok(text) if !text.contains('\0') => format!("'{}'", text.replace('\'', "''")),
+            _ => format!("CAST(X'{}' AS TEXT)", hex(text)),
+        },
+        ValueRef::Blob(blob) => format!("X'{}'", hex(blob)),
+    }
+}
+
+// The `WHERE` condition selecting the entries `filter` matches, with its parameters numbered
+// from `?1`. Creation and expiry times are all stored as UTC RFC 3339 by `to_rfc3339`, so
+// comparing them as text orders them by time.
+fn filter_condition(filter: &EntryFilter) -> (String, Vec<String>) {
+    let mut conditions: Vec<String> = Vec::new();
+    let mut values: Vec<String> = Vec::new();
+    if let Some(issuer) = filter.issuer {
+        values.push(issuer.to_string());
+        conditions.push(format!("issuer = ?{} COLLATE NOCASE", values.len()));
+    }
+    if let Some(since) = filter.since {
+        values.push(since.to_rfc3339());
+        conditions.push(format!("created_at >= ?{}", values.len()));
+    }
+    if let Some(unexpired_at) = filter.unexpired_at {
+        values.push(unexpired_at.to_rfc3339());
+        conditions.push(format!("(expires_at IS NULL OR expires_at > ?{})", values.len()));
+    }
+
+    let condition = if conditions.is_empty() { "1".to_string() } else { conditions.join(" AND ") };
+    (condition, values)
+}
+
+// Maps a row selected with `ENTRY_COLUMNS`
+fn entry_from_row(row: &Row) -> SqliteResult<TotpEntry> {
+    Ok(TotpEntry {
+        id: Some(row.get(0)?),
+        name: row.get(1)?,
+        secret: row.get(2)?,
+        issuer: {
+            let issuer: String = row.get(3)?;
+            if issuer.is_empty() { None } else { Some(issuer) }
+        },
+        created_at: row.get(4)?,
+        algorithm: {
+            let algorithm: String = row.get(5)?;
+            HashAlgorithm::parse(&algorithm).ok_or_else(|| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    5,
+                    rusqlite::types::Type::Text,
+                    format!("unknown algorithm: {}", algorithm).into(),
+                )
+            })?
+        },
+        digits: row.get(6)?,
+        period: row.get(7)?,
+        account: row.get(8)?,
+        truncation_offset: row.get(9)?,
+        favorite: row.get(10)?,
+        login_url: row.get(11)?,
+        backup_codes: {
+            let codes: Option<String> = row.get(12)?;
+            codes.map(|codes| codes.lines().map(str::to_string).collect()).unwrap_or_default()
+        },
+        epoch: row.get(13)?,
+        expires_at: {
+            let expires_at: Option<String> = row.get(14)?;
+            expires_at
+                .map(|expires_at| {
+                    chrono::DateTime::parse_from_rfc3339(&expires_at)
+                        .map(|time| time.with_timezone(&chrono::Utc))
+                        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(14, rusqlite::types::Type::Text, e.into()))
+                })
+                .transpose()?
+        },
+    })
+}
+
+// Backup codes are stored one per line, and NULL when there are none
+fn join_codes(codes: &[String]) -> Option<String> {
+    if codes.is_empty() { None } else { Some(codes.join("\n")) }
+}
+
+fn migrate(conn: &Connection) -> SqliteResult<()> {
+    let version = |conn: &Connection| -> SqliteResult<usize> { conn.query_row("PRAGMA user_version", [], |row| row.get(0)) };
+    if version(conn)? >= MIGRATIONS.len() {
+        return Ok(());
+    }
+
+    // Take the write lock before re-reading the version, so two processes opening an old
+    // database at the same time don't both run the same migration
+    let tx = Transaction::new_unchecked(conn, TransactionBehavior::Immediate)?;
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(version(&tx)?) {
+        log::debug!("Applying schema migration {} of {}", index + 1, MIGRATIONS.len());
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+    }
+    tx.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_name() {
+        assert!(validate_name("GitHub (work)").is_ok());
+        assert!(validate_name(&"é".repeat(MAX_NAME_LENGTH)).is_ok());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LENGTH + 1)).unwrap_err().contains("65 characters"));
+        assert!(validate_name("  ").is_err());
+        assert!(validate_name("git\nhub").is_err());
+        assert!(validate_name("\u{1b}[31mred").is_err());
+        assert!(validate_name("admin\u{202E}txt").is_err());
+    }
+
+    #[test]
+    fn test_sql_literal() {
+        use rusqlite::types::ValueRef;
+
+        assert_eq!(sql_literal(ValueRef::Null), "NULL");
+        assert_eq!(sql_literal(ValueRef::Integer(-3)), "-3");
+        assert_eq!(sql_literal(ValueRef::Real(1.0)), "1.0");
+        assert_eq!(sql_literal(ValueRef::Real(f64::NEG_INFINITY)), "-1e999");
+        assert_eq!(sql_literal(ValueRef::Text(b"o'neil")), "'o''neil'");
+        assert_eq!(sql_literal(ValueRef::Text(b"a\0b")), "CAST(X'610062' AS TEXT)");
+        assert_eq!(sql_literal(ValueRef::Blob(&[0xab, 0x01])), "X'AB01'");
+    }
+
+    #[test]
+    fn test_dump_body() {
+        assert_eq!(dump_body("-- comment\nBEGIN TRANSACTION;\nCREATE TABLE t (a);\nCOMMIT;\n").trim(), "CREATE TABLE t (a);");
+        assert_eq!(dump_body("PRAGMA foreign_keys=OFF;\r\nBEGIN;\r\nSELECT 1;\r\ncommit;").trim(), "SELECT 1;");
+        assert_eq!(dump_body("CREATE TABLE t (a);"), "CREATE TABLE t (a);");
+        assert_eq!(dump_body("BEGIN;\nSELECT 1;\nCOMMIT;\n-- done\n-- 工作工\n").trim(), "SELECT 1;");
+        // A `BEGIN` after the first statement is left for SQLite to reject
+        assert!(dump_body("SELECT 1;\nBEGIN;\nSELECT 2;").contains("BEGIN"));
+    }
+
+    #[test]
+    fn test_sanitize_name() {
+        assert_eq!(sanitize_name(" git\nhub\t"), "git hub");
+        assert_eq!(sanitize_name("\u{1b}[31mred\u{7}"), "[31mred");
+        assert_eq!(sanitize_name("zero\u{200B}width"), "zerowidth");
+        assert_eq!(sanitize_name(&"a".repeat(100)).len(), MAX_NAME_LENGTH);
+        assert_eq!(sanitize_name("\r\n"), "");
+        for name in ["x\u{0}y", " padded ", &format!("{} b", "a".repeat(63))] {
+            assert!(validate_name(&sanitize_name(name)).is_ok(), "{:?}", name);
+        }
+    }
+
+    fn schema(db: &TotpDatabase) -> (usize, Vec<String>) {
+        let version = db.conn.query_row("PRAGMA user_version", [], |row| row.get(0)).unwrap();
+        let mut stmt = db.conn.prepare("SELECT sql FROM sqlite_master WHERE sql IS NOT NULL ORDER BY name").unwrap();
+        let tables = stmt.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect();
+        (version, tables)
+    }
+
+    #[test]
+    fn test_in_memory_schema_matches_file() {
+        let path = std::env::temp_dir().join(format!("totp-schema-{}.db", std::process::id()));
+        let file_db = TotpDatabase::new(path.to_str().unwrap()).unwrap();
+        let memory_db = TotpDatabase::in_memory().unwrap();
+
+        let (version, tables) = schema(&memory_db);
+        assert_eq!(version, MIGRATIONS.len());
+        assert_eq!(schema(&file_db), (version, tables));
+
+        drop(file_db);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_database_module_does_not_print() {
+        // Built from pieces so this test doesn't trip over its own source
+        let source = include_str!("database.rs");
+        for macro_name in ["print", "println", "eprint", "eprintln", "dbg"] {
+            let call = format!("{}!(", macro_name);
+            assert!(!source.contains(&call), "database.rs must not call {}", call);
+        }
+    }
+
+    #[test]
+    fn test_serialized_entry_omits_cleared_id() {
+        let entry = TotpEntry { name: "github".to_string(), secret: "JBSWY3DPEHPK3PXP".to_string(), ..TotpEntry::default() };
+        let json = serde_json::to_string(&entry).unwrap();
+        assert!(!json.contains("\"id\""));
+        assert_eq!(serde_json::from_str::<TotpEntry>(&json).unwrap().id, None);
+
+        // Older exports still carry their ids
+        let stored = TotpEntry { id: Some(7), ..entry };
+        assert!(serde_json::to_string(&stored).unwrap().contains("\"id\":7"));
+    }
+
+    #[test]
+    fn test_serialized_entry_only_mentions_favorites() {
+        let entry = TotpEntry { name: "github".to_string(), ..TotpEntry::default() };
+        assert!(!serde_json::to_string(&entry).unwrap().contains("favorite"));
 
-        Ok((count, oldest))
+        let favorite = TotpEntry { favorite: true, ..entry };
+        let json = serde_json::to_string(&favorite).unwrap();
+        assert!(json.contains("\"favorite\":true"));
+        assert!(serde_json::from_str::<TotpEntry>(&json).unwrap().favorite);
     }
 }
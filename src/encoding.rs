@@ -0,0 +1,100 @@
+use zeroize::Zeroizing;
+use crate::base32::{base32_decode, base32_encode, base32hex_decode};
+
+// How a secret is written when it's handed to `add` or `import`. Entries are always stored in
+// standard base32, so everything after onboarding only ever sees one encoding.
+
+/// The text encoding of a secret given on the command line or in a backup.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SecretEncoding {
+    /// RFC 4648 base32, as authenticator apps show it
+    #[default]
+    Base32,
+    /// RFC 4648 base32hex (`0-9A-V`)
+    Base32Hex,
+    /// The raw seed as hexadecimal digits, two per byte
+    Hex,
+}
+
+impl SecretEncoding {
+    pub const ALL: [Self; 3] = [Self::Base32, Self::Base32Hex, Self::Hex];
+
+    /// Parses an `--encoding` value, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|encoding| encoding.as_str().eq_ignore_ascii_case(name))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Base32 => "base32",
+            Self::Base32Hex => "base32hex",
+            Self::Hex => "hex",
+        }
+    }
+
+    pub fn decode(self, secret: &str) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+        match self {
+            Self::Base32 => Ok(base32_decode(secret)?),
+            Self::Base32Hex => Ok(base32hex_decode(secret)?),
+            Self::Hex => hex_decode(secret),
+        }
+    }
+
+    /// Rewrites `secret` in standard base32 for storage. Base32 input is returned as given, so
+    /// the usual validation still sees (and reports on) exactly what the user typed.
+    pub fn to_base32(self, secret: &str) -> anyhow::Result<String> {
+        match self {
+            Self::Base32 => Ok(secret.to_string()),
+            _ => Ok(base32_encode(&self.decode(secret)?)),
+        }
+    }
+}
+
+// Like base32, the digits may be grouped with spaces or hyphens and use either case; a `0x`
+// prefix is accepted as well
+fn hex_decode(input: &str) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let input = input.trim();
+    let input = input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")).unwrap_or(input);
+
+    let mut digits = Zeroizing::new(Vec::with_capacity(input.len()));
+    for c in input.chars().filter(|c| !c.is_whitespace() && *c != '-') {
+        match c.to_digit(16) {
+            Some(digit) => digits.push(digit as u8),
+            None => anyhow::bail!("Invalid hex character: {:?}", c),
+        }
+    }
+    if digits.len() % 2 != 0 {
+        anyhow::bail!("Invalid hex length: {} digits, expected two per byte", digits.len());
+    }
+
+    Ok(Zeroizing::new(digits.chunks(2).map(|pair| pair[0] << 4 | pair[1]).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_base32() {
+        assert_eq!(SecretEncoding::parse("Base32Hex"), Some(SecretEncoding::Base32Hex));
+        assert_eq!(SecretEncoding::parse("base64"), None);
+
+        // "foobar" in each alphabet (RFC 4648 section 10)
+        assert_eq!(SecretEncoding::Base32Hex.to_base32("CPNMUOJ1E8======").unwrap(), "MZXW6YTBOI");
+        assert_eq!(SecretEncoding::Base32.to_base32("mzxw 6ytb oi").unwrap(), "mzxw 6ytb oi");
+        assert!(SecretEncoding::Base32Hex.to_base32("MZXW6YTBOI").is_err());
+    }
+
+    #[test]
+    fn test_hex() {
+        // The RFC 6238 SHA-1 test seed
+        let seed = "3132333435363738393031323334353637383930";
+        assert_eq!(SecretEncoding::Hex.to_base32(seed).unwrap(), "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ");
+        assert_eq!(*SecretEncoding::Hex.decode("0xDE ad-BE ef").unwrap(), [0xde, 0xad, 0xbe, 0xef]);
+
+        let odd = SecretEncoding::Hex.decode("abc").unwrap_err();
+        assert!(odd.to_string().contains("length"), "{}", odd);
+        let not_hex = SecretEncoding::Hex.decode("JBSWY3DP").unwrap_err();
+        assert!(not_hex.to_string().contains("'J'"), "{}", not_hex);
+    }
+}
@@ -0,0 +1,33 @@
+// Structured errors for the library's stable API, so callers can match on what went wrong
+// instead of parsing messages. The binary only turns them into `anyhow` errors at its boundary.
+
+/// Everything that can go wrong in [`Totp`](crate::Totp), [`TotpDatabase`](crate::TotpDatabase)
+/// and base32 decoding.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// A secret isn't valid in the expected base32 alphabet (`base32` or `base32hex`)
+    #[error("Invalid {alphabet}: {reason}")]
+    InvalidBase32 { alphabet: &'static str, reason: &'static str },
+    /// Digits, period or truncation offset outside what RFC 4226 and the algorithm allow, an
+    /// empty secret, or a time before the token's epoch
+    #[error("{0}")]
+    InvalidParameters(String),
+    /// The system clock is set before the Unix epoch, so no code can be right
+    #[error("System clock is invalid: it is set before 1970-01-01, please correct the date and time")]
+    Clock,
+    /// No entry (or, for restores, no deleted entry) has this name
+    #[error("Entry not found: {0}")]
+    EntryNotFound(String),
+    /// Another entry already uses this name
+    #[error("Entry already exists: {0}")]
+    DuplicateName(String),
+    /// A SQL dump given to [`TotpDatabase::restore_dump`](crate::TotpDatabase::restore_dump)
+    /// doesn't rebuild a database this version can open
+    #[error("Invalid database dump: {0}")]
+    InvalidDump(String),
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,36 @@
+use std::fmt;
+use std::process::ExitCode;
+
+// Process exit codes, kept consistent across commands so scripts can tell failures apart.
+// Unexpected errors (I/O, database, network) exit with 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Failure {
+    /// Bad usage, an invalid flag value or an invalid secret
+    InvalidInput = 2,
+    /// The requested entry does not exist
+    NotFound = 3,
+    /// An entry with the same name already exists
+    AlreadyExists = 4,
+    /// A required integration (e.g. Cloudflare KV) is not configured
+    NotConfigured = 5,
+}
+
+impl Failure {
+    pub(crate) fn exit_code(self) -> ExitCode {
+        ExitCode::from(self as u8)
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            Self::InvalidInput => "invalid input",
+            Self::NotFound => "entry not found",
+            Self::AlreadyExists => "entry already exists",
+            Self::NotConfigured => "not configured",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for Failure {}
@@ -0,0 +1,227 @@
+use std::io::Write;
+use crate::TotpEntry;
+use crate::aegis::read_aegis;
+use crate::andotp::read_andotp;
+use crate::csv_format::{read_csv, write_csv};
+use crate::freeotp::read_freeotp;
+
+// The `--format` values of `import` and `export`. Adding a format means a variant here plus its
+// reader or writer; the commands only dispatch through these enums.
+
+/// What [`ImportFormat::read`] found in a backup.
+#[derive(Debug, Default)]
+pub struct Backup {
+    /// Each item either converts to a [`TotpEntry`] or explains why it was skipped
+    pub entries: Vec<Result<TotpEntry, String>>,
+    /// Problems with the file itself that didn't stop the import, such as trailing data
+    pub warnings: Vec<String>,
+}
+
+/// A backup format `import` can read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportFormat {
+    /// Our own JSON export
+    Json,
+    Csv,
+    Aegis,
+    AndOtp,
+    FreeOtp,
+}
+
+impl ImportFormat {
+    pub const ALL: [Self; 5] = [Self::Json, Self::Csv, Self::Aegis, Self::AndOtp, Self::FreeOtp];
+
+    /// Parses a `--format` value, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.as_str().eq_ignore_ascii_case(name))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+            Self::Aegis => "aegis",
+            Self::AndOtp => "andotp",
+            Self::FreeOtp => "freeotp",
+        }
+    }
+
+    /// Parses a whole backup. Fails only when the file as a whole can't be read; each item
+    /// otherwise either converts to a [`TotpEntry`] or explains why it was skipped. A leading
+    /// UTF-8 byte order mark, as some Windows editors add, is ignored.
+    pub fn read(self, content: &str) -> anyhow::Result<Backup> {
+        let content = content.strip_prefix('\u{feff}').unwrap_or(content);
+        let entries = match self {
+            Self::Json => return read_json(content),
+            Self::Csv => {
+                let (entries, errors) = read_csv(content.as_bytes())?;
+                let skipped = errors.into_iter().map(|error| Err(format!("line {} ({})", error.line, error.reason)));
+                entries.into_iter().map(Ok).chain(skipped).collect()
+            }
+            Self::Aegis => read_aegis(content)?,
+            Self::AndOtp => read_andotp(content)?,
+            Self::FreeOtp => read_freeotp(content),
+        };
+        Ok(Backup { entries, warnings: Vec::new() })
+    }
+}
+
+// Our JSON export: one array of entries. Anything after the array (an editor's comment, a second
+// paste) is reported and ignored instead of failing the whole import.
+fn read_json(content: &str) -> anyhow::Result<Backup> {
+    let mut stream = serde_json::Deserializer::from_str(content).into_iter::<Vec<TotpEntry>>();
+    let entries = match stream.next() {
+        Some(Ok(entries)) => entries,
+        // serde_json's message already ends with "at line N column M"
+        Some(Err(e)) => anyhow::bail!("Invalid JSON backup: {}", e),
+        None => anyhow::bail!("Invalid JSON backup: the file is empty"),
+    };
+
+    let mut warnings = Vec::new();
+    let rest = &content[stream.byte_offset()..];
+    if !rest.trim().is_empty() {
+        let start = content.len() - rest.trim_start().len();
+        let (line, column) = position(content, start);
+        warnings.push(format!("Ignored trailing data after the JSON array (line {} column {})", line, column));
+    }
+
+    Ok(Backup { entries: entries.into_iter().map(Ok).collect(), warnings })
+}
+
+// 1-based line and column of a byte offset, counted the way serde_json reports errors
+fn position(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |newline| newline + 1) + 1;
+    (line, column)
+}
+
+/// A file format `export` can write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    Json,
+    Csv,
+}
+
+impl ExportFormat {
+    pub const ALL: [Self; 2] = [Self::Json, Self::Csv];
+
+    /// Parses a `--format` value, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.as_str().eq_ignore_ascii_case(name))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Csv => "csv",
+        }
+    }
+
+    /// Writes `entries` followed by a final newline. JSON is pretty-printed unless `compact` is
+    /// set; CSV ignores it.
+    pub fn write<W: Write>(self, mut writer: W, entries: &[TotpEntry], compact: bool) -> anyhow::Result<()> {
+        match self {
+            Self::Json if compact => serde_json::to_writer(&mut writer, entries)?,
+            Self::Json => serde_json::to_writer_pretty(&mut writer, entries)?,
+            Self::Csv => return write_csv(writer, entries),
+        }
+        writeln!(writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Format names for error messages, e.g. `json, csv or aegis`.
+pub fn choices(names: &[&str]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => name.to_string(),
+        [rest @ .., last] => format!("{} or {}", rest.join(", "), last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str) -> TotpEntry {
+        TotpEntry { name: name.to_string(), secret: "JBSWY3DPEHPK3PXP".to_string(), ..TotpEntry::default() }
+    }
+
+    #[test]
+    fn test_parse_format_names() {
+        for format in ImportFormat::ALL {
+            assert_eq!(ImportFormat::parse(format.as_str()), Some(format));
+        }
+        assert_eq!(ImportFormat::parse("AndOTP"), Some(ImportFormat::AndOtp));
+        assert_eq!(ImportFormat::parse("xml"), None);
+        assert_eq!(ExportFormat::parse("CSV"), Some(ExportFormat::Csv));
+        assert_eq!(ExportFormat::parse("aegis"), None);
+        assert_eq!(choices(&["json", "csv", "aegis"]), "json, csv or aegis");
+    }
+
+    #[test]
+    fn test_export_then_import_round_trips() {
+        let entries = vec![entry("github"), entry("gitlab")];
+        for format in ExportFormat::ALL {
+            let mut buffer = Vec::new();
+            format.write(&mut buffer, &entries, false).unwrap();
+            let import = ImportFormat::parse(format.as_str()).unwrap();
+
+            let read = import.read(std::str::from_utf8(&buffer).unwrap()).unwrap();
+            assert!(read.warnings.is_empty());
+            let names: Vec<String> = read.entries.into_iter().map(|entry| entry.unwrap().name).collect();
+            assert_eq!(names, ["github", "gitlab"], "{}", format.as_str());
+        }
+    }
+
+    #[test]
+    fn test_compact_json_is_one_line() {
+        let mut pretty = Vec::new();
+        ExportFormat::Json.write(&mut pretty, &[entry("github")], false).unwrap();
+        let mut compact = Vec::new();
+        ExportFormat::Json.write(&mut compact, &[entry("github")], true).unwrap();
+
+        assert!(pretty.iter().filter(|&&byte| byte == b'\n').count() > 1);
+        assert_eq!(compact.iter().filter(|&&byte| byte == b'\n').count(), 1);
+        assert!(compact.len() < pretty.len());
+    }
+
+    #[test]
+    fn test_csv_rows_that_fail_are_skip_reasons() {
+        let read = ImportFormat::Csv.read("name,secret\ngithub,JBSWY3DPEHPK3PXP\n,JBSWY3DPEHPK3PXP\n").unwrap().entries;
+        assert_eq!(read.len(), 2);
+        assert_eq!(read[0].as_ref().unwrap().name, "github");
+        assert_eq!(read[1].as_ref().unwrap_err(), "line 3 (missing name)");
+    }
+
+    // As saved by Notepad: a byte order mark, CRLF line endings and a trailing blank line
+    const BOM_EXPORT: &str = "\u{feff}[\r\n  {\"name\": \"github\", \"secret\": \"JBSWY3DPEHPK3PXP\", \"created_at\": \"2024-01-01T00:00:00Z\"}\r\n]\r\n\r\n";
+
+    #[test]
+    fn test_json_with_bom_and_trailing_whitespace() {
+        let read = ImportFormat::Json.read(BOM_EXPORT).unwrap();
+        assert!(read.warnings.is_empty());
+        assert_eq!(read.entries.len(), 1);
+        assert_eq!(read.entries[0].as_ref().unwrap().name, "github");
+
+        // The other formats skip the mark too
+        let csv = ImportFormat::Csv.read("\u{feff}name,secret\ngithub,JBSWY3DPEHPK3PXP\n").unwrap();
+        assert_eq!(csv.entries[0].as_ref().unwrap().name, "github");
+    }
+
+    #[test]
+    fn test_json_trailing_data_is_a_warning() {
+        let read = ImportFormat::Json.read(&format!("{}// exported by hand\n", BOM_EXPORT)).unwrap();
+        assert_eq!(read.entries.len(), 1);
+        assert_eq!(read.warnings, ["Ignored trailing data after the JSON array (line 5 column 1)"]);
+    }
+
+    #[test]
+    fn test_json_syntax_errors_name_the_position() {
+        let error = ImportFormat::Json.read("[\n  {\"name\": \"github\",}\n]").unwrap_err();
+        assert!(error.to_string().contains("line 2 column"), "{}", error);
+        assert!(ImportFormat::Json.read("  \n").is_err());
+    }
+}
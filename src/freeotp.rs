@@ -0,0 +1,39 @@
+use crate::TotpEntry;
+use crate::otpauth;
+
+/// Parses a FreeOTP(+) URI export: one `otpauth://` URL per line. Blank lines are ignored, and
+/// lines that aren't valid TOTP URLs are returned as skip reasons with their line number.
+pub fn read_freeotp(text: &str) -> Vec<Result<TotpEntry, String>> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line_number, line)| {
+            otpauth::parse(line).map_err(|e| format!("line {} ({})", line_number, e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totp::HashAlgorithm;
+
+    #[test]
+    fn test_read_freeotp_uris() {
+        let text = "otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub\n\
+                    \n\
+                    otpauth://hotp/counter?secret=JBSWY3DPEHPK3PXP&counter=1\n\
+                    otpauth://totp/work?secret=JBSWY3DPEHPK3PXP&algorithm=SHA256&digits=8\n";
+
+        let results = read_freeotp(text);
+        assert_eq!(results.len(), 3);
+        assert_eq!(results[0].as_ref().unwrap().issuer.as_deref(), Some("GitHub"));
+        assert!(results[1].as_ref().unwrap_err().starts_with("line 3 "));
+
+        let work = results[2].as_ref().unwrap();
+        assert_eq!(work.name, "work");
+        assert_eq!(work.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(work.digits, 8);
+    }
+}
@@ -0,0 +1,114 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+use anyhow::Context;
+
+// The Cloudflare API token in the OS keychain, so it doesn't have to sit in a readable file or
+// the environment. We talk to the platform's own tool (`secret-tool` for the freedesktop Secret
+// Service on Linux and the BSDs, `security` on macOS) and never pass the token on its command
+// line, where other users could see it in the process list.
+
+const SERVICE: &str = "totp-console";
+const ACCOUNT: &str = "cloudflare-api-token";
+
+/// The stored API token, or None if there is none or no keychain is available.
+pub fn get_token() -> Option<String> {
+    let output = lookup_command()?.stderr(Stdio::null()).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let token = String::from_utf8(output.stdout).ok()?.trim().to_string();
+    if token.is_empty() {
+        return None;
+    }
+    log::debug!("Using the Cloudflare API token from the keychain");
+    Some(token)
+}
+
+/// Stores `token`, replacing any previous one.
+pub fn set_token(token: &str) -> anyhow::Result<()> {
+    if cfg!(target_os = "macos") {
+        // `security -i` reads commands from stdin, which keeps the token out of argv
+        let script = format!("add-generic-password -U -s {} -a {} -w {}\n", SERVICE, ACCOUNT, quote(token)?);
+        run_with_input(Command::new("security").arg("-i"), &script)
+    } else if cfg!(unix) {
+        let mut command = Command::new("secret-tool");
+        command.args(["store", "--label=totp-console Cloudflare API token", "service", SERVICE, "account", ACCOUNT]);
+        run_with_input(&mut command, token)
+    } else {
+        anyhow::bail!("No supported keychain on this platform; use config.toml or CF_API_TOKEN instead")
+    }
+}
+
+/// Removes the stored token. Returns false if there was none.
+pub fn delete_token() -> anyhow::Result<bool> {
+    if get_token().is_none() {
+        return Ok(false);
+    }
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("security");
+        command.args(["delete-generic-password", "-s", SERVICE, "-a", ACCOUNT]);
+        command
+    } else {
+        let mut command = Command::new("secret-tool");
+        command.args(["clear", "service", SERVICE, "account", ACCOUNT]);
+        command
+    };
+    let status = command.stdout(Stdio::null()).status().context("Failed to run the keychain tool")?;
+    if !status.success() {
+        anyhow::bail!("The keychain refused to remove the token ({})", status);
+    }
+    Ok(true)
+}
+
+fn lookup_command() -> Option<Command> {
+    if cfg!(target_os = "macos") {
+        let mut command = Command::new("security");
+        command.args(["find-generic-password", "-s", SERVICE, "-a", ACCOUNT, "-w"]);
+        Some(command)
+    } else if cfg!(unix) {
+        let mut command = Command::new("secret-tool");
+        command.args(["lookup", "service", SERVICE, "account", ACCOUNT]);
+        Some(command)
+    } else {
+        None
+    }
+}
+
+fn run_with_input(command: &mut Command, input: &str) -> anyhow::Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Failed to run {} (is it installed?)", program))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let status = child.wait()?;
+    if !status.success() {
+        anyhow::bail!("{} failed to store the token ({})", program, status);
+    }
+    Ok(())
+}
+
+// Double-quotes a token for `security -i`. Cloudflare tokens are URL-safe base64, so anything
+// that would need escaping means the token was pasted wrong.
+fn quote(token: &str) -> anyhow::Result<String> {
+    if token.is_empty() || token.chars().any(|c| c.is_whitespace() || c == '"' || c == '\\') {
+        anyhow::bail!("API token contains whitespace or quotes; check that it was pasted correctly");
+    }
+    Ok(format!("\"{}\"", token))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote_rejects_mangled_tokens() {
+        assert_eq!(quote("abc-DEF_123").unwrap(), "\"abc-DEF_123\"");
+        assert!(quote("").is_err());
+        assert!(quote("abc def").is_err());
+        assert!(quote("abc\"; delete-keychain").is_err());
+    }
+}
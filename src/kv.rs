@@ -1,85 +1,622 @@
+use std::collections::{BTreeMap, HashSet};
 use std::env;
-use crate::database::TotpEntry;
+use std::time::Duration;
+use crate::TotpEntry;
+use crate::keychain;
+use anyhow::Context;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
+pub const DEFAULT_TIMEOUT_SECS: u64 = 10;
+pub const DEFAULT_RETRIES: u32 = 2;
+
+/// The vault key used unless another is configured. Each entry lives under its own KV key,
+/// `<vault key>:<entry name>`, so vaults with different keys can share a namespace.
+pub const DEFAULT_KEY: &str = "totp";
+// Older versions kept the whole vault in this one key
+const LEGACY_KEY: &str = "totp_entries";
+// Cloudflare's limits for a single bulk request
+const BULK_WRITE_LIMIT: usize = 10_000;
+const BULK_GET_LIMIT: usize = 100;
+
+/// How long to wait for Cloudflare and how often to try again after a transient failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestOptions {
+    /// Applies to connecting and to each attempt as a whole
+    pub timeout: Duration,
+    /// Extra attempts after the first one fails with a network error or a 5xx/429 response
+    pub retries: u32,
+}
+
+impl Default for RequestOptions {
+    fn default() -> Self {
+        Self { timeout: Duration::from_secs(DEFAULT_TIMEOUT_SECS), retries: DEFAULT_RETRIES }
+    }
+}
+
+impl RequestOptions {
+    /// These options, overridden by `CF_TIMEOUT` (seconds) and `CF_RETRIES` when they are set.
+    pub fn with_env(self) -> Result<Self, String> {
+        let mut options = self;
+        if let Ok(value) = env::var("CF_TIMEOUT") {
+            options.timeout = parse_timeout(&value).map_err(|reason| format!("CF_TIMEOUT: {}", reason))?;
+        }
+        if let Ok(value) = env::var("CF_RETRIES") {
+            options.retries = parse_retries(&value).map_err(|reason| format!("CF_RETRIES: {}", reason))?;
+        }
+        Ok(options)
+    }
+}
+
+/// Checks a vault key: it must be non-empty and free of `:`, which separates it from the entry
+/// name, so that one vault's keys never look like another's.
+pub fn validate_key(key: &str) -> Result<(), String> {
+    if key.is_empty() {
+        return Err("KV key is empty".to_string());
+    }
+    if key.contains(':') {
+        return Err(format!("KV key can't contain ':', got {}", key));
+    }
+    // Cloudflare allows 512 bytes per key, and the entry name still has to fit
+    if key.len() > 64 {
+        return Err(format!("KV key is {} bytes long, the limit is 64", key.len()));
+    }
+    Ok(())
+}
+
+/// Parses a timeout in whole seconds; zero is rejected since it would fail every request.
+pub fn parse_timeout(value: &str) -> Result<Duration, String> {
+    match value.parse::<u64>() {
+        Ok(0) => Err("timeout must be at least 1 second".to_string()),
+        Ok(secs) => Ok(Duration::from_secs(secs)),
+        Err(_) => Err(format!("invalid timeout: {}", value)),
+    }
+}
+
+pub fn parse_retries(value: &str) -> Result<u32, String> {
+    value.parse().map_err(|_| format!("invalid retry count: {}", value))
+}
+
+// Server-side hiccups and rate limiting are worth another try; anything else won't change
+fn is_transient(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+// 500ms, 1s, 2s, ... capped at 8s
+fn backoff(attempt: u32) -> Duration {
+    Duration::from_millis(500 * 2u64.pow(attempt.min(4)))
+}
+
 // Cloudflare KV integration
-#[derive(Deserialize, Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct CloudflareKV {
     account_id: String,
     namespace_id: String,
     api_token: String,
+    #[serde(skip)]
+    options: RequestOptions,
+    #[serde(skip, default = "default_key")]
+    key: String,
+}
+
+fn default_key() -> String {
+    DEFAULT_KEY.to_string()
 }
 
 impl CloudflareKV {
-    fn new(account_id: String, namespace_id: String, api_token: String) -> Self {
+    pub fn new(account_id: String, namespace_id: String, api_token: String) -> Self {
         Self {
             account_id,
             namespace_id,
             api_token,
+            options: RequestOptions::default(),
+            key: default_key(),
         }
     }
 
-    pub(crate) async fn sync_to_kv(&self, entries: &[TotpEntry]) -> anyhow::Result<()> {
-        let client = reqwest::Client::new();
-        let url = format!(
-            "https://api.cloudflare.com/client/v4/accounts/{}/storage/kv/namespaces/{}/values/totp_entries",
-            self.account_id, self.namespace_id
-        );
+    pub fn with_options(mut self, options: RequestOptions) -> Self {
+        self.options = options;
+        self
+    }
 
-        let json_data = serde_json::to_string(entries)?;
+    /// Stores the vault under `key` instead of [`DEFAULT_KEY`]; see [`validate_key`].
+    pub fn with_key(mut self, key: String) -> Self {
+        self.key = key;
+        self
+    }
 
-        let response = client
-            .put(&url)
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .header("Content-Type", "application/json")
-            .body(json_data)
-            .send()
-            .await?;
+    fn client(&self) -> anyhow::Result<reqwest::Client> {
+        Ok(reqwest::Client::builder()
+            .connect_timeout(self.options.timeout)
+            .timeout(self.options.timeout)
+            .build()?)
+    }
 
-        if response.status().is_success() {
-            println!("✅ Synced {} entries to Cloudflare KV", entries.len());
-        } else {
-            eprintln!("❌ Failed to sync to Cloudflare KV: {}", response.status());
+    // Sends the request built by `request`, retrying network errors and transient statuses
+    // with exponential backoff. The last response is returned as is, so callers still see
+    // non-transient failures like 401 straight away.
+    async fn send_with_retry(
+        &self,
+        request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> anyhow::Result<reqwest::Response> {
+        let attempts = self.options.retries + 1;
+        let mut attempt = 1;
+        loop {
+            // Built by hand so the URL can be logged; the headers carry the token and never are
+            let (client, built) = request().build_split();
+            let result = match built {
+                Ok(built) => {
+                    log::debug!("{} {} (attempt {} of {})", built.method(), built.url(), attempt, attempts);
+                    client.execute(built).await
+                }
+                Err(e) => Err(e),
+            };
+            let transient = match &result {
+                Ok(response) => {
+                    log::debug!("Cloudflare answered {}", response.status());
+                    is_transient(response.status())
+                }
+                Err(e) => {
+                    log::debug!("Request failed: {}", e);
+                    e.is_timeout() || e.is_connect() || e.is_request()
+                }
+            };
+
+            if !transient {
+                return Ok(result?);
+            }
+            if attempt >= attempts {
+                let context = format!("Cloudflare KV request failed after {} attempt(s)", attempt);
+                return match result {
+                    Ok(response) => Err(anyhow::anyhow!("{}: {}", context, response.status())),
+                    Err(e) => Err(anyhow::Error::from(e).context(context)),
+                };
+            }
+
+            log::debug!("Retrying in {:?}", backoff(attempt - 1));
+            tokio::time::sleep(backoff(attempt - 1)).await;
+            attempt += 1;
         }
+    }
 
-        Ok(())
+    fn namespace_url(&self, path: &str) -> String {
+        format!(
+            "https://api.cloudflare.com/client/v4/accounts/{}/storage/kv/namespaces/{}/{}",
+            self.account_id, self.namespace_id, path
+        )
     }
 
-    pub(crate) async fn load_from_kv(&self) -> anyhow::Result<Vec<TotpEntry>> {
-        let client = reqwest::Client::new();
-        let url = format!(
-            "https://api.cloudflare.com/client/v4/accounts/{}/storage/kv/namespaces/{}/values/totp_entries",
-            self.account_id, self.namespace_id
-        );
+    fn authorized(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        request.header("Authorization", format!("Bearer {}", self.api_token))
+    }
+
+    /// Uploads `entries` one key per entry, only rewriting entries that changed and deleting
+    /// keys for entries that no longer exist locally. A vault still stored in the old
+    /// single-key format is migrated by the first sync.
+    pub async fn sync_to_kv(&self, entries: &[TotpEntry]) -> anyhow::Result<SyncSummary> {
+        let client = self.client()?;
+        let remote = self.load_per_entry(&client).await?;
+        let plan = plan_sync(&self.key, entries, &remote)?;
+
+        for chunk in plan.writes.chunks(BULK_WRITE_LIMIT) {
+            let body: Vec<BulkWrite> = chunk
+                .iter()
+                .map(|(key, value)| BulkWrite { key, value })
+                .collect();
+            let url = self.namespace_url("bulk");
+            let response = self.send_with_retry(|| self.authorized(client.put(&url)).json(&body)).await?;
+            api_result::<serde_json::Value>(response, "write to Cloudflare KV").await?;
+        }
+
+        for chunk in plan.deletes.chunks(BULK_WRITE_LIMIT) {
+            let url = self.namespace_url("bulk/delete");
+            let response = self.send_with_retry(|| self.authorized(client.post(&url)).json(chunk)).await?;
+            api_result::<serde_json::Value>(response, "delete from Cloudflare KV").await?;
+        }
+
+        // Everything now lives under per-entry keys, so the old blob can go
+        let migrated = self.load_legacy(&client).await?.is_some();
+        if migrated {
+            let url = self.namespace_url(&format!("values/{}", LEGACY_KEY));
+            let response = self.send_with_retry(|| self.authorized(client.delete(&url))).await?;
+            api_result::<serde_json::Value>(response, "remove the old single-key backup").await?;
+        }
+
+        Ok(SyncSummary {
+            written: plan.writes.len(),
+            deleted: plan.deletes.len(),
+            unchanged: plan.unchanged,
+            migrated,
+        })
+    }
+
+    /// Downloads every entry. With the default vault key, falls back to the old single
+    /// `totp_entries` key when nothing has been synced in the per-entry format yet.
+    pub async fn load_from_kv(&self) -> anyhow::Result<Vec<TotpEntry>> {
+        let client = self.client()?;
+        let remote = self.load_per_entry(&client).await?;
+        if !remote.is_empty() {
+            return Ok(remote.into_values().collect());
+        }
+
+        Ok(self.load_legacy(&client).await?.unwrap_or_default())
+    }
+
+    // Entries stored under `<vault key>:<name>`, keyed by their KV key
+    async fn load_per_entry(&self, client: &reqwest::Client) -> anyhow::Result<BTreeMap<String, TotpEntry>> {
+        let keys = self.list_keys(client).await?;
+        let mut entries = BTreeMap::new();
+
+        for chunk in keys.chunks(BULK_GET_LIMIT) {
+            let url = self.namespace_url("bulk/get");
+            let body = serde_json::json!({ "keys": chunk });
+            let response = self.send_with_retry(|| self.authorized(client.post(&url)).json(&body)).await?;
+            let result: BulkGetResult = api_result(response, "read from Cloudflare KV").await?;
+
+            for (key, value) in result.values {
+                // A key can vanish between listing and reading it
+                let Some(value) = value else { continue };
+                let entry: TotpEntry = serde_json::from_str(&value)
+                    .with_context(|| format!("Cloudflare KV key {} does not hold a TOTP entry", key))?;
+                entries.insert(key, entry);
+            }
+        }
+
+        Ok(entries)
+    }
+
+    async fn list_keys(&self, client: &reqwest::Client) -> anyhow::Result<Vec<String>> {
+        let mut keys = Vec::new();
+        let mut cursor: Option<String> = None;
+
+        loop {
+            let mut query = vec![("prefix", format!("{}:", self.key)), ("limit", "1000".to_string())];
+            if let Some(cursor) = &cursor {
+                query.push(("cursor", cursor.clone()));
+            }
+            let url = self.namespace_url("keys");
+            let response = self.send_with_retry(|| self.authorized(client.get(&url)).query(&query)).await?;
+            let page: ApiResponse<Vec<KeyInfo>> = api_response(response, "list Cloudflare KV keys").await?;
+
+            keys.extend(page.result.into_iter().map(|key| key.name));
+            cursor = page.result_info.and_then(|info| info.cursor).filter(|cursor| !cursor.is_empty());
+            if cursor.is_none() {
+                return Ok(keys);
+            }
+        }
+    }
+
+    // The old format predates vault keys, so it only belongs to the default one
+    async fn load_legacy(&self, client: &reqwest::Client) -> anyhow::Result<Option<Vec<TotpEntry>>> {
+        if self.key != DEFAULT_KEY {
+            return Ok(None);
+        }
+        let url = self.namespace_url(&format!("values/{}", LEGACY_KEY));
+        let response = self.send_with_retry(|| self.authorized(client.get(&url))).await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            anyhow::bail!("Failed to load from Cloudflare KV: {}", response.status());
+        }
+
+        Ok(Some(response.json().await?))
+    }
+}
+
+/// What a sync changed remotely.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncSummary {
+    pub written: usize,
+    pub deleted: usize,
+    pub unchanged: usize,
+    /// The old single-key backup was found and removed
+    pub migrated: bool,
+}
+
+// Keys to write (with their stored value) and keys to delete to make the remote match `local`
+#[derive(Debug, Default)]
+struct SyncPlan {
+    writes: Vec<(String, String)>,
+    deletes: Vec<String>,
+    unchanged: usize,
+}
+
+fn plan_sync(vault_key: &str, local: &[TotpEntry], remote: &BTreeMap<String, TotpEntry>) -> anyhow::Result<SyncPlan> {
+    let mut plan = SyncPlan::default();
+    let mut local_keys = HashSet::new();
 
-        let response = client
-            .get(&url)
-            .header("Authorization", format!("Bearer {}", self.api_token))
-            .send()
-            .await?;
+    for entry in local {
+        let key = entry_key(vault_key, &entry.name);
+        let value = stored_value(entry)?;
+        let up_to_date = match remote.get(&key) {
+            Some(existing) => stored_value(existing)? == value,
+            None => false,
+        };
 
-        if response.status().is_success() {
-            let entries: Vec<TotpEntry> = response.json().await?;
-            println!("✅ Loaded {} entries from Cloudflare KV", entries.len());
-            Ok(entries)
+        if up_to_date {
+            plan.unchanged += 1;
         } else {
-            eprintln!("❌ Failed to load from Cloudflare KV: {}", response.status());
-            Ok(Vec::new())
+            plan.writes.push((key.clone(), value));
         }
+        local_keys.insert(key);
     }
+
+    plan.deletes = remote.keys().filter(|key| !local_keys.contains(*key)).cloned().collect();
+    Ok(plan)
 }
 
-pub fn get_cloudflare_kv() -> Option<CloudflareKV> {
-    // Read `kv.json`, and if it doesn't exist, use env vars.
-    if let Ok(file_content) = std::fs::read_to_string("kv.json")
-        && let Ok(kv) = serde_json::from_str::<CloudflareKV>(&file_content) {
-            return Some(kv);
+/// How the local vault and Cloudflare KV disagree, matched by entry name.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct KvDiff {
+    pub local_only: Vec<String>,
+    pub remote_only: Vec<String>,
+    /// Entries on both sides, with the names of the fields that differ
+    pub differing: Vec<(String, Vec<&'static str>)>,
+}
+
+impl KvDiff {
+    pub fn is_empty(&self) -> bool {
+        self.local_only.is_empty() && self.remote_only.is_empty() && self.differing.is_empty()
+    }
+}
+
+/// Compares local entries against what [`CloudflareKV::load_from_kv`] returned, sorted by name.
+pub fn diff_entries(local: &[TotpEntry], remote: &[TotpEntry]) -> KvDiff {
+    let local: BTreeMap<&str, &TotpEntry> = local.iter().map(|entry| (entry.name.as_str(), entry)).collect();
+    let remote: BTreeMap<&str, &TotpEntry> = remote.iter().map(|entry| (entry.name.as_str(), entry)).collect();
+    let mut diff = KvDiff::default();
+
+    for (name, entry) in &local {
+        let Some(other) = remote.get(name) else {
+            diff.local_only.push(name.to_string());
+            continue;
+        };
+
+        let fields: Vec<&'static str> = [
+            ("secret", entry.secret != other.secret),
+            ("issuer", entry.issuer != other.issuer),
+            ("account", entry.account != other.account),
+            ("algorithm", entry.algorithm != other.algorithm),
+            ("digits", entry.digits != other.digits),
+            ("period", entry.period != other.period),
+            ("truncation", entry.truncation_offset != other.truncation_offset),
+            ("favorite", entry.favorite != other.favorite),
+            ("url", entry.login_url != other.login_url),
+            ("backup codes", entry.backup_codes != other.backup_codes),
+            ("epoch", entry.epoch != other.epoch),
+            ("expiration", entry.expires_at != other.expires_at),
+        ]
+        .into_iter()
+        .filter_map(|(field, changed)| changed.then_some(field))
+        .collect();
+
+        if !fields.is_empty() {
+            diff.differing.push((name.to_string(), fields));
         }
+    }
+
+    diff.remote_only = remote.keys().filter(|name| !local.contains_key(*name)).map(|name| name.to_string()).collect();
+    diff
+}
+
+/// The KV key an entry is stored under in the vault with key `vault_key`.
+pub fn entry_key(vault_key: &str, name: &str) -> String {
+    format!("{}:{}", vault_key, name)
+}
+
+// The local row id means nothing on another machine, so it isn't stored or compared
+fn stored_value(entry: &TotpEntry) -> anyhow::Result<String> {
+    Ok(serde_json::to_string(&TotpEntry { id: None, ..entry.clone() })?)
+}
+
+#[derive(Serialize)]
+struct BulkWrite<'a> {
+    key: &'a str,
+    value: &'a str,
+}
+
+#[derive(Deserialize)]
+struct BulkGetResult {
+    values: BTreeMap<String, Option<String>>,
+}
+
+#[derive(Deserialize)]
+struct KeyInfo {
+    name: String,
+}
+
+#[derive(Deserialize)]
+struct ResultInfo {
+    cursor: Option<String>,
+}
 
+// The envelope every Cloudflare v4 API response comes in
+#[derive(Deserialize)]
+struct ApiResponse<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<ApiError>,
+    result: T,
+    result_info: Option<ResultInfo>,
+}
+
+#[derive(Deserialize)]
+struct ApiError {
+    code: i64,
+    message: String,
+}
+
+async fn api_response<T: DeserializeOwned>(response: reqwest::Response, action: &str) -> anyhow::Result<ApiResponse<T>> {
+    let status = response.status();
+    let body = response.text().await?;
+
+    let parsed: Option<ApiResponse<serde_json::Value>> = serde_json::from_str(&body).ok();
+    let errors = parsed
+        .as_ref()
+        .map(|parsed| parsed.errors.iter().map(|e| format!("{} ({})", e.message, e.code)).collect::<Vec<_>>().join(", "))
+        .unwrap_or_default();
 
-    let account_id = env::var("CF_ACCOUNT_ID").ok()?;
-    let namespace_id = env::var("CF_NAMESPACE_ID").ok()?;
-    let api_token = env::var("CF_API_TOKEN").ok()?;
+    if !status.is_success() || parsed.as_ref().is_some_and(|parsed| !parsed.success) {
+        if errors.is_empty() {
+            anyhow::bail!("Failed to {}: {}", action, status);
+        }
+        anyhow::bail!("Failed to {}: {}: {}", action, status, errors);
+    }
+
+    serde_json::from_str(&body).with_context(|| format!("Failed to {}: unexpected response", action))
+}
 
+async fn api_result<T: DeserializeOwned>(response: reqwest::Response, action: &str) -> anyhow::Result<T> {
+    Ok(api_response(response, action).await?.result)
+}
+
+// `kv.json`, where the token may be left out in favour of the keychain
+#[derive(Deserialize)]
+struct KvFile {
+    account_id: String,
+    namespace_id: String,
+    api_token: Option<String>,
+}
+
+/// Credentials from `kv.json`, or the CF_* environment variables if it doesn't exist. A token
+/// stored in the OS keychain with `kv-login` takes precedence over the file and `CF_API_TOKEN`.
+pub fn get_cloudflare_kv() -> Option<CloudflareKV> {
+    let file = std::fs::read_to_string("kv.json").ok().and_then(|content| serde_json::from_str::<KvFile>(&content).ok());
+    let (account_id, namespace_id, api_token) = match file {
+        Some(file) => (file.account_id, file.namespace_id, file.api_token),
+        None => (env::var("CF_ACCOUNT_ID").ok()?, env::var("CF_NAMESPACE_ID").ok()?, env::var("CF_API_TOKEN").ok()),
+    };
+
+    let api_token = keychain::get_token().or(api_token)?;
     Some(CloudflareKV::new(account_id, namespace_id, api_token))
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::totp::HashAlgorithm;
+
+    // The old single-key format is a serialized `[TotpEntry]`, which `load_from_kv` still reads
+    #[test]
+    fn test_kv_payload_round_trip() {
+        let entries = vec![TotpEntry {
+            id: Some(1),
+            name: "github".to_string(),
+            secret: "JBSWY3DPEHPK3PXP".to_string(),
+            issuer: Some("GitHub".to_string()),
+            created_at: "2024-01-01T00:00:00+00:00".to_string(),
+            algorithm: HashAlgorithm::Sha256,
+            digits: 8,
+            period: 60,
+            account: Some("alice@example.com".to_string()),
+            truncation_offset: None,
+            favorite: false,
+            login_url: None,
+            backup_codes: Vec::new(),
+            epoch: 0,
+            expires_at: None,
+        }];
+
+        let payload = serde_json::to_string(entries.as_slice()).unwrap();
+        let loaded: Vec<TotpEntry> = serde_json::from_str(&payload).unwrap();
+
+        assert_eq!(loaded.len(), 1);
+        assert_eq!(loaded[0].name, "github");
+        assert_eq!(loaded[0].issuer.as_deref(), Some("GitHub"));
+        assert_eq!(loaded[0].algorithm, HashAlgorithm::Sha256);
+        assert_eq!(loaded[0].digits, 8);
+        assert_eq!(loaded[0].period, 60);
+    }
+
+    #[test]
+    fn test_kv_loads_payload_from_before_code_parameters() {
+        let payload = r#"[{"id":1,"name":"github","secret":"JBSWY3DPEHPK3PXP","issuer":null,"created_at":"2024-01-01T00:00:00+00:00"}]"#;
+        let loaded: Vec<TotpEntry> = serde_json::from_str(payload).unwrap();
+
+        assert_eq!(loaded[0].algorithm, HashAlgorithm::Sha1);
+        assert_eq!(loaded[0].digits, 6);
+        assert_eq!(loaded[0].period, 30);
+    }
+
+    #[test]
+    fn test_request_option_parsing() {
+        assert_eq!(parse_timeout("30"), Ok(Duration::from_secs(30)));
+        assert!(parse_timeout("0").is_err());
+        assert!(parse_timeout("soon").is_err());
+        assert_eq!(parse_retries("0"), Ok(0));
+        assert!(parse_retries("-1").is_err());
+    }
+
+    #[test]
+    fn test_only_transient_statuses_are_retried() {
+        assert!(is_transient(StatusCode::BAD_GATEWAY));
+        assert!(is_transient(StatusCode::TOO_MANY_REQUESTS));
+        assert!(!is_transient(StatusCode::UNAUTHORIZED));
+        assert!(!is_transient(StatusCode::NOT_FOUND));
+        assert_eq!(backoff(0), Duration::from_millis(500));
+        assert_eq!(backoff(10), Duration::from_secs(8));
+    }
+
+    fn entry(name: &str, secret: &str) -> TotpEntry {
+        TotpEntry { name: name.to_string(), secret: secret.to_string(), ..TotpEntry::default() }
+    }
+
+    #[test]
+    fn test_sync_plan_is_incremental() {
+        let mut remote = BTreeMap::new();
+        remote.insert(entry_key(DEFAULT_KEY, "github"), TotpEntry { id: Some(7), ..entry("github", "JBSWY3DPEHPK3PXP") });
+        remote.insert(entry_key(DEFAULT_KEY, "gitlab"), entry("gitlab", "JBSWY3DPEHPK3PXP"));
+        remote.insert(entry_key(DEFAULT_KEY, "old"), entry("old", "JBSWY3DPEHPK3PXP"));
+
+        let local = vec![
+            TotpEntry { id: Some(1), ..entry("github", "JBSWY3DPEHPK3PXP") },
+            entry("gitlab", "GEZDGNBVGY3TQOJQ"),
+            entry("new", "JBSWY3DPEHPK3PXP"),
+        ];
+        let plan = plan_sync(DEFAULT_KEY, &local, &remote).unwrap();
+
+        // A different local row id alone doesn't count as a change
+        assert_eq!(plan.unchanged, 1);
+        let written: Vec<&str> = plan.writes.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(written, vec!["totp:gitlab", "totp:new"]);
+        assert_eq!(plan.deletes, vec!["totp:old".to_string()]);
+
+        let stored: TotpEntry = serde_json::from_str(&plan.writes[1].1).unwrap();
+        assert_eq!(stored.name, "new");
+        assert_eq!(stored.id, None);
+    }
+
+    #[test]
+    fn test_vault_keys() {
+        assert!(validate_key("work").is_ok());
+        assert!(validate_key("").is_err());
+        assert!(validate_key("work:2").is_err());
+        assert!(validate_key(&"k".repeat(65)).is_err());
+
+        let plan = plan_sync("totp-work", &[entry("github", "JBSWY3DPEHPK3PXP")], &BTreeMap::new()).unwrap();
+        assert_eq!(plan.writes[0].0, "totp-work:github");
+    }
+
+    #[test]
+    fn test_diff_entries_by_name() {
+        let local = vec![
+            entry("github", "JBSWY3DPEHPK3PXP"),
+            entry("gitlab", "JBSWY3DPEHPK3PXP"),
+            TotpEntry { issuer: Some("Work".to_string()), ..entry("vpn", "JBSWY3DPEHPK3PXP") },
+        ];
+        let remote = vec![
+            entry("gitlab", "GEZDGNBVGY3TQOJQ"),
+            entry("vpn", "JBSWY3DPEHPK3PXP"),
+            entry("old", "JBSWY3DPEHPK3PXP"),
+        ];
+
+        let diff = diff_entries(&local, &remote);
+        assert_eq!(diff.local_only, vec!["github".to_string()]);
+        assert_eq!(diff.remote_only, vec!["old".to_string()]);
+        assert_eq!(diff.differing, vec![("gitlab".to_string(), vec!["secret"]), ("vpn".to_string(), vec!["issuer"])]);
+        assert!(diff_entries(&local, &local).is_empty());
+    }
+}
@@ -0,0 +1,51 @@
+//! TOTP (RFC 6238) code generation and a SQLite-backed store of TOTP entries.
+//!
+//! The stable API is re-exported at the crate root: [`Totp`] generates codes, [`TotpEntry`] is a
+//! stored account, [`TotpDatabase`] persists entries, and [`base32_decode`] / [`base32_encode`]
+//! handle secrets as authenticator apps display them. Failures are reported as [`Error`], whose
+//! variants (such as [`Error::EntryNotFound`]) can be matched on.
+//!
+//! ```no_run
+//! use console_totp::{Totp, TotpDatabase};
+//!
+//! let db = TotpDatabase::new("totp.db")?;
+//! if let Some(entry) = db.get_entry_by_name("github")? {
+//!     println!("{}", Totp::from_entry(&entry)?.generate()?);
+//! }
+//! # Ok::<(), console_totp::Error>(())
+//! ```
+
+pub mod base32;
+pub mod database;
+pub mod error;
+pub mod totp;
+
+// Import/export and integration helpers behind the `totp-console` binary. They are public so the
+// binary can use them, but they aren't part of the stable API and may change between releases.
+#[doc(hidden)]
+pub mod aegis;
+#[doc(hidden)]
+pub mod andotp;
+#[doc(hidden)]
+pub mod csv_format;
+#[doc(hidden)]
+pub mod encoding;
+#[doc(hidden)]
+pub mod format;
+#[doc(hidden)]
+pub mod freeotp;
+#[doc(hidden)]
+pub mod keychain;
+#[doc(hidden)]
+pub mod kv;
+#[doc(hidden)]
+pub mod otpauth;
+#[doc(hidden)]
+pub mod qrcode;
+#[doc(hidden)]
+pub mod screen;
+
+pub use crate::base32::{base32_decode, base32_encode};
+pub use crate::database::{DeletedEntry, TotpDatabase, TotpEntry};
+pub use crate::error::{Error, Result};
+pub use crate::totp::{HashAlgorithm, Totp};
@@ -1,450 +1,3547 @@
-mod base32;
-mod database;
-mod totp;
-mod kv;
-mod qrcode;
+mod config;
+mod exit;
+mod osc52;
+mod style;
+mod table;
+mod template;
+mod tui;
+mod verbose;
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
-use database::TotpDatabase;
-use crate::base32::base32_decode;
-use crate::database::TotpEntry;
-use crate::kv::get_cloudflare_kv;
-use crate::qrcode::{read_totp_qr_from_file};
-use crate::totp::Totp;
+use std::process::ExitCode;
+use std::io::{IsTerminal, Read, Write};
+use anyhow::Context;
+use console_totp::database::{self, EntryFilter, EntryPage, Move, Page, SearchMode, SortOrder};
+use console_totp::encoding::SecretEncoding;
+use console_totp::format::{self, ExportFormat, ImportFormat};
+use console_totp::keychain;
+use console_totp::kv;
+use console_totp::otpauth;
+use console_totp::screen::{capture_screen, Region};
+use console_totp::qrcode::{is_scannable_file, read_totp_qr, read_totp_qr_from_file, render_qr_terminal, safe_file_stem, write_qr_png, write_qr_svg, QrError, QrOutput};
+use console_totp::totp::{unix_time, unix_time_ms, validate_params, validate_truncation_offset, weak_secret, DEFAULT_DIGITS, DEFAULT_PERIOD, MIN_SECRET_LEN};
+use console_totp::{base32_decode, base32_encode, Error, HashAlgorithm, Totp, TotpDatabase, TotpEntry};
+use crate::exit::Failure;
+use crate::style::ColorChoice;
+use crate::table::Table;
+use crate::template::Template;
 
 #[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    let db = TotpDatabase::new("totp.db")?;
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => match e.downcast_ref::<Failure>() {
+            // The command has already explained what went wrong
+            Some(failure) => failure.exit_code(),
+            None => {
+                eprintln!("{}Error: {:#}", style::icon("❌ "), e);
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
+
+async fn run() -> anyhow::Result<()> {
+    let mut args = env::args().collect::<Vec<_>>();
+
+    // Global options may appear anywhere on the command line
+    let mut color: Option<ColorChoice> = None;
+    let mut quiet = false;
+    let mut ephemeral = false;
+    let mut profile: Option<String> = None;
+    let mut verbosity: u8 = 0;
+    let mut i = 1;
+    while i < args.len() {
+        if let Some(count) = verbose::count_flag(&args[i]) {
+            verbosity = verbosity.saturating_add(count);
+            args.remove(i);
+            continue;
+        }
+        if args[i] == "--quiet" || args[i] == "-q" {
+            quiet = true;
+            args.remove(i);
+            continue;
+        }
+        if args[i] == "--ephemeral" {
+            ephemeral = true;
+            args.remove(i);
+            continue;
+        }
+        if let Some(value) = args[i].strip_prefix("--profile=") {
+            profile = Some(value.to_string());
+            args.remove(i);
+            continue;
+        }
+        if args[i] == "--profile" {
+            if i + 1 >= args.len() {
+                eprintln!("{}--profile requires a name", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            profile = args.drain(i..i + 2).nth(1);
+            continue;
+        }
+
+        let value = if let Some(value) = args[i].strip_prefix("--color=") {
+            let value = value.to_string();
+            args.remove(i);
+            value
+        } else if args[i] == "--color" {
+            if i + 1 >= args.len() {
+                eprintln!("{}--color requires a value (auto, always or never)", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            args.drain(i..i + 2).nth(1).unwrap_or_default()
+        } else {
+            i += 1;
+            continue;
+        };
+
+        match ColorChoice::parse(&value) {
+            Some(choice) => color = Some(choice),
+            None => {
+                eprintln!("{}Invalid --color value: {} (expected auto, always or never)", style::icon("❌ "), value);
+                return Err(Failure::InvalidInput.into());
+            }
+        }
+    }
+    verbose::init(verbosity);
+    let mut config = config::load()?;
+    style::init(color.or(config.color).unwrap_or(ColorChoice::Auto));
+    style::init_quiet(quiet);
+
+    // The flag wins over the environment; an ephemeral session has no database to pick
+    let profile = profile.or_else(|| env::var("TOTP_PROFILE").ok().filter(|profile| !profile.is_empty()));
+    if let Some(profile) = profile.filter(|_| !ephemeral) {
+        if let Err(reason) = config::validate_profile(&profile) {
+            eprintln!("{}Invalid profile ({})", style::icon("❌ "), reason);
+            return Err(Failure::InvalidInput.into());
+        }
+        config.use_profile(&profile)?;
+    }
 
-    let args = env::args().collect::<Vec<_>>();
+    // An ephemeral session starts empty and is discarded on exit, leaving the database untouched
+    let db = if ephemeral {
+        TotpDatabase::in_memory()?
+    } else {
+        TotpDatabase::new(config.database())?
+    };
 
     if args.len() < 2 {
         print_usage();
         return Ok(());
     }
-    
-    let mut clipboard = arboard::Clipboard::new()?;
 
     match args[1].as_str() {
         "add" => {
-            if args.len() < 4 {
-                eprintln!("❌ Usage: totp-console add <name> <secret> [issuer]");
-                return Ok(());
+            let mut positional: Vec<&str> = Vec::new();
+            let mut force = false;
+            let mut account: Option<&str> = None;
+            let mut secret_source: Option<SecretSource> = None;
+            let mut truncation_offset: Option<u8> = None;
+            let mut epoch = 0;
+            let mut expires_at: Option<chrono::DateTime<chrono::Utc>> = None;
+            let mut encoding = SecretEncoding::default();
+            let mut login_url: Option<String> = None;
+            let mut backup_codes: Option<&str> = None;
+            let mut sanitize = false;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--force" => {
+                        force = true;
+                        i += 1;
+                    }
+                    "--sanitize" => {
+                        sanitize = true;
+                        i += 1;
+                    }
+                    "--prompt" => {
+                        secret_source = Some(SecretSource::Prompt);
+                        i += 1;
+                    }
+                    "--secret-stdin" => {
+                        secret_source = Some(SecretSource::Stdin);
+                        i += 1;
+                    }
+                    "--secret-file" => {
+                        if i + 1 < args.len() {
+                            secret_source = Some(SecretSource::File(&args[i + 1]));
+                            i += 2;
+                        } else {
+                            eprintln!("{}--secret-file requires a path", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--account" | "--label" => {
+                        if i + 1 < args.len() {
+                            account = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}{} requires a value", style::icon("❌ "), args[i]);
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--encoding" => {
+                        encoding = parse_encoding(args.get(i + 1))?;
+                        i += 2;
+                    }
+                    "--url" => {
+                        if i + 1 < args.len() {
+                            login_url = parse_login_url(&args[i + 1])?;
+                            i += 2;
+                        } else {
+                            eprintln!("{}--url requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--backup-codes" => {
+                        if i + 1 < args.len() {
+                            backup_codes = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--backup-codes requires a file (or - for stdin)", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--truncation-offset" => {
+                        if i + 1 < args.len() {
+                            truncation_offset = parse_truncation_offset(&args[i + 1], config.new_entry().algorithm)?;
+                            i += 2;
+                        } else {
+                            eprintln!("{}--truncation-offset requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--epoch" => {
+                        if i + 1 < args.len() {
+                            epoch = parse_epoch(&args[i + 1])?;
+                            i += 2;
+                        } else {
+                            eprintln!("{}--epoch requires a Unix timestamp in seconds", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--expires" => {
+                        expires_at = Some(parse_time("--expires", args.get(i + 1))?);
+                        i += 2;
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => {
+                        positional.push(value);
+                        i += 1;
+                    }
+                }
             }
 
-            let name = &args[2];
-            let secret = &args[3];
-            let issuer = args.get(4).cloned();
+            // When the secret comes from elsewhere, only name and issuer are positional
+            let secret_args = if secret_source.is_some() { 0 } else { 1 };
+            if positional.len() < 1 + secret_args || positional.len() > 2 + secret_args {
+                eprintln!("{}Usage: totp-console add <name> <secret> [issuer] [--account <label>] [--url <url>] [--backup-codes <file>] [--encoding <encoding>] [--sanitize] [--force]", style::icon("❌ "));
+                eprintln!("       totp-console add <name> [issuer] --prompt | --secret-stdin | --secret-file <path>");
+                return Err(Failure::InvalidInput.into());
+            }
 
-            // Validate secret
-            if base32_decode(secret).is_err() {
-                eprintln!("❌ Invalid base32 secret");
-                return Ok(());
+            if backup_codes == Some("-") && matches!(secret_source, Some(SecretSource::Stdin)) {
+                eprintln!("{}--secret-stdin and --backup-codes - can't both read stdin", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let name = match checked_name(positional[0], sanitize) {
+                Ok(name) => name,
+                Err(reason) => {
+                    eprintln!("{}Invalid name ({})", style::icon("❌ "), reason);
+                    return Err(Failure::InvalidInput.into());
+                }
+            };
+            let name = name.as_str();
+            let secret = match secret_source {
+                Some(source) => source.read(&format!("Secret for {}: ", name))?,
+                None => positional[1].to_string(),
+            };
+            let secret = match encoding.to_base32(&secret) {
+                Ok(secret) => secret,
+                Err(e) => {
+                    eprintln!("{}Invalid secret ({})", style::icon("❌ "), e);
+                    return Err(Failure::InvalidInput.into());
+                }
+            };
+            let secret = secret.as_str();
+            let issuer = positional.get(1 + secret_args).map(|issuer| issuer.to_string());
+            let backup_codes = match backup_codes {
+                Some(path) => read_backup_codes(path)?,
+                None => Vec::new(),
+            };
+
+            if let Err(reason) = validate_secret(secret, force) {
+                eprintln!("{}Invalid secret ({})", style::icon("❌ "), reason);
+                return Err(Failure::InvalidInput.into());
             }
 
             let entry = TotpEntry {
-                id: None,
-                name: name.clone(),
-                secret: secret.clone(),
+                name: name.to_string(),
+                secret: secret.to_string(),
                 issuer,
-                created_at: String::new(),
+                account: account.map(str::to_string),
+                truncation_offset,
+                epoch,
+                expires_at,
+                login_url,
+                backup_codes,
+                ..config.new_entry()
+            };
+            warn_if_expired(&entry);
+
+            match db.add_entry(&entry) {
+                Ok(_) if style::quiet() => {}
+                Ok(_) => println!("{}Added TOTP entry: {}", style::icon("✅ "), name),
+                Err(e) if database::is_duplicate(&e) => {
+                    eprintln!("{}Entry already exists: {}", style::icon("❌ "), name);
+                    return Err(Failure::AlreadyExists.into());
+                }
+                Err(e) => return Err(anyhow::Error::from(e).context("Failed to add entry")),
+            }
+        }
+        "add-url" => {
+            let mut positional: Vec<&str> = Vec::new();
+            let mut force = false;
+            let mut sanitize = false;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--force" => force = true,
+                    "--sanitize" => sanitize = true,
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => positional.push(value),
+                }
+            }
+
+            if positional.len() != 1 {
+                eprintln!("{}Usage: totp-console add-url <otpauth_url> [--sanitize] [--force]", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let mut entry = match otpauth::parse(positional[0]) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    eprintln!("{}Invalid otpauth URL ({})", style::icon("❌ "), e);
+                    return Err(Failure::InvalidInput.into());
+                }
             };
+            match checked_name(&entry.name, sanitize) {
+                Ok(name) => entry.name = name,
+                Err(reason) => {
+                    eprintln!("{}Invalid name in URL ({})", style::icon("❌ "), reason);
+                    return Err(Failure::InvalidInput.into());
+                }
+            }
+            if let Err(reason) = validate_secret(&entry.secret, force) {
+                eprintln!("{}Invalid secret in URL ({})", style::icon("❌ "), reason);
+                return Err(Failure::InvalidInput.into());
+            }
 
             match db.add_entry(&entry) {
-                Ok(_) => println!("✅ Added TOTP entry: {}", name),
-                Err(e) => eprintln!("❌ Failed to add entry: {}", e),
+                Ok(_) if style::quiet() => {}
+                Ok(_) => println!("{}Added TOTP entry: {}", style::icon("✅ "), entry.name),
+                Err(e) if database::is_duplicate(&e) => {
+                    eprintln!("{}Entry already exists: {}", style::icon("❌ "), entry.name);
+                    return Err(Failure::AlreadyExists.into());
+                }
+                Err(e) => return Err(anyhow::Error::from(e).context("Failed to add entry")),
+            }
+        }
+        "gen" => {
+            let mut bytes = 20usize;
+            let mut name: Option<&str> = None;
+            let mut issuer: Option<&str> = None;
+            let mut show_qr = false;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--bytes" => {
+                        if i + 1 < args.len() {
+                            match args[i + 1].parse::<usize>() {
+                                Ok(n) if (MIN_SECRET_LEN..=64).contains(&n) => bytes = n,
+                                _ => {
+                                    eprintln!("{}--bytes must be between {} and 64", style::icon("❌ "), MIN_SECRET_LEN);
+                                    return Err(Failure::InvalidInput.into());
+                                }
+                            }
+                            i += 2;
+                        } else {
+                            eprintln!("{}--bytes requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--name" => {
+                        if i + 1 < args.len() {
+                            name = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--name requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--issuer" => {
+                        if i + 1 < args.len() {
+                            issuer = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--issuer requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--qr" => {
+                        show_qr = true;
+                        i += 1;
+                    }
+                    _ => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), args[i]);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+
+            if let Some(name) = name
+                && let Err(reason) = database::validate_name(name) {
+                    eprintln!("{}Invalid name ({})", style::icon("❌ "), reason);
+                    return Err(Failure::InvalidInput.into());
+                }
+
+            // Secrets must come from the OS CSPRNG
+            let mut secret = vec![0u8; bytes];
+            getrandom::fill(&mut secret).map_err(|e| anyhow::anyhow!("Failed to gather randomness: {}", e))?;
+
+            let entry = TotpEntry {
+                name: name.unwrap_or("account").to_string(),
+                secret: base32_encode(&secret),
+                issuer: issuer.map(str::to_string),
+                ..config.new_entry()
+            };
+            let url = otpauth::build(&entry);
+
+            if style::quiet() {
+                println!("{}", entry.secret);
+            } else {
+                println!("{}Secret: {}", style::icon("🔐 "), entry.secret);
+                println!("{}URL:    {}", style::icon("🔗 "), url);
+            }
+
+            if show_qr {
+                println!();
+                println!("{}", render_qr_terminal(&url)?);
+            }
+
+            if name.is_some() {
+                match db.add_entry(&entry) {
+                    Ok(_) if style::quiet() => {}
+                    Ok(_) => println!("{}Added TOTP entry: {}", style::icon("✅ "), entry.name),
+                    Err(e) if database::is_duplicate(&e) => {
+                        eprintln!("{}Entry already exists: {}", style::icon("❌ "), entry.name);
+                        return Err(Failure::AlreadyExists.into());
+                    }
+                    Err(e) => return Err(anyhow::Error::from(e).context("Failed to add entry")),
+                }
             }
         }
         "read" => {
-            if args.len() != 3 {
-                eprintln!("❌ Usage: totp-console read <image_path>");
+            let mut positional: Vec<&str> = Vec::new();
+            let mut force = false;
+            let mut sanitize = false;
+            let mut dir: Option<&str> = None;
+            let mut screen = false;
+            let mut region: Option<Region> = None;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--force" => {
+                        force = true;
+                        i += 1;
+                    }
+                    "--sanitize" => {
+                        sanitize = true;
+                        i += 1;
+                    }
+                    "--screen" => {
+                        screen = true;
+                        i += 1;
+                    }
+                    "--region" => {
+                        let Some(geometry) = args.get(i + 1) else {
+                            eprintln!("{}--region requires a geometry such as 400x400+100+200", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        };
+                        match Region::parse(geometry) {
+                            Some(parsed) => region = Some(parsed),
+                            None => {
+                                eprintln!("{}--region must be <width>x<height>+<x>+<y>, e.g. 400x400+100+200, got {}", style::icon("❌ "), geometry);
+                                return Err(Failure::InvalidInput.into());
+                            }
+                        }
+                        i += 2;
+                    }
+                    "--dir" => {
+                        if i + 1 < args.len() {
+                            dir = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--dir requires a directory", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => {
+                        positional.push(value);
+                        i += 1;
+                    }
+                }
+            }
+
+            if region.is_some() && !screen {
+                eprintln!("{}--region picks part of the screen and only works with --screen", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            if let Some(dir) = dir {
+                if !positional.is_empty() || screen {
+                    eprintln!("{}--dir reads every image in a directory and can't be combined with <image_path> or --screen", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                }
+                return read_qr_dir(&db, dir, force, sanitize);
+            }
+
+            if positional.len() != usize::from(!screen) {
+                eprintln!("{}Usage: totp-console read <image_path> | --dir <folder> | --screen [--region <geometry>] [--sanitize] [--force]", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            let (read, source) = if screen {
+                (capture_screen(region).and_then(read_totp_qr), "the screen")
+            } else {
+                (read_totp_qr_from_file(positional[0]), "image")
+            };
+            match read {
+                Ok(mut entry) => {
+                    match checked_name(&entry.name, sanitize) {
+                        Ok(name) => entry.name = name,
+                        Err(reason) => {
+                            eprintln!("{}Invalid name in QR code ({})", style::icon("❌ "), reason);
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    if let Err(reason) = validate_secret(&entry.secret, force) {
+                        eprintln!("{}Invalid secret in QR code ({})", style::icon("❌ "), reason);
+                        return Err(Failure::InvalidInput.into());
+                    }
+
+                    match db.add_entry(&entry) {
+                        Ok(_) if style::quiet() => {}
+                        Ok(_) => println!("{}Added TOTP entry from {}: {}", style::icon("✅ "), source, entry.name),
+                        Err(e) if database::is_duplicate(&e) => {
+                            eprintln!("{}Entry already exists: {}", style::icon("❌ "), entry.name);
+                            return Err(Failure::AlreadyExists.into());
+                        }
+                        Err(e) => return Err(anyhow::Error::from(e).context("Failed to add entry")),
+                    }
+                }
+                Err(e) => {
+                    eprintln!("{}Error reading TOTP QR code: {}", style::icon("❌ "), e);
+                    // XWayland only sees X11 windows, so a browser running natively on Wayland
+                    // comes out blank and the capture holds no QR code
+                    if screen && e.downcast_ref::<QrError>().is_some() && env::var_os("WAYLAND_DISPLAY").is_some() {
+                        eprintln!("   On Wayland the capture may be blank; take a screenshot and use `read <image_path>` instead");
+                    }
+                    return Err(Failure::InvalidInput.into());
+                }
+            }
+        }
+        "list" => {
+            let mut issuer: Option<&str> = None;
+            let mut group_by_issuer = false;
+            let mut since: Option<chrono::DateTime<chrono::Utc>> = None;
+            let mut deleted = false;
+            let mut all = false;
+            let mut page = Page::default();
+            let mut sort = SortOrder::default();
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--limit" | "--offset" => {
+                        parse_page_flag(&args[i], args.get(i + 1), &mut page)?;
+                        i += 2;
+                    }
+                    "--issuer" => {
+                        if i + 1 < args.len() {
+                            issuer = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--issuer requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--group-by-issuer" => {
+                        group_by_issuer = true;
+                        i += 1;
+                    }
+                    "--since" => {
+                        since = Some(parse_time("--since", args.get(i + 1))?);
+                        i += 2;
+                    }
+                    "--deleted" => {
+                        deleted = true;
+                        i += 1;
+                    }
+                    "--all" => {
+                        all = true;
+                        i += 1;
+                    }
+                    "--sort" => {
+                        let Some(value) = args.get(i + 1) else {
+                            eprintln!("{}--sort requires a value (name, created or position)", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        };
+                        sort = match SortOrder::parse(value) {
+                            Some(sort) => sort,
+                            None => {
+                                eprintln!("{}Invalid --sort value: {} (expected name, created or position)", style::icon("❌ "), value);
+                                return Err(Failure::InvalidInput.into());
+                            }
+                        };
+                        i += 2;
+                    }
+                    _ => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), args[i]);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+
+            if deleted && (page != Page::default() || since.is_some() || all) {
+                eprintln!("{}--limit, --offset, --since and --all can't be combined with --deleted", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            if deleted {
+                let entries = db.get_deleted_entries()?;
+
+                if entries.is_empty() {
+                    println!("{}Recycle bin is empty", style::icon("📭 "));
+                    return Ok(());
+                }
+
+                println!("{}Deleted Entries:", style::icon("🗑️  "));
+                println!();
+
+                let mut table = Table::new(&["Name", "Issuer", "Deleted"]);
+                for deleted in entries {
+                    table.add_row(vec![
+                        deleted.entry.name,
+                        deleted.entry.issuer.unwrap_or_default(),
+                        deleted.deleted_at,
+                    ]);
+                }
+                print!("{}", table.render());
+                return Ok(());
+            }
+
+            // Expired entries stay out of the way unless asked for
+            let filter = EntryFilter { issuer, since, unexpired_at: None };
+            let (result, hidden) = if all {
+                (db.get_entries_page(&filter, sort, page)?, 0)
+            } else {
+                let now = chrono::Utc::now();
+                let result = db.get_entries_page(&EntryFilter { unexpired_at: Some(now), ..filter }, sort, page)?;
+                (result, db.count_expired(&filter, now)?)
+            };
+            let entries = &result.entries;
+            let hidden_note = || {
+                if hidden > 0 {
+                    let (noun, pronoun) = if hidden == 1 { ("entry", "it") } else { ("entries", "them") };
+                    println!("{}{} expired {} hidden; show {} with `list --all`", style::icon("⏳ "), hidden, noun, pronoun);
+                }
+            };
+
+            if entries.is_empty() && result.total > 0 {
+                println!("{}No entries in this range ({} in total)", style::icon("📭 "), result.total);
+                return Ok(());
+            }
+            if entries.is_empty() {
+                println!("{}No TOTP entries found", style::icon("📭 "));
+                hidden_note();
                 return Ok(());
             }
-            let image_path = &args[2];
-            match read_totp_qr_from_file(image_path) {
-                Ok(entry) => {
-                    match db.add_entry(&entry) {
-                        Ok(_) => println!("✅ Added TOTP entry from image: {}", entry.name),
-                        Err(e) => eprintln!("❌ Failed to add entry: {}", e),
-                    }
-                }
-                Err(e) => eprintln!("❌ Error reading TOTP QR code: {}", e),
+
+            println!("{}TOTP Entries:", style::icon("📋 "));
+            println!();
+
+            if group_by_issuer {
+                let mut groups: BTreeMap<Option<String>, Vec<&TotpEntry>> = BTreeMap::new();
+                for entry in entries {
+                    groups.entry(entry.issuer.clone()).or_default().push(entry);
+                }
+
+                // Named issuers first, entries without one last
+                let (unnamed, named): (Vec<_>, Vec<_>) = groups.into_iter().partition(|(issuer, _)| issuer.is_none());
+                for (issuer, entries) in named.into_iter().chain(unnamed) {
+                    println!("{}{} ({})", style::icon("🏢 "), issuer.as_deref().unwrap_or("(no issuer)"), entries.len());
+
+                    let mut table = Table::new(&["Name", "Parameters", "Created"]);
+                    for entry in &entries {
+                        table.add_row(vec![
+                            name_cell(entry),
+                            parameters_cell(entry),
+                            entry.created_at.clone(),
+                        ]);
+                    }
+                    println!("{}", table.render());
+                }
+            } else {
+                print!("{}", entries_table(entries).render());
+            }
+            print_page_footer(&result, page);
+            if hidden > 0 {
+                println!();
+                hidden_note();
+            }
+        }
+        "get" => {
+            let mut names: Vec<&str> = Vec::new();
+            let mut at: Option<u64> = None;
+            let mut template: Option<Template> = None;
+            let mut only_code = false;
+            let mut all = false;
+            let mut adjacent = false;
+            let mut backup_codes = false;
+            let mut group: Option<String> = None;
+            let mut overrides = ParamOverrides::default();
+            let mut issuer: Option<&str> = None;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--at" => {
+                        if i + 1 < args.len() {
+                            match args[i + 1].parse::<u64>() {
+                                Ok(time) => at = Some(time),
+                                Err(_) => {
+                                    eprintln!("{}--at requires a Unix timestamp in seconds", style::icon("❌ "));
+                                    return Err(Failure::InvalidInput.into());
+                                }
+                            }
+                            i += 2;
+                        } else {
+                            eprintln!("{}--at requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--at-rfc3339" => {
+                        if i + 1 < args.len() {
+                            match chrono::DateTime::parse_from_rfc3339(&args[i + 1]) {
+                                Ok(time) if time.timestamp() >= 0 => at = Some(time.timestamp() as u64),
+                                _ => {
+                                    eprintln!("{}--at-rfc3339 requires a timestamp like 2024-01-01T12:00:00Z, after 1970", style::icon("❌ "));
+                                    return Err(Failure::InvalidInput.into());
+                                }
+                            }
+                            i += 2;
+                        } else {
+                            eprintln!("{}--at-rfc3339 requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--only-code" => {
+                        only_code = true;
+                        i += 1;
+                    }
+                    "--all" => {
+                        all = true;
+                        i += 1;
+                    }
+                    "--adjacent" => {
+                        adjacent = true;
+                        i += 1;
+                    }
+                    "--backup-codes" => {
+                        backup_codes = true;
+                        i += 1;
+                    }
+                    flag if is_group_flag(flag) => {
+                        group = Some(group_separator(flag)?);
+                        i += 1;
+                    }
+                    "--algorithm" | "--digits" | "--period" => {
+                        overrides.parse_flag(&args[i], args.get(i + 1))?;
+                        i += 2;
+                    }
+                    "--issuer" => {
+                        if i + 1 < args.len() {
+                            issuer = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--issuer requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--format" | "--template" => {
+                        if i + 1 < args.len() {
+                            match Template::parse(&args[i + 1]) {
+                                Ok(parsed) => template = Some(parsed),
+                                Err(reason) => {
+                                    eprintln!("{}Invalid template ({})", style::icon("❌ "), reason);
+                                    return Err(Failure::InvalidInput.into());
+                                }
+                            }
+                            i += 2;
+                        } else {
+                            eprintln!("{}{} requires a template such as \"{{code}}\"", style::icon("❌ "), args[i]);
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => {
+                        names.push(value);
+                        i += 1;
+                    }
+                }
+            }
+
+            if let Err(reason) = overrides.validate() {
+                eprintln!("{}Invalid parameters ({})", style::icon("❌ "), reason);
+                return Err(Failure::InvalidInput.into());
+            }
+
+            if adjacent && (all || only_code || template.is_some()) {
+                eprintln!("{}--adjacent can't be combined with --all, --only-code or --format", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            if backup_codes {
+                let [name] = names.as_slice() else {
+                    eprintln!("{}Usage: totp-console get <name> --backup-codes [--issuer <issuer>]", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                };
+                if all || adjacent || only_code || template.is_some() || at.is_some() || group.is_some() || !overrides.is_empty() {
+                    eprintln!("{}--backup-codes only shows the stored codes and can't be combined with other output options", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                }
+                let Some(entry) = resolve_entry(&db, name, issuer)? else {
+                    eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                    return Err(Failure::NotFound.into());
+                };
+                if entry.backup_codes.is_empty() {
+                    eprintln!("{}{} has no backup codes", style::icon("❌ "), entry.name);
+                    eprintln!("   Store them with `totp-console update {} --backup-codes <file>`", entry.name);
+                    return Err(Failure::NotConfigured.into());
+                }
+
+                if !style::quiet() {
+                    println!("{}Backup codes for {} ({}):", style::icon("🔐 "), entry.name, entry.backup_codes.len());
+                }
+                for code in &entry.backup_codes {
+                    println!("{}", code);
+                }
+                return Ok(());
+            }
+
+            if all {
+                if !names.is_empty() {
+                    eprintln!("{}--all can't be combined with entry names", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                }
+
+                let entries: Vec<TotpEntry> = match issuer {
+                    Some(issuer) => db.get_entries_by_issuer(issuer)?,
+                    None => db.get_all_entries()?,
+                }
+                .into_iter()
+                .map(|entry| overrides.apply(entry))
+                .collect();
+                if entries.is_empty() {
+                    println!("{}No TOTP entries found", style::icon("📭 "));
+                    return Ok(());
+                }
+
+                // Custom output goes line by line; otherwise a one-shot snapshot like a single `loop` frame
+                if only_code || template.is_some() || at.is_some() || style::quiet() {
+                    for entry in &entries {
+                        warn_if_expired(entry);
+                        print_code(entry, at, only_code, template.as_ref(), group.as_deref())?;
+                    }
+                    return Ok(());
+                }
+
+                let mut table = Table::new(&["Name", "Code", "Expires in"]);
+                for entry in &entries {
+                    let totp = Totp::from_entry(entry)?;
+                    let code = totp.generate()?;
+                    table.add_row(vec![name_cell(entry), style::group_code(&code, group.as_deref()),
+                                       style::remaining(totp.time_remaining()?)]);
+                }
+                print!("{}", table.render());
+                return Ok(());
+            }
+
+            if names.is_empty() {
+                eprintln!("{}Usage: totp-console get <name>... | --all [--issuer <issuer>] [--at <unix_time> | --at-rfc3339 <time>] [--algorithm <alg>] [--digits <n>] [--period <secs>] [--group[=<sep>]] [--adjacent | --format <template> | --only-code]", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            // Report each name that can't be resolved but keep going with the rest
+            let mut failure: Option<Failure> = None;
+            for name in names {
+                match resolve_entry(&db, name, issuer) {
+                    Ok(Some(entry)) => {
+                        let entry = overrides.apply(entry);
+                        warn_if_expired(&entry);
+                        if adjacent {
+                            print_adjacent_codes(&entry, at, group.as_deref())?;
+                        } else {
+                            print_code(&entry, at, only_code, template.as_ref(), group.as_deref())?;
+                        }
+                        record_usage(&db, &config, &entry.name, "get");
+                    }
+                    Ok(None) => {
+                        eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                        failure.get_or_insert(Failure::NotFound);
+                    }
+                    Err(e) => match e.downcast::<Failure>() {
+                        Ok(reason) => {
+                            failure.get_or_insert(reason);
+                        }
+                        Err(e) => return Err(e),
+                    },
+                }
+            }
+
+            if let Some(failure) = failure {
+                return Err(failure.into());
+            }
+        }
+        "copy" => {
+            let mut positional: Vec<&str> = Vec::new();
+            let mut watch = false;
+            let mut clear_on_exit = false;
+            let mut osc52 = false;
+            let mut group: Option<String> = None;
+            let mut issuer: Option<&str> = None;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--watch" => {
+                        watch = true;
+                        i += 1;
+                    }
+                    "--clear-on-exit" => {
+                        clear_on_exit = true;
+                        i += 1;
+                    }
+                    "--osc52" => {
+                        osc52 = true;
+                        i += 1;
+                    }
+                    flag if is_group_flag(flag) => {
+                        group = Some(group_separator(flag)?);
+                        i += 1;
+                    }
+                    "--issuer" => {
+                        if i + 1 < args.len() {
+                            issuer = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--issuer requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => {
+                        positional.push(value);
+                        i += 1;
+                    }
+                }
+            }
+
+            if positional.len() != 1 {
+                eprintln!("{}Usage: totp-console copy <name> [--issuer <issuer>] [--osc52 | --watch [--clear-on-exit] [--group[=<sep>]]]", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            if clear_on_exit && !watch {
+                eprintln!("{}--clear-on-exit only applies together with --watch", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            // The terminal can't be asked what its clipboard holds, so there is nothing to watch or clear
+            if osc52 && watch {
+                eprintln!("{}--osc52 copies once and can't be combined with --watch", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            // A plain copy never shows the code, so there is nothing to group
+            if group.is_some() && !watch {
+                eprintln!("{}--group only applies together with --watch; the clipboard always gets the plain code", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let name = positional[0];
+            match resolve_entry(&db, name, issuer)? {
+                Some(entry) if watch => {
+                    let options = LoopOptions { copy: true, clear_on_exit, group, ..LoopOptions::default() };
+                    run_live_loop(&[entry], &options).await?;
+                }
+                Some(entry) if osc52 => {
+                    let remaining = copy_code(&db, &config, &entry, true)?;
+                    if !style::quiet() {
+                        println!("{}Sent TOTP code for {} to your terminal's clipboard, valid for {} seconds", style::icon("✅ "),
+                                 entry.name, remaining);
+                        println!("   If nothing was copied, your terminal doesn't support OSC 52 or has it turned off");
+                    }
+                }
+                Some(entry) => {
+                    let remaining = copy_code(&db, &config, &entry, false)?;
+                    if !style::quiet() {
+                        println!("{}Copied TOTP code for {}, valid for {} seconds", style::icon("✅ "),
+                                 entry.name, remaining);
+                    }
+                }
+                None => {
+                    eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                    return Err(Failure::NotFound.into());
+                }
+            }
+        }
+        "open" => {
+            let mut positional: Vec<&str> = Vec::new();
+            let mut issuer: Option<&str> = None;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--issuer" => {
+                        if i + 1 < args.len() {
+                            issuer = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--issuer requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => {
+                        positional.push(value);
+                        i += 1;
+                    }
+                }
+            }
+
+            if positional.len() != 1 {
+                eprintln!("{}Usage: totp-console open <name> [--issuer <issuer>]", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let name = positional[0];
+            let Some(entry) = resolve_entry(&db, name, issuer)? else {
+                eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                return Err(Failure::NotFound.into());
+            };
+            let Some(login_url) = entry.login_url.as_deref() else {
+                eprintln!("{}{} has no login page", style::icon("❌ "), entry.name);
+                eprintln!("   Set one with `totp-console update {} --url <url>`", entry.name);
+                return Err(Failure::NotConfigured.into());
+            };
+
+            // Copy first, so the code is ready by the time the page has loaded
+            let remaining = copy_code(&db, &config, &entry, false)?;
+            open_in_browser(login_url)?;
+            if !style::quiet() {
+                println!("{}Opened {} and copied its code, valid for {} seconds", style::icon("✅ "),
+                         login_url, remaining);
+            }
+        }
+        "url" => {
+            let mut positional: Vec<&str> = Vec::new();
+            let mut all = false;
+            let mut issuer: Option<&str> = None;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--all" => {
+                        all = true;
+                        i += 1;
+                    }
+                    "--issuer" => {
+                        if i + 1 < args.len() {
+                            issuer = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--issuer requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => {
+                        positional.push(value);
+                        i += 1;
+                    }
+                }
+            }
+
+            let entries = match (all, positional.as_slice()) {
+                (true, []) => match issuer {
+                    Some(issuer) => db.get_entries_by_issuer(issuer)?,
+                    None => db.get_all_entries()?,
+                },
+                (false, [name]) => match resolve_entry(&db, name, issuer)? {
+                    Some(entry) => vec![entry],
+                    None => {
+                        eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                        return Err(Failure::NotFound.into());
+                    }
+                },
+                _ => {
+                    eprintln!("{}Usage: totp-console url <name> | --all [--issuer <issuer>]", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                }
+            };
+
+            // Bare URLs, one per line, so the output can be piped or pasted as is
+            for entry in &entries {
+                warn_if_not_portable(entry);
+                println!("{}", otpauth::build(entry));
+            }
+        }
+        "qr" => {
+            let mut positional: Vec<&str> = Vec::new();
+            let mut output = QrOutput::Utf8;
+            let mut issuer: Option<&str> = None;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--output" => {
+                        if i + 1 < args.len() {
+                            match QrOutput::parse(&args[i + 1]) {
+                                Some(parsed) => output = parsed,
+                                None => {
+                                    let names = QrOutput::ALL.map(QrOutput::as_str);
+                                    eprintln!("{}Unknown QR output: {} (expected {})", style::icon("❌ "), args[i + 1], format::choices(&names));
+                                    return Err(Failure::InvalidInput.into());
+                                }
+                            }
+                            i += 2;
+                        } else {
+                            eprintln!("{}--output requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--issuer" => {
+                        if i + 1 < args.len() {
+                            issuer = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--issuer requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => {
+                        positional.push(value);
+                        i += 1;
+                    }
+                }
+            }
+
+            let (name, file_path) = match (output.writes_file(), positional.as_slice()) {
+                (false, [name]) => (*name, None),
+                (true, [name, file_path]) => (*name, Some(*file_path)),
+                (true, [_]) => {
+                    eprintln!("{}--output {} writes a file; give its path: totp-console qr <name> --output {} <file_path>",
+                              style::icon("❌ "), output.as_str(), output.as_str());
+                    return Err(Failure::InvalidInput.into());
+                }
+                (false, [_, _]) => {
+                    eprintln!("{}utf8 output is printed to the terminal; use --output png or --output svg to write a file", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                }
+                _ => {
+                    eprintln!("{}Usage: totp-console qr <name> [--issuer <issuer>] [--output utf8 | --output png|svg <file_path>]", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                }
+            };
+
+            let Some(entry) = resolve_entry(&db, name, issuer)? else {
+                eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                return Err(Failure::NotFound.into());
+            };
+            warn_if_not_portable(&entry);
+
+            let url = otpauth::build(&entry);
+            match (output, file_path) {
+                (QrOutput::Png, Some(path)) => write_qr_png(&url, path).with_context(|| format!("Failed to write {}", path))?,
+                (QrOutput::Svg, Some(path)) => write_qr_svg(&url, path).with_context(|| format!("Failed to write {}", path))?,
+                _ => {
+                    println!("{}", render_qr_terminal(&url)?);
+                    return Ok(());
+                }
+            }
+            if !style::quiet() {
+                println!("{}Wrote the QR code for {} to {}; it contains the secret, so keep it safe", style::icon("✅ "),
+                         entry.name, file_path.unwrap_or_default());
+            }
+        }
+        "delete" => {
+            let mut positional: Vec<&str> = Vec::new();
+            let mut yes = false;
+            let mut issuer: Option<&str> = None;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--yes" | "-y" => {
+                        yes = true;
+                        i += 1;
+                    }
+                    "--issuer" => {
+                        if i + 1 < args.len() {
+                            issuer = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--issuer requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => {
+                        positional.push(value);
+                        i += 1;
+                    }
+                }
+            }
+
+            if positional.len() != 1 {
+                eprintln!("{}Usage: totp-console delete <name> [--issuer <issuer>] [--yes]", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let name = positional[0];
+            let Some(entry) = resolve_entry(&db, name, issuer)? else {
+                eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                return Err(Failure::NotFound.into());
+            };
+
+            let description = match &entry.issuer {
+                Some(issuer) => format!("'{}' (issuer: {})", entry.name, issuer),
+                None => format!("'{}'", entry.name),
+            };
+            if !yes && is_interactive() && !confirm(&format!("Delete {}?", description))? {
+                println!("Deletion cancelled");
+                return Ok(());
+            }
+
+            match db.delete_entry(&entry.name) {
+                Ok(()) => {
+                    println!("{}Deleted entry: {}", style::icon("✅ "), description);
+                    println!("   Restore it with: totp-console restore {}", entry.name);
+                }
+                // Removed by another process since it was looked up
+                Err(Error::EntryNotFound(_)) => {
+                    eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                    return Err(Failure::NotFound.into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        "restore" if args[2..].iter().any(|arg| arg == "--dump") => {
+            let mut dump_path: Option<&str> = None;
+            let mut yes = false;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--dump" => {
+                        if i + 1 < args.len() {
+                            dump_path = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--dump requires a file (or - for stdin)", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--yes" | "-y" => {
+                        yes = true;
+                        i += 1;
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    _ => {
+                        eprintln!("{}Usage: totp-console restore --dump <file_path|-> [--yes]", style::icon("❌ "));
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+            let dump_path = dump_path.unwrap_or_default();
+
+            // Unlike `purge`, there's no going ahead without asking: a script or a dump piped into
+            // stdin would replace everything without anyone having agreed to it
+            if !yes && !is_interactive() {
+                eprintln!("{}Restoring a dump replaces the whole database; pass --yes to do it without a terminal to confirm on", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let sql = read_import_source(dump_path)?;
+            let count = db.get_stats()?.count;
+            let question = format!(
+                "Replace the whole database ({} entries, the recycle bin and the usage log) with {}? This cannot be undone.",
+                count, dump_path
+            );
+            if !yes && !confirm(&question)? {
+                println!("Restore cancelled");
+                return Ok(());
+            }
+
+            match db.restore_dump(&sql) {
+                Ok(restored) => println!("{}Restored {} entries from {}", style::icon("✅ "), restored, dump_path),
+                Err(Error::InvalidDump(reason)) => {
+                    eprintln!("{}Can't restore {}: {}; the database is unchanged", style::icon("❌ "), dump_path, reason);
+                    return Err(Failure::InvalidInput.into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        "restore" => {
+            if args.len() != 3 {
+                eprintln!("{}Usage: totp-console restore <name>", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let name = &args[2];
+            match db.restore_entry(name) {
+                Ok(restored) => println!("{}Restored entry: {}", style::icon("✅ "), restored),
+                Err(Error::EntryNotFound(_)) => {
+                    eprintln!("{}No deleted entry named: {}", style::icon("❌ "), name);
+                    return Err(Failure::NotFound.into());
+                }
+                Err(Error::DuplicateName(_)) => {
+                    eprintln!("{}An entry named '{}' already exists; rename or delete it first", style::icon("❌ "), name);
+                    return Err(Failure::AlreadyExists.into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        "favorite" | "unfavorite" => {
+            let favorite = args[1] == "favorite";
+            if args.len() != 3 {
+                eprintln!("{}Usage: totp-console {} <name>", style::icon("❌ "), args[1]);
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let name = &args[2];
+            let Some(entry) = db.get_entry_by_name(name)? else {
+                eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                return Err(Failure::NotFound.into());
+            };
+
+            db.set_favorite(&entry.name, favorite)?;
+            if favorite {
+                println!("{}{} is now a favorite and listed first", style::icon("⭐ "), entry.name);
+            } else {
+                println!("{}{} is no longer a favorite", style::icon("✅ "), entry.name);
+            }
+        }
+        "move" => {
+            let mut positional: Vec<&str> = Vec::new();
+            let mut moves: Vec<Move> = Vec::new();
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--up" => {
+                        moves.push(Move::Up);
+                        i += 1;
+                    }
+                    "--down" => {
+                        moves.push(Move::Down);
+                        i += 1;
+                    }
+                    "--to" => {
+                        match args.get(i + 1).map(|value| value.parse::<usize>()) {
+                            Some(Ok(position)) if position > 0 => moves.push(Move::To(position)),
+                            _ => {
+                                eprintln!("{}--to requires a position of 1 or more", style::icon("❌ "));
+                                return Err(Failure::InvalidInput.into());
+                            }
+                        }
+                        i += 2;
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => {
+                        positional.push(value);
+                        i += 1;
+                    }
+                }
+            }
+
+            let ([name], [to]) = (positional.as_slice(), moves.as_slice()) else {
+                eprintln!("{}Usage: totp-console move <name> --up | --down | --to <position>", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            };
+            match db.move_entry(name, *to) {
+                Ok(position) => println!("{}Moved {} to position {}", style::icon("✅ "), name, position),
+                Err(Error::EntryNotFound(_)) => {
+                    eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                    return Err(Failure::NotFound.into());
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+        "purge" => {
+            let mut name: Option<&str> = None;
+            let mut yes = false;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    "--yes" | "-y" => yes = true,
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value if name.is_none() => name = Some(value),
+                    _ => {
+                        eprintln!("{}Usage: totp-console purge [name] [--yes]", style::icon("❌ "));
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+
+            let target = match name {
+                Some(name) => format!("deleted entries named '{}'", name),
+                None => "all deleted entries".to_string(),
+            };
+            if !yes && is_interactive() && !confirm(&format!("Permanently remove {}? This cannot be undone.", target))? {
+                println!("Purge cancelled");
+                return Ok(());
+            }
+
+            let purged = db.purge_deleted(name)?;
+            if purged == 0 && name.is_some() {
+                eprintln!("{}No deleted entry named: {}", style::icon("❌ "), name.unwrap_or_default());
+                return Err(Failure::NotFound.into());
+            }
+            println!("{}Purged {} deleted entries", style::icon("🗑️  "), purged);
+        }
+        "loop" => {
+            let mut name: Option<&str> = None;
+            let mut options = LoopOptions::default();
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--count" => {
+                        if i + 1 < args.len() {
+                            match args[i + 1].parse::<u64>() {
+                                Ok(n) if n > 0 => options.count = Some(n),
+                                _ => {
+                                    eprintln!("{}--count requires a positive integer", style::icon("❌ "));
+                                    return Err(Failure::InvalidInput.into());
+                                }
+                            }
+                            i += 2;
+                        } else {
+                            eprintln!("{}--count requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--no-clear" => {
+                        options.no_clear = true;
+                        i += 1;
+                    }
+                    "--copy" => {
+                        options.copy = true;
+                        i += 1;
+                    }
+                    "--clear-on-exit" => {
+                        options.clear_on_exit = true;
+                        i += 1;
+                    }
+                    flag if is_group_flag(flag) => {
+                        options.group = Some(group_separator(flag)?);
+                        i += 1;
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value => {
+                        if name.is_some() {
+                            eprintln!("{}Usage: totp-console loop [name] [--count <n>] [--no-clear] [--group[=<sep>]] [--copy [--clear-on-exit]]", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                        name = Some(value);
+                        i += 1;
+                    }
+                }
+            }
+
+            if options.copy && name.is_none() {
+                eprintln!("{}--copy needs a single entry: totp-console loop <name> --copy", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            if options.clear_on_exit && !options.copy {
+                eprintln!("{}--clear-on-exit only applies together with --copy", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let entries = if let Some(name) = name {
+                // Single entry loop
+                match db.get_entry_by_name(name)? {
+                    Some(entry) => vec![entry],
+                    None => {
+                        eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                        return Err(Failure::NotFound.into());
+                    }
+                }
+            } else {
+                // All entries loop
+                db.get_all_entries()?
+            };
+
+            run_live_loop(&entries, &options).await?;
+        }
+        "kv-status" | "sync" => {
+            // `sync --dry-run` is the same as `kv-status`
+            let dry_run = args[1] == "kv-status" || args[2..].iter().any(|arg| arg == "--dry-run");
+            let verify = args[2..].iter().any(|arg| arg == "--verify");
+            if dry_run && verify {
+                eprintln!("{}--verify checks a real sync and can't be combined with --dry-run or kv-status", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            let flags: Vec<String> = args[2..].iter().filter(|arg| *arg != "--dry-run" && *arg != "--verify").cloned().collect();
+            match configured_kv(&config, &flags)? {
+                Some(kv) if dry_run => {
+                    let local = db.get_all_entries()?;
+                    let remote = kv.load_from_kv().await?;
+                    print_kv_diff(&kv::diff_entries(&local, &remote), local.len(), remote.len());
+                }
+                Some(kv) => {
+                    let entries = db.get_all_entries()?;
+                    let summary = kv.sync_to_kv(&entries).await?;
+                    if summary.migrated {
+                        println!("{}Moved the vault from the old single-key format to one key per entry", style::icon("📦 "));
+                    }
+                    println!("{}Synced {} entries to Cloudflare KV ({} written, {} deleted, {} unchanged)",
+                             style::icon("✅ "), entries.len(), summary.written, summary.deleted, summary.unchanged);
+
+                    if verify {
+                        let remote = kv.load_from_kv().await?;
+                        let diff = kv::diff_entries(&entries, &remote);
+                        if !diff.is_empty() {
+                            eprintln!("{}Verification failed: Cloudflare KV returned {} entries that don't match the {} sent",
+                                      style::icon("❌ "), remote.len(), entries.len());
+                            eprint!("{}", kv_diff_table(&diff).render());
+                            anyhow::bail!("Cloudflare KV does not match the local entries after the sync");
+                        }
+                        println!("{}Verified: Cloudflare KV returns the same {} entries", style::icon("✅ "), remote.len());
+                    }
+                }
+                None => {
+                    eprintln!("{}Cloudflare KV not configured. Add a [kv] section to config.toml or set CF_ACCOUNT_ID, CF_NAMESPACE_ID, and CF_API_TOKEN (or store the token with kv-login).", style::icon("❌ "));
+                    return Err(Failure::NotConfigured.into());
+                }
+            }
+        }
+
+        "load" => {
+            match configured_kv(&config, &args[2..])? {
+                Some(kv) => {
+                    let entries = kv.load_from_kv().await?;
+                    println!("{}Loaded {} entries from Cloudflare KV", style::icon("✅ "), entries.len());
+                    let mut added = 0;
+
+                    for entry in entries {
+                        match db.add_entry(&entry) {
+                            Ok(_) => {
+                                added += 1;
+                                println!("{}Added: {}", style::icon("✅ "), entry.name);
+                            }
+                            Err(_) => {
+                                println!("{}Skipped (already exists): {}", style::icon("⚠️  "), entry.name);
+                            }
+                        }
+                    }
+
+                    println!("{}Loaded {} new entries from Cloudflare KV", style::icon("📥 "), added);
+                }
+                None => {
+                    eprintln!("{}Cloudflare KV not configured. Add a [kv] section to config.toml or set CF_ACCOUNT_ID, CF_NAMESPACE_ID, and CF_API_TOKEN (or store the token with kv-login).", style::icon("❌ "));
+                    return Err(Failure::NotConfigured.into());
+                }
+            }
+        }
+        "profiles" => {
+            if args.len() > 2 {
+                eprintln!("{}Usage: totp-console profiles", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let dir = config::profile_dir();
+            let mut profiles = vec![(config::DEFAULT_PROFILE.to_string(), config.default_database().to_path_buf())];
+            if let Some(dir) = &dir {
+                for name in config::list_profiles(dir)? {
+                    let path = dir.join(format!("{}.db", name));
+                    profiles.push((name, path));
+                }
+            }
+
+            // Marked like the current branch in `git branch`
+            let mut table = Table::new(&["Profile", "Database"]);
+            for (name, path) in profiles {
+                let marker = if !ephemeral && name == config.profile() { "* " } else { "  " };
+                table.add_row(vec![format!("{}{}", marker, name), path.display().to_string()]);
+            }
+            print!("{}", table.render());
+        }
+        "kv-login" => {
+            let mut remove = false;
+            let mut source = SecretSource::Prompt;
+            for flag in &args[2..] {
+                match flag.as_str() {
+                    "--remove" => remove = true,
+                    "--stdin" => source = SecretSource::Stdin,
+                    _ => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+
+            if remove {
+                if keychain::delete_token()? {
+                    println!("{}Removed the Cloudflare API token from the keychain", style::icon("✅ "));
+                } else {
+                    println!("No Cloudflare API token in the keychain");
+                }
+            } else {
+                let token = source.read("Cloudflare API token: ")?;
+                if token.is_empty() {
+                    eprintln!("{}No API token given", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                }
+                keychain::set_token(&token)?;
+                println!("{}Stored the Cloudflare API token in the keychain", style::icon("✅ "));
+                println!("   It is used instead of api_token in config.toml, kv.json or CF_API_TOKEN");
+            }
+        }
+        "export" => {
+            let mut file_path: Option<&str> = None;
+            let mut format = ExportFormat::Json;
+            let mut qr_dir: Option<&str> = None;
+            let mut names: Vec<&str> = Vec::new();
+            let mut since: Option<chrono::DateTime<chrono::Utc>> = None;
+            let mut compact = false;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--format" => {
+                        if i + 1 < args.len() {
+                            match ExportFormat::parse(&args[i + 1]) {
+                                Some(parsed) => format = parsed,
+                                None => {
+                                    let names = ExportFormat::ALL.map(ExportFormat::as_str);
+                                    eprintln!("{}Unknown export format: {} (expected {})", style::icon("❌ "), args[i + 1], format::choices(&names));
+                                    return Err(Failure::InvalidInput.into());
+                                }
+                            }
+                            i += 2;
+                        } else {
+                            eprintln!("{}--format requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--stdout" => {
+                        if file_path.is_some() {
+                            eprintln!("{}--stdout replaces <file_path>; use one or the other", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                        file_path = Some("-");
+                        i += 1;
+                    }
+                    "--qr-dir" => {
+                        if i + 1 < args.len() {
+                            qr_dir = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--qr-dir requires a directory", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--compact" => {
+                        compact = true;
+                        i += 1;
+                    }
+                    "--since" => {
+                        since = Some(parse_time("--since", args.get(i + 1))?);
+                        i += 2;
+                    }
+                    "--name" => {
+                        if i + 1 < args.len() {
+                            names.push(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--name requires an entry name", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value if file_path.is_none() => {
+                        file_path = Some(value);
+                        i += 1;
+                    }
+                    _ => {
+                        eprintln!("{}Usage: totp-console export <file_path|-> [--format json|csv] [--compact] | --stdout | --qr-dir <dir> [--name <entry>... | --since <time>]", style::icon("❌ "));
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+
+            if since.is_some() && !names.is_empty() {
+                eprintln!("{}--since and --name both pick entries; use one or the other", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            let entries = match since {
+                Some(since) => {
+                    let filter = EntryFilter { since: Some(since), ..EntryFilter::default() };
+                    db.get_entries_page(&filter, SortOrder::Name, Page::default())?.entries
+                }
+                None => select_export_entries(&db, &names)?,
+            };
+
+            if let Some(dir) = qr_dir {
+                if file_path.is_some() {
+                    eprintln!("{}--qr-dir writes one file per entry and can't be combined with <file_path>", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                }
+                if compact {
+                    eprintln!("{}--compact only applies to JSON exports", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                }
+
+                if entries.is_empty() {
+                    eprintln!("{}No entries to export", style::icon("⚠️  "));
+                    return Ok(());
+                }
+
+                std::fs::create_dir_all(dir)?;
+
+                // Names that only differ in case or unsafe characters would collide on disk
+                let mut used_stems = HashSet::new();
+                for entry in &entries {
+                    let stem = safe_file_stem(&entry.name);
+                    let mut unique_stem = stem.clone();
+                    let mut suffix = 2;
+                    while !used_stems.insert(unique_stem.to_lowercase()) {
+                        unique_stem = format!("{}-{}", stem, suffix);
+                        suffix += 1;
+                    }
+
+                    let path = std::path::Path::new(dir).join(format!("{}.png", unique_stem));
+                    write_qr_png(&otpauth::build(entry), &path)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                }
+
+                println!("{}Wrote {} QR codes to {}", style::icon("✅ "), entries.len(), dir);
+                return Ok(());
+            }
+
+            let Some(file_path) = file_path else {
+                eprintln!("{}Usage: totp-console export <file_path|-> [--format json|csv] [--compact] | --stdout | --qr-dir <dir> [--name <entry>... | --since <time>]", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            };
+            if compact && format != ExportFormat::Json {
+                eprintln!("{}--compact only applies to JSON exports", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            // Without local row ids that would only confuse another database
+            let entries: Vec<TotpEntry> = entries.into_iter().map(|entry| TotpEntry { id: None, ..entry }).collect();
+
+            if entries.is_empty() {
+                eprintln!("{}No entries to export", style::icon("⚠️  "));
+                return Ok(());
+            }
+
+            // `-` streams the export to stdout (e.g. into gpg), so the summary goes to stderr instead
+            if file_path == "-" {
+                format.write(std::io::stdout().lock(), &entries, compact)?;
+                if !style::quiet() {
+                    eprintln!("{}Exported {} entries to stdout", style::icon("✅ "), entries.len());
+                }
+                return Ok(());
+            }
+
+            let file = std::fs::File::create(file_path).with_context(|| format!("Failed to create {}", file_path))?;
+            format.write(std::io::BufWriter::new(file), &entries, compact)?;
+
+            println!("{}Exported {} entries to {}", style::icon("✅ "), entries.len(), file_path);
+        }
+        "dump" => {
+            let mut file_path: Option<&str> = None;
+            for arg in &args[2..] {
+                match arg.as_str() {
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value if file_path.is_none() => file_path = Some(value),
+                    _ => {
+                        eprintln!("{}Usage: totp-console dump <file_path|->", style::icon("❌ "));
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+            let Some(file_path) = file_path else {
+                eprintln!("{}Usage: totp-console dump <file_path|->", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            };
+
+            let sql = db.dump()?;
+            let count = db.get_stats()?.count;
+
+            // Like `export -`, the summary goes to stderr so the dump can be piped
+            if file_path == "-" {
+                std::io::stdout().lock().write_all(sql.as_bytes())?;
+                if !style::quiet() {
+                    eprintln!("{}Dumped {} entries to stdout", style::icon("✅ "), count);
+                }
+                return Ok(());
+            }
+
+            std::fs::write(file_path, sql).with_context(|| format!("Failed to write {}", file_path))?;
+            println!("{}Dumped {} entries to {}", style::icon("✅ "), count, file_path);
+        }
+        "import" => {
+            let mut file_path: Option<&str> = None;
+            let mut format = ImportFormat::Json;
+            let mut force = false;
+            let mut dry_run = false;
+            let mut overwrite = false;
+            let mut sanitize = false;
+            let mut encoding = SecretEncoding::default();
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--format" => {
+                        if i + 1 < args.len() {
+                            match ImportFormat::parse(&args[i + 1]) {
+                                Some(parsed) => format = parsed,
+                                None => {
+                                    let names = ImportFormat::ALL.map(ImportFormat::as_str);
+                                    eprintln!("{}Unknown import format: {} (expected {})", style::icon("❌ "), args[i + 1], format::choices(&names));
+                                    return Err(Failure::InvalidInput.into());
+                                }
+                            }
+                            i += 2;
+                        } else {
+                            eprintln!("{}--format requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--force" => {
+                        force = true;
+                        i += 1;
+                    }
+                    "--dry-run" => {
+                        dry_run = true;
+                        i += 1;
+                    }
+                    "--overwrite" => {
+                        overwrite = true;
+                        i += 1;
+                    }
+                    "--sanitize" => {
+                        sanitize = true;
+                        i += 1;
+                    }
+                    "--encoding" => {
+                        encoding = parse_encoding(args.get(i + 1))?;
+                        i += 2;
+                    }
+                    "--stdin" => {
+                        if file_path.is_some() {
+                            eprintln!("{}--stdin replaces <file_path>; use one or the other", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                        file_path = Some("-");
+                        i += 1;
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value if file_path.is_none() => {
+                        file_path = Some(value);
+                        i += 1;
+                    }
+                    _ => {
+                        eprintln!("{}Usage: totp-console import <file_path|-> [--format json|csv|aegis|andotp|freeotp] [--encoding <encoding>] [--sanitize] [--force] [--overwrite] [--dry-run]", style::icon("❌ "));
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+
+            let Some(file_path) = file_path else {
+                eprintln!("{}Usage: totp-console import <file_path|-> [--format json|csv|aegis|andotp|freeotp] [--encoding <encoding>] [--sanitize] [--force] [--overwrite] [--dry-run]", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            };
+
+            let mut skipped = 0;
+            let backup = format.read(&read_import_source(file_path)?)?;
+            for warning in &backup.warnings {
+                eprintln!("{}{}", style::icon("⚠️  "), warning);
+            }
+            let entries = importable_entries(backup.entries, &mut skipped);
+
+            let mut added = 0;
+            let mut updated = 0;
+            // Entries that passed validation but still couldn't generate a code
+            let mut unusable = 0;
+            // A dry run tracks the names it would take, so duplicates within the file are caught too
+            let mut taken: HashSet<String> = if dry_run {
+                db.get_all_entries()?.into_iter().map(|entry| entry.name).collect()
+            } else {
+                HashSet::new()
+            };
+
+            for mut entry in entries {
+                let skip = if dry_run { "Would skip" } else { "Skipped" };
+                match checked_name(&entry.name, sanitize) {
+                    Ok(name) => entry.name = name,
+                    Err(reason) => {
+                        eprintln!("{}{} \"{}\" ({})", style::icon("⚠️  "), skip, entry.name.escape_debug(), reason);
+                        skipped += 1;
+                        continue;
+                    }
+                }
+
+                // Validate secret
+                match encoding.to_base32(&entry.secret) {
+                    Ok(secret) => entry.secret = secret,
+                    Err(e) => {
+                        eprintln!("{}{} {} ({})", style::icon("⚠️  "), skip, entry.name, e);
+                        skipped += 1;
+                        continue;
+                    }
+                }
+                if let Err(reason) = validate_secret(&entry.secret, force) {
+                    eprintln!("{}{} {} ({})", style::icon("⚠️  "), skip, entry.name, reason);
+                    skipped += 1;
+                    continue;
+                }
+
+                if let Err(reason) = validate_params(entry.digits, entry.period) {
+                    eprintln!("{}{} {} ({})", style::icon("⚠️  "), skip, entry.name, reason);
+                    skipped += 1;
+                    continue;
+                }
+
+                // The checks above look at each field on its own; generating a code runs the whole
+                // pipeline, so an entry that would only fail at first use is caught here instead
+                if let Err(e) = Totp::from_entry(&entry).and_then(|totp| totp.generate()) {
+                    eprintln!("{}{} {} (can't generate a code: {})", style::icon("⚠️  "), skip, entry.name, e);
+                    skipped += 1;
+                    unusable += 1;
+                    continue;
+                }
+
+                if dry_run {
+                    if taken.insert(entry.name.clone()) {
+                        added += 1;
+                        println!("{}Would add: {}", style::icon("➕ "), entry.name);
+                    } else if overwrite {
+                        updated += 1;
+                        println!("{}Would update: {}", style::icon("🔄 "), entry.name);
+                    } else {
+                        println!("{}Would skip (already exists): {}", style::icon("⚠️  "), entry.name);
+                        skipped += 1;
+                    }
+                    continue;
+                }
+
+                match db.add_entry(&entry) {
+                    Ok(_) => {
+                        added += 1;
+                        println!("{}Added: {}", style::icon("✅ "), entry.name);
+                    }
+                    Err(e) if overwrite && database::is_duplicate(&e) => {
+                        db.overwrite_entry(&entry).with_context(|| format!("Failed to update {}", entry.name))?;
+                        updated += 1;
+                        println!("{}Updated: {}", style::icon("🔄 "), entry.name);
+                    }
+                    Err(_) => {
+                        println!("{}Skipped (already exists): {}", style::icon("⚠️  "), entry.name);
+                        skipped += 1;
+                    }
+                }
+            }
+
+            match (dry_run, overwrite) {
+                (true, false) => println!("{}Dry run: would import {} entries, skip {}; nothing was changed", style::icon("🔍 "), added, skipped),
+                (true, true) => println!("{}Dry run: would add {} entries, update {}, skip {}; nothing was changed", style::icon("🔍 "), added, updated, skipped),
+                (false, false) => println!("{}Imported {} entries, skipped {}", style::icon("📥 "), added, skipped),
+                (false, true) => println!("{}Added {} entries, updated {}, skipped {}", style::icon("📥 "), added, updated, skipped),
+            }
+            if unusable > 0 {
+                eprintln!("{}{} of the skipped entries looked valid but couldn't generate a code; check their parameters in the source file",
+                          style::icon("⚠️  "), unusable);
+            }
+        }
+        "search" => {
+            let mut query: Option<&str> = None;
+            let mut page = Page::default();
+            let mut mode = SearchMode::Contains;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--limit" | "--offset" => {
+                        parse_page_flag(&args[i], args.get(i + 1), &mut page)?;
+                        i += 2;
+                    }
+                    "--exact" | "--prefix" => {
+                        if mode != SearchMode::Contains {
+                            eprintln!("{}--exact and --prefix can't be combined", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                        mode = if args[i] == "--exact" { SearchMode::Exact } else { SearchMode::Prefix };
+                        i += 1;
+                    }
+                    flag if flag.starts_with("--") => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                    value if query.is_none() => {
+                        query = Some(value);
+                        i += 1;
+                    }
+                    _ => {
+                        eprintln!("{}Usage: totp-console search <query> [--exact | --prefix] [--limit <n>] [--offset <n>]", style::icon("❌ "));
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+
+            let Some(query) = query else {
+                eprintln!("{}Usage: totp-console search <query> [--exact | --prefix] [--limit <n>] [--offset <n>]", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            };
+            let result = db.search_entries_page(query, mode, page)?;
+
+            if result.entries.is_empty() && result.total > 0 {
+                println!("{}No matches in this range ({} in total)", style::icon("🔍 "), result.total);
+                return Ok(());
+            }
+            if result.entries.is_empty() {
+                println!("{}No entries found matching '{}'", style::icon("🔍 "), query);
+                return Ok(());
+            }
+
+            println!("{}Search Results for '{}':", style::icon("🔍 "), query);
+            println!();
+
+            print!("{}", entries_table(&result.entries).render());
+            print_page_footer(&result, page);
+        }
+        "update" => {
+            if args.len() < 3 {
+                eprintln!("{}Usage: totp-console update <name> [--secret [secret]] [--issuer <issuer>] [--url <url>] [--backup-codes <file>] [--expires <time>] [--truncation-offset <n|dynamic>] [--force]", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let name = &args[2];
+            let mut new_secret: Option<String> = None;
+            let mut new_issuer: Option<&str> = None;
+            let mut new_truncation: Option<&str> = None;
+            let mut new_login_url: Option<Option<String>> = None;
+            let mut new_backup_codes: Option<&str> = None;
+            let mut new_expires_at: Option<Option<chrono::DateTime<chrono::Utc>>> = None;
+            // Read only once the entry is known to exist, so a typo in the name doesn't first
+            // ask for a secret
+            let mut secret_source: Option<SecretSource> = None;
+            let mut force = false;
+
+            let mut i = 3;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--secret" => {
+                        // Without a value, ask for it so the secret stays out of argv and shell history
+                        if i + 1 < args.len() && !args[i + 1].starts_with("--") {
+                            new_secret = Some(args[i + 1].clone());
+                            i += 2;
+                        } else {
+                            secret_source = Some(SecretSource::Prompt);
+                            i += 1;
+                        }
+                    }
+                    "--secret-stdin" => {
+                        secret_source = Some(SecretSource::Stdin);
+                        i += 1;
+                    }
+                    "--secret-file" => {
+                        if i + 1 < args.len() {
+                            secret_source = Some(SecretSource::File(&args[i + 1]));
+                            i += 2;
+                        } else {
+                            eprintln!("{}--secret-file requires a path", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--issuer" => {
+                        if i + 1 < args.len() {
+                            new_issuer = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--issuer requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--url" => {
+                        // An empty value removes the login page
+                        if i + 1 < args.len() {
+                            new_login_url = Some(parse_login_url(&args[i + 1])?);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--url requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--backup-codes" => {
+                        if i + 1 < args.len() {
+                            new_backup_codes = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--backup-codes requires a file (or - for stdin)", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--expires" => {
+                        // An empty value removes the expiration
+                        new_expires_at = match args.get(i + 1) {
+                            Some(value) if value.is_empty() => Some(None),
+                            value => Some(Some(parse_time("--expires", value)?)),
+                        };
+                        i += 2;
+                    }
+                    "--truncation-offset" => {
+                        if i + 1 < args.len() {
+                            new_truncation = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--truncation-offset requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--force" => {
+                        force = true;
+                        i += 1;
+                    }
+                    _ => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), args[i]);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+
+            if new_secret.is_none() && secret_source.is_none() && new_issuer.is_none() && new_truncation.is_none() && new_login_url.is_none() && new_backup_codes.is_none() && new_expires_at.is_none() {
+                eprintln!("{}Please specify at least one field to update (--secret, --issuer, --url, --backup-codes, --expires or --truncation-offset)", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            if new_backup_codes == Some("-") && matches!(secret_source, Some(SecretSource::Stdin)) {
+                eprintln!("{}--secret-stdin and --backup-codes - can't both read stdin", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+
+            let Some(entry) = db.get_entry_by_name(name)? else {
+                eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                return Err(Failure::NotFound.into());
+            };
+            // The valid range depends on the entry's hash algorithm
+            let new_truncation = match new_truncation {
+                Some(value) => Some(parse_truncation_offset(value, entry.algorithm)?),
+                None => None,
+            };
+
+            // Read before anything is written, so a missing file doesn't leave a half-done update
+            let new_backup_codes = match new_backup_codes {
+                Some(path) => Some(read_backup_codes(path)?),
+                None => None,
+            };
+
+            if let Some(source) = &secret_source {
+                new_secret = Some(source.read(&format!("New secret for {}: ", entry.name))?);
+            }
+            // Validate new secret if provided
+            if let Some(secret) = &new_secret
+                && let Err(reason) = validate_secret(secret, force) {
+                    eprintln!("{}Invalid secret ({})", style::icon("❌ "), reason);
+                    return Err(Failure::InvalidInput.into());
+                }
+
+            db.transaction(|db| {
+                if new_secret.is_some() || new_issuer.is_some() {
+                    db.update_entry(&entry.name, new_secret.as_deref(), new_issuer)?;
+                }
+                if let Some(offset) = new_truncation {
+                    db.set_truncation_offset(&entry.name, offset)?;
+                }
+                if let Some(login_url) = &new_login_url {
+                    db.set_login_url(&entry.name, login_url.as_deref())?;
+                }
+                if let Some(codes) = &new_backup_codes {
+                    db.set_backup_codes(&entry.name, codes)?;
+                }
+                if let Some(expires_at) = new_expires_at {
+                    db.set_expires_at(&entry.name, expires_at)?;
+                }
+                Ok(())
+            })?;
+            if let Some(expires_at) = new_expires_at {
+                warn_if_expired(&TotpEntry { expires_at, ..entry.clone() });
+            }
+            println!("{}Updated entry: {}", style::icon("✅ "), entry.name);
+        }
+        "info" => {
+            let stats = db.get_stats()?;
+
+            println!("{}Database Statistics", style::icon("📊 "));
+            println!("=====================");
+            println!("Total entries: {}", stats.count);
+
+            if let Some((name, created_at)) = &stats.oldest {
+                println!("Oldest entry:  {} ({})", name, created_at);
+            }
+            if let Some((name, created_at)) = &stats.newest {
+                println!("Newest entry:  {} ({})", name, created_at);
+            }
+
+            if stats.count > 0 {
+                println!();
+                println!("Algorithms:    {}", breakdown(&stats.algorithms, |algorithm| algorithm.to_string()));
+                println!("Digits:        {}", breakdown(&stats.digits, |digits| digits.to_string()));
+                println!("Periods:       {}", breakdown(&stats.periods, |period| format!("{}s", period)));
+            }
+
+            println!();
+            if ephemeral {
+                println!("Database file: none (ephemeral session)");
+            } else {
+                println!("Profile:       {}", config.profile());
+                let path = config.database();
+                match database_size(path) {
+                    Some(size) => println!("Database file: {} ({})", path.display(), human_size(size)),
+                    None => println!("Database file: {}", path.display()),
+                }
+            }
+        }
+        "history" => {
+            let mut name: Option<&str> = None;
+            let mut limit = HISTORY_LIMIT;
+
+            let mut i = 2;
+            while i < args.len() {
+                match args[i].as_str() {
+                    "--name" => {
+                        if i + 1 < args.len() {
+                            name = Some(&args[i + 1]);
+                            i += 2;
+                        } else {
+                            eprintln!("{}--name requires a value", style::icon("❌ "));
+                            return Err(Failure::InvalidInput.into());
+                        }
+                    }
+                    "--limit" => {
+                        match args.get(i + 1).map(|value| value.parse::<usize>()) {
+                            Some(Ok(value)) if value > 0 => limit = value,
+                            _ => {
+                                eprintln!("{}--limit requires a positive number", style::icon("❌ "));
+                                return Err(Failure::InvalidInput.into());
+                            }
+                        }
+                        i += 2;
+                    }
+                    flag => {
+                        eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                        return Err(Failure::InvalidInput.into());
+                    }
+                }
+            }
+
+            let records = db.get_usage_log(name, Some(limit))?;
+            if records.is_empty() {
+                println!("{}No codes recorded", style::icon("📭 "));
+                if !config.history {
+                    println!("History is off; add `history = true` to config.toml to start recording.");
+                }
+                return Ok(());
+            }
+
+            let mut table = Table::new(&["When (UTC)", "Name", "Action"]);
+            for record in records {
+                let when = chrono::DateTime::parse_from_rfc3339(&record.used_at)
+                    .map(|time| time.to_utc().format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or(record.used_at);
+                table.add_row(vec![when, record.name, record.action]);
+            }
+            print!("{}", table.render());
+        }
+        "doctor" => run_doctor(&db, &config).await?,
+        "tui" => {
+            if let Some(flag) = args.get(2) {
+                eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                return Err(Failure::InvalidInput.into());
+            }
+            if !is_interactive() {
+                eprintln!("{}tui needs an interactive terminal; use `list` or `get --all` instead", style::icon("❌ "));
+                return Err(Failure::InvalidInput.into());
+            }
+            tui::run(db.get_all_entries()?)?;
+        }
+        _ => {
+            eprintln!("{}Unknown command: {}", style::icon("❌ "), args[1]);
+            print_usage();
+            return Err(Failure::InvalidInput.into());
+        }
+    }
+
+    Ok(())
+}
+
+// The file `import` reads, or stdin for `-` so a decrypted backup (`gpg -d ... |`) never
+// touches the disk
+fn read_import_source(path: &str) -> anyhow::Result<String> {
+    if path == "-" {
+        let mut content = String::new();
+        std::io::stdin().read_to_string(&mut content).context("Failed to read stdin")?;
+        Ok(content)
+    } else {
+        std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path))
+    }
+}
+
+// `--backup-codes <file>` (or `-` for stdin): one code per line, ignoring blank lines and
+// surrounding whitespace. An empty file means no codes.
+fn read_backup_codes(path: &str) -> anyhow::Result<Vec<String>> {
+    let content = read_import_source(path)?;
+    Ok(content.lines().map(str::trim).filter(|code| !code.is_empty()).map(str::to_string).collect())
+}
+
+// `--since` for list and export, `--expires` for add and update: an RFC 3339 time, or a date
+// meaning midnight UTC
+fn parse_time(flag: &str, value: Option<&String>) -> anyhow::Result<chrono::DateTime<chrono::Utc>> {
+    let Some(value) = value else {
+        eprintln!("{}{} requires a time such as 2024-01-01T12:00:00Z or a date such as 2024-01-01", style::icon("❌ "), flag);
+        return Err(Failure::InvalidInput.into());
+    };
+    if let Ok(time) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(time.with_timezone(&chrono::Utc));
+    }
+    match chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => Ok(date.and_time(chrono::NaiveTime::MIN).and_utc()),
+        Err(_) => {
+            eprintln!("{}Invalid {} value: {} (expected a time such as 2024-01-01T12:00:00Z or a date such as 2024-01-01)", style::icon("❌ "), flag, value);
+            Err(Failure::InvalidInput.into())
+        }
+    }
+}
+
+// What `export` writes, sorted by name: every entry, or only those named with --name. Every
+// missing name is reported before failing, so nothing is exported by half.
+fn select_export_entries(db: &TotpDatabase, names: &[&str]) -> anyhow::Result<Vec<TotpEntry>> {
+    if names.is_empty() {
+        return Ok(db.get_all_entries()?);
+    }
+
+    let mut entries: Vec<TotpEntry> = Vec::new();
+    let mut missing = false;
+    for name in names {
+        match db.get_entry_by_name(name)? {
+            // The same entry may be named twice, in different case
+            Some(entry) if entries.iter().any(|selected| selected.id == entry.id) => {}
+            Some(entry) => entries.push(entry),
+            None => {
+                eprintln!("{}Entry not found: {}", style::icon("❌ "), name);
+                missing = true;
+            }
+        }
+    }
+    if missing {
+        return Err(Failure::NotFound.into());
+    }
+
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(entries)
+}
+
+// `read --dir`: adds the QR code from every image in `dir`, reporting each file on its own
+// line. A file that can't be read or added is reported and skipped, never ending the batch.
+fn read_qr_dir(db: &TotpDatabase, dir: &str, force: bool, sanitize: bool) -> anyhow::Result<()> {
+    let mut paths: Vec<std::path::PathBuf> = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read directory {}", dir))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_file() && is_scannable_file(path))
+        .collect();
+    paths.sort();
+
+    if paths.is_empty() {
+        println!("{}No images found in {}", style::icon("⚠️  "), dir);
+        return Ok(());
+    }
+
+    let mut added = 0;
+    for path in &paths {
+        let file = path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+        let mut entry = match read_totp_qr_from_file(path) {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("{}{}: {}", style::icon("⚠️  "), file, e);
+                continue;
+            }
+        };
+        match checked_name(&entry.name, sanitize) {
+            Ok(name) => entry.name = name,
+            Err(reason) => {
+                eprintln!("{}{}: invalid name \"{}\" ({})", style::icon("⚠️  "), file, entry.name.escape_debug(), reason);
+                continue;
+            }
+        }
+        if let Err(reason) = validate_secret(&entry.secret, force) {
+            eprintln!("{}{}: invalid secret for {} ({})", style::icon("⚠️  "), file, entry.name, reason);
+            continue;
+        }
+
+        match db.add_entry(&entry) {
+            Ok(_) => {
+                added += 1;
+                if !style::quiet() {
+                    println!("{}{}: added {}", style::icon("✅ "), file, entry.name);
+                }
+            }
+            Err(e) if database::is_duplicate(&e) => {
+                eprintln!("{}{}: entry already exists: {}", style::icon("⚠️  "), file, entry.name);
+            }
+            Err(e) => eprintln!("{}{}: failed to add {} ({})", style::icon("⚠️  "), file, entry.name, e),
+        }
+    }
+
+    if !style::quiet() {
+        println!("{}Added {} entries from {} images, skipped {}", style::icon("📥 "), added, paths.len(), paths.len() - added);
+    }
+    Ok(())
+}
+
+// Read-only comparison shown by `kv-status` and `sync --dry-run`
+fn print_kv_diff(diff: &kv::KvDiff, local: usize, remote: usize) {
+    if diff.is_empty() {
+        println!("{}Local and Cloudflare KV are in sync ({} entries)", style::icon("✅ "), local);
+        return;
+    }
+
+    print!("{}", kv_diff_table(diff).render());
+
+    println!();
+    println!("{} local, {} remote: {} local only, {} remote only, {} differing",
+             local, remote, diff.local_only.len(), diff.remote_only.len(), diff.differing.len());
+    println!("`sync` uploads local changes and deletes remote-only entries; `load` adds remote-only entries locally");
+}
+
+// One row per entry that differs between local and Cloudflare KV
+fn kv_diff_table(diff: &kv::KvDiff) -> Table {
+    let mut table = Table::new(&["Name", "Status"]);
+    for name in &diff.local_only {
+        table.add_row(vec![name.clone(), "local only".to_string()]);
+    }
+    for name in &diff.remote_only {
+        table.add_row(vec![name.clone(), "remote only".to_string()]);
+    }
+    for (name, fields) in &diff.differing {
+        table.add_row(vec![name.clone(), format!("differs ({})", fields.join(", "))]);
+    }
+    table
+}
+
+// The configured Cloudflare KV client for sync and load, or None if there are no credentials.
+// `--timeout <secs>`, `--retries <n>` and `--kv-key <key>` fall back to CF_TIMEOUT, CF_RETRIES and
+// CF_KV_KEY, and then to config.toml
+fn configured_kv(config: &config::Config, args: &[String]) -> anyhow::Result<Option<kv::CloudflareKV>> {
+    let mut options = match config.request_options().with_env() {
+        Ok(options) => options,
+        Err(reason) => {
+            eprintln!("{}Invalid {}", style::icon("❌ "), reason);
+            return Err(Failure::InvalidInput.into());
+        }
+    };
+    let mut key = env::var("CF_KV_KEY").ok().filter(|key| !key.is_empty()).unwrap_or_else(|| config.kv_key());
+
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            flag @ ("--timeout" | "--retries") => {
+                let Some(value) = args.get(i + 1) else {
+                    eprintln!("{}{} requires a value", style::icon("❌ "), flag);
+                    return Err(Failure::InvalidInput.into());
+                };
+                let parsed = if flag == "--timeout" {
+                    kv::parse_timeout(value).map(|timeout| options.timeout = timeout)
+                } else {
+                    kv::parse_retries(value).map(|retries| options.retries = retries)
+                };
+                if let Err(reason) = parsed {
+                    eprintln!("{}Invalid {} ({})", style::icon("❌ "), flag, reason);
+                    return Err(Failure::InvalidInput.into());
+                }
+                i += 2;
+            }
+            "--kv-key" => {
+                let Some(value) = args.get(i + 1) else {
+                    eprintln!("{}--kv-key requires a value", style::icon("❌ "));
+                    return Err(Failure::InvalidInput.into());
+                };
+                key = value.clone();
+                i += 2;
+            }
+            flag => {
+                eprintln!("{}Unknown flag: {}", style::icon("❌ "), flag);
+                return Err(Failure::InvalidInput.into());
+            }
+        }
+    }
+    if let Err(reason) = kv::validate_key(&key) {
+        eprintln!("{}Invalid KV key ({})", style::icon("❌ "), reason);
+        return Err(Failure::InvalidInput.into());
+    }
+
+    Ok(config.cloudflare_kv().map(|kv| kv.with_options(options).with_key(key)))
+}
+
+/// Whether a person is at the terminal to answer prompts.
+fn is_interactive() -> bool {
+    std::io::stdin().is_terminal() && std::io::stdout().is_terminal()
+}
+
+/// Asks a yes/no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> std::io::Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+// `--limit <n>` or `--offset <n>` for list and search
+fn parse_page_flag(flag: &str, value: Option<&String>, page: &mut Page) -> anyhow::Result<()> {
+    let Some(value) = value else {
+        eprintln!("{}{} requires a value", style::icon("❌ "), flag);
+        return Err(Failure::InvalidInput.into());
+    };
+    let Ok(count) = value.parse::<usize>() else {
+        eprintln!("{}{} must be a non-negative number, got {}", style::icon("❌ "), flag, value);
+        return Err(Failure::InvalidInput.into());
+    };
+
+    if flag == "--limit" {
+        page.limit = Some(count);
+    } else {
+        page.offset = count;
+    }
+    Ok(())
+}
+
+// "Showing 11-20 of 134 entries" when only part of the results fit on the page
+fn print_page_footer(result: &EntryPage, page: Page) {
+    if result.entries.len() < result.total {
+        println!();
+        println!("Showing {}-{} of {} entries", page.offset + 1, page.offset + result.entries.len(), result.total);
+    }
+}
+
+// Name, issuer, non-default parameters and creation time, as shown by list and search
+fn entries_table(entries: &[TotpEntry]) -> Table {
+    let mut table = Table::new(&["Name", "Account", "Issuer", "Parameters", "Created"]);
+    for entry in entries {
+        table.add_row(vec![
+            name_cell(entry),
+            entry.account.clone().unwrap_or_default(),
+            entry.issuer.clone().unwrap_or_default(),
+            parameters_cell(entry),
+            entry.created_at.clone(),
+        ]);
+    }
+    table
+}
+
+// ⭐ in front of favorites, when icons are shown
+fn favorite_marker(entry: &TotpEntry) -> &'static str {
+    if entry.favorite { style::icon("⭐ ") } else { "" }
+}
+
+// " (expired)" after the name once the entry has expired
+fn expired_tag(entry: &TotpEntry) -> &'static str {
+    if entry.is_expired_at(chrono::Utc::now()) { " (expired)" } else { "" }
+}
+
+// The name as list and search show it: starred when a favorite, tagged once expired
+fn name_cell(entry: &TotpEntry) -> String {
+    format!("{}{}{}", favorite_marker(entry), entry.name, expired_tag(entry))
+}
+
+// Codes still work after an entry expires, but using one is worth a second look
+fn warn_if_expired(entry: &TotpEntry) {
+    if let Some(expires_at) = entry.expires_at
+        && entry.is_expired_at(chrono::Utc::now())
+    {
+        eprintln!("{}{} expired on {}; delete it once it's no longer needed", style::icon("⚠️  "), entry.name,
+                  expires_at.format("%Y-%m-%d %H:%M UTC"));
+    }
+}
+
+// "name (account)" when the account label adds something the name doesn't already say
+fn display_name(entry: &TotpEntry) -> String {
+    match &entry.account {
+        Some(account) if !account.eq_ignore_ascii_case(&entry.name) => format!("{} ({})", entry.name, account),
+        _ => entry.name.clone(),
+    }
+}
+
+// Blank for the usual SHA1 / 6 digits / 30s so unusual entries stand out
+fn parameters_cell(entry: &TotpEntry) -> String {
+    let mut cell = if entry.algorithm != HashAlgorithm::default() || entry.digits != DEFAULT_DIGITS || entry.period != DEFAULT_PERIOD {
+        format!("{}, {} digits, {}s", entry.algorithm, entry.digits, entry.period)
+    } else {
+        String::new()
+    };
+    if let Some(offset) = entry.truncation_offset {
+        if !cell.is_empty() {
+            cell.push_str(", ");
+        }
+        cell.push_str(&format!("offset {}", offset));
+    }
+    cell
+}
+
+// `--encoding` on add and import: how the given secrets are written
+fn parse_encoding(value: Option<&String>) -> anyhow::Result<SecretEncoding> {
+    let Some(value) = value else {
+        eprintln!("{}--encoding requires a value", style::icon("❌ "));
+        return Err(Failure::InvalidInput.into());
+    };
+    match SecretEncoding::parse(value) {
+        Some(encoding) => Ok(encoding),
+        None => {
+            let names = SecretEncoding::ALL.map(SecretEncoding::as_str);
+            eprintln!("{}Unknown encoding: {} (expected {})", style::icon("❌ "), value, format::choices(&names));
+            Err(Failure::InvalidInput.into())
+        }
+    }
+}
+
+// `--truncation-offset <n|dynamic>`; `dynamic` clears the override
+fn parse_truncation_offset(value: &str, algorithm: HashAlgorithm) -> anyhow::Result<Option<u8>> {
+    if value.eq_ignore_ascii_case("dynamic") {
+        return Ok(None);
+    }
+
+    let checked = value
+        .parse::<u8>()
+        .map_err(|_| format!("expected a byte offset or `dynamic`, got {}", value))
+        .and_then(|offset| validate_truncation_offset(algorithm, offset).map(|_| offset));
+    match checked {
+        Ok(offset) => Ok(Some(offset)),
+        Err(reason) => {
+            eprintln!("{}Invalid --truncation-offset ({})", style::icon("❌ "), reason);
+            Err(Failure::InvalidInput.into())
+        }
+    }
+}
+
+// Opened only by the commands that need it, so everything else still works over SSH or in a
+// container without a display
+fn open_clipboard() -> anyhow::Result<arboard::Clipboard> {
+    arboard::Clipboard::new().map_err(|e| {
+        eprintln!("{}Clipboard unavailable: {}", style::icon("❌ "), e);
+        if osc52::over_ssh() {
+            eprintln!("   Over SSH, `copy <name> --osc52` puts the code on your local clipboard through the terminal");
+        } else {
+            eprintln!("   Use `get` to print the code instead, or run from a desktop session");
+        }
+        Failure::NotConfigured.into()
+    })
+}
+
+// Puts the entry's current code on the clipboard for `copy` and `open`, or with `osc52` asks the
+// terminal to. Returns the seconds it stays valid.
+fn copy_code(db: &TotpDatabase, config: &config::Config, entry: &TotpEntry, osc52: bool) -> anyhow::Result<u64> {
+    warn_if_expired(entry);
+    let totp = Totp::from_entry(entry)?;
+    let code = totp.generate()?;
+    let remaining = totp.time_remaining()?;
+
+    if osc52 {
+        osc52::copy(&code).context("Failed to write to the terminal")?;
+    } else {
+        open_clipboard()?.set_text(code)?;
+    }
+    record_usage(db, config, &entry.name, "copy");
+    Ok(remaining)
+}
+
+/// `add --url` and `update --url`: only web pages, since the value is handed to the system's
+/// opener, which would just as happily run a `file:` path. Empty means no login page.
+fn parse_login_url(value: &str) -> anyhow::Result<Option<String>> {
+    if value.is_empty() {
+        return Ok(None);
+    }
+    match url::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(Some(url.to_string())),
+        Ok(url) => {
+            eprintln!("{}Invalid --url (expected an http or https address, got {}:)", style::icon("❌ "), url.scheme());
+            Err(Failure::InvalidInput.into())
+        }
+        Err(e) => {
+            eprintln!("{}Invalid --url ({})", style::icon("❌ "), e);
+            Err(Failure::InvalidInput.into())
+        }
+    }
+}
+
+// Hands `url` to the desktop's default browser. Like the clipboard, this needs a desktop session.
+fn open_in_browser(url: &str) -> anyhow::Result<()> {
+    let mut command = if cfg!(target_os = "macos") {
+        std::process::Command::new("open")
+    } else if cfg!(windows) {
+        // `start` treats its first quoted argument as a window title, hence the empty one
+        let mut command = std::process::Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else {
+        std::process::Command::new("xdg-open")
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .arg(url)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => anyhow::bail!("{} could not open {} ({}); the code is on the clipboard", program, url, status),
+        Err(e) => anyhow::bail!("Failed to run {} to open {} ({}); the code is on the clipboard", program, url, e),
+    }
+}
+
+/// `get --algorithm/--digits/--period`: parameters used instead of the stored ones for this run
+/// only, to try out what a misconfigured provider might expect. The database is never changed.
+#[derive(Debug, Default)]
+struct ParamOverrides {
+    algorithm: Option<HashAlgorithm>,
+    digits: Option<u32>,
+    period: Option<u64>,
+}
+
+impl ParamOverrides {
+    fn parse_flag(&mut self, flag: &str, value: Option<&String>) -> anyhow::Result<()> {
+        let Some(value) = value else {
+            eprintln!("{}{} requires a value", style::icon("❌ "), flag);
+            return Err(Failure::InvalidInput.into());
+        };
+
+        let parsed = match flag {
+            "--algorithm" => HashAlgorithm::parse(value).map(|algorithm| self.algorithm = Some(algorithm)),
+            "--digits" => value.parse().ok().map(|digits| self.digits = Some(digits)),
+            _ => value.parse().ok().map(|period| self.period = Some(period)),
+        };
+        if parsed.is_none() {
+            eprintln!("{}Invalid {}: {}", style::icon("❌ "), flag, value);
+            return Err(Failure::InvalidInput.into());
+        }
+        Ok(())
+    }
+
+    fn is_empty(&self) -> bool {
+        self.algorithm.is_none() && self.digits.is_none() && self.period.is_none()
+    }
+
+    // Checked once up front instead of failing on the first entry
+    fn validate(&self) -> Result<(), String> {
+        validate_params(self.digits.unwrap_or(DEFAULT_DIGITS), self.period.unwrap_or(DEFAULT_PERIOD))
+    }
+
+    fn apply(&self, entry: TotpEntry) -> TotpEntry {
+        TotpEntry {
+            algorithm: self.algorithm.unwrap_or(entry.algorithm),
+            digits: self.digits.unwrap_or(entry.digits),
+            period: self.period.unwrap_or(entry.period),
+            ..entry
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct LoopOptions {
+    /// Stop after this many refreshes
+    count: Option<u64>,
+    no_clear: bool,
+    /// Put each new code on the clipboard (single entry only)
+    copy: bool,
+    /// Wipe our code from the clipboard when the loop ends
+    clear_on_exit: bool,
+    /// Separator shown in the middle of each code; the clipboard still gets the plain digits
+    group: Option<String>,
+}
+
+// Each page of a live view that doesn't fit on the screen stays up for this many refreshes
+const LIVE_PAGE_TICKS: u64 = 5;
+
+// Lines of a live frame besides the entries: title, rule, page line, the blank line and hint
+// at the bottom, and the line the cursor rests on
+const LIVE_FRAME_LINES: usize = 6;
+
+// The entries a live frame has room for on this terminal, and a line saying which page they
+// are when not all of them fit. The size is read every frame, so resizing takes effect at once
+fn live_page(total: usize, tick: u64) -> (std::ops::Range<usize>, Option<String>) {
+    let rows = ratatui::crossterm::terminal::size().map_or(usize::MAX, |(_, rows)| usize::from(rows));
+    let per_page = rows.saturating_sub(LIVE_FRAME_LINES).max(1);
+    if total <= per_page {
+        return (0..total, None);
+    }
+
+    let pages = total.div_ceil(per_page);
+    let page = (tick / LIVE_PAGE_TICKS) as usize % pages;
+    let start = page * per_page;
+    let end = (start + per_page).min(total);
+    (start..end, Some(format!("Page {}/{} (entries {}-{} of {})", page + 1, pages, start + 1, end, total)))
+}
+
+// Live view shared by `loop` and `copy --watch`
+async fn run_live_loop(entries: &[TotpEntry], options: &LoopOptions) -> anyhow::Result<()> {
+    if entries.is_empty() {
+        println!("{}No TOTP entries found", style::icon("📭 "));
+        return Ok(());
+    }
+
+    // Decode every secret once up front; each tick then only hashes the current time step
+    let totps = entries
+        .iter()
+        .map(|entry| Totp::from_entry(entry).with_context(|| format!("Invalid secret for {}", entry.name)))
+        .collect::<anyhow::Result<Vec<Totp>>>()?;
+
+    let mut clipboard = if options.copy { Some(open_clipboard()?) } else { None };
+
+    // Only redraw in place on an interactive terminal; otherwise append so output can be piped
+    let clear_screen = !options.no_clear && std::io::stdout().is_terminal();
+
+    if clear_screen {
+        println!("{}Live TOTP Mode (Press Ctrl+C to stop)", style::icon("🔄 "));
+        println!("=========================================");
+    }
+
+    let mut last_codes: HashMap<String, String> = HashMap::new();
+    let mut copied: Option<String> = None;
+    let mut ticks = 0u64;
+
+    // Trap Ctrl+C for the whole session (rather than per tick) so a press while a frame is
+    // being drawn isn't lost, and break out to the cleanup below instead of dying mid-frame
+    let ctrl_c = tokio::signal::ctrl_c();
+    tokio::pin!(ctrl_c);
+
+    if clear_screen {
+        // Hide the cursor while redrawing
+        print!("\x1B[?25l");
+    }
+
+    let result: anyhow::Result<()> = async {
+        loop {
+            if clear_screen {
+                // Clear screen
+                print!("\x1B[2J\x1B[1;1H");
+            }
+
+            println!("{}Live TOTP Codes - {}", style::icon("🔄 "), chrono::Utc::now().format("%H:%M:%S"));
+            println!("==========================================");
+            if options.copy {
+                println!("{}New codes are copied to the clipboard as they change", style::icon("📋 "));
             }
-        }
-        "list" => {
-            let entries = db.get_all_entries()?;
 
-            if entries.is_empty() {
-                println!("📭 No TOTP entries found");
-                return Ok(());
+            // Appended output has no screen to overflow, so it always shows every entry
+            let (visible, page) = if clear_screen { live_page(entries.len(), ticks) } else { (0..entries.len(), None) };
+            if let Some(page) = &page {
+                println!("{}", page);
             }
 
-            println!("📋 TOTP Entries:");
-            println!("================");
+            // One clock reading per frame, so every row agrees on the time
+            let now_ms = unix_time_ms(std::time::SystemTime::now())?;
+            for (entry, totp) in entries[visible.clone()].iter().zip(&totps[visible]) {
+                let code = totp.generate_at_time(now_ms / 1000)?;
+                let remaining = totp.time_remaining_at(now_ms / 1000);
 
-            for entry in entries {
-                println!("🔑 {}", entry.name);
-                if let Some(issuer) = entry.issuer {
-                    println!("   Issuer: {}", issuer);
+                let status = if last_codes.get(&entry.name) == Some(&code) {
+                    "  "
+                } else if style::enabled() {
+                    "🆕"
+                } else {
+                    "* "
+                };
+
+                println!("{} {}{:20} | {} | {}{}",
+                         status, style::icon(if entry.favorite { "⭐ " } else { "🔑 " }),
+                         format!("{}{}", entry.name, expired_tag(entry)),
+                         style::group_code(&code, options.group.as_deref()),
+                         style::countdown_bar(totp.progress_at(now_ms)), style::remaining(remaining));
+
+                // Only touch the clipboard when the code actually rolls over
+                if let Some(clipboard) = clipboard.as_mut()
+                    && copied.as_ref() != Some(&code)
+                {
+                    clipboard.set_text(code.clone())?;
+                    copied = Some(code.clone());
                 }
-                println!("   Created: {}", entry.created_at);
+
+                last_codes.insert(entry.name.clone(), code);
+            }
+
+            ticks += 1;
+            if options.count.is_some_and(|count| ticks >= count) {
+                break;
+            }
+
+            if clear_screen && page.is_some() {
+                println!("\nPages turn every {} seconds. Press Ctrl+C to exit live mode", LIVE_PAGE_TICKS);
+            } else if clear_screen {
+                println!("\nPress Ctrl+C to exit live mode");
+            } else {
                 println!();
             }
-        }
-        "get" => {
-            if args.len() < 3 {
-                eprintln!("❌ Usage: totp-console get <name>");
-                return Ok(());
+            tokio::select! {
+                _ = tokio::time::sleep(std::time::Duration::from_millis(1_000)) => {}
+                _ = &mut ctrl_c => break,
             }
+        }
+        Ok(())
+    }.await;
 
-            let name = &args[2];
-            match db.get_entry_by_name(name)? {
-                Some(entry) => {
-                    let secret = base32_decode(&entry.secret)?;
-                    let totp = Totp::new(secret);
-                    let code = totp.generate()?;
-                    let remaining = totp.time_remaining();
+    // Restore the terminal even if a frame failed: reset colors and show the cursor again
+    if clear_screen {
+        print!("\x1B[0m\x1B[?25h");
+        println!();
+    }
 
-                    println!("🔑 {} | Code: {} | Expires in: {}s",
-                             entry.name, code, remaining);
-                }
-                None => {
-                    eprintln!("❌ Entry not found: {}", name);
-                }
-            }
+    if let Some(clipboard) = clipboard.as_mut().filter(|_| options.clear_on_exit) {
+        // Leave the clipboard alone if something else has been copied since
+        if copied.is_some() && clipboard.get_text().ok() == copied {
+            clipboard.clear()?;
+            println!("{}Cleared the clipboard", style::icon("🧹 "));
         }
-        "copy" => {
-            if args.len() < 3 {
-                eprintln!("❌ Usage: totp-console copy <name>");
-                return Ok(());
-            }
+    }
 
-            let name = &args[2];
-            match db.get_entry_by_name(name)? {
-                Some(entry) => {
-                    let secret = base32_decode(&entry.secret)?;
-                    let totp = Totp::new(secret);
-                    let code = totp.generate()?;
-                    let remaining = totp.time_remaining();
-                    
-                    clipboard.set_text(code)?;
-                    
-                    println!("✅ Copied TOTP code for {}, valid for {} seconds",
-                             entry.name, remaining);
-                }
-                None => {
-                    eprintln!("❌ Entry not found: {}", name);
-                }
-            }
+    result
+}
+
+// Largest clock error (in seconds) before codes start landing in the wrong 30-second step
+const MAX_CLOCK_SKEW: i64 = 15;
+
+// Environment checks for `doctor`. Returns an error when a critical check fails.
+async fn run_doctor(db: &TotpDatabase, config: &config::Config) -> anyhow::Result<()> {
+    let mut critical_failures = 0;
+
+    println!("{}Environment Check", style::icon("🩺 "));
+    println!("=====================");
+
+    // System clock: codes are only accepted if our clock agrees with the server's
+    match unix_time(std::time::SystemTime::now()) {
+        Err(e) => {
+            println!("{}Clock: {}", style::icon("❌ "), e);
+            critical_failures += 1;
         }
-        "delete" => {
-            if args.len() < 3 {
-                eprintln!("❌ Usage: totp-console delete <name>");
-                return Ok(());
+        Ok(now) => match clock_skew(now).await {
+            Some(skew) if skew.abs() > MAX_CLOCK_SKEW => {
+                println!("{}Clock: off by {}s compared to an internet time source; codes will be rejected", style::icon("❌ "), skew);
+                critical_failures += 1;
             }
+            Some(skew) => println!("{}Clock: within {}s of an internet time source", style::icon("✅ "), skew.abs()),
+            None => println!("{}Clock: couldn't reach an internet time source to check for skew", style::icon("⚠️  ")),
+        },
+    }
 
-            let name = &args[2];
-            if db.delete_entry(name)? {
-                println!("✅ Deleted entry: {}", name);
+    // Database: readable, and every stored secret still produces a code
+    match db.get_all_entries() {
+        Ok(entries) => {
+            let broken: Vec<&str> = entries
+                .iter()
+                .filter(|entry| Totp::from_entry(entry).is_err())
+                .map(|entry| entry.name.as_str())
+                .collect();
+            if broken.is_empty() {
+                println!("{}Database: {} entries readable", style::icon("✅ "), entries.len());
             } else {
-                eprintln!("❌ Entry not found: {}", name);
+                println!("{}Database: entries with invalid secrets: {}", style::icon("❌ "), broken.join(", "));
+                critical_failures += 1;
             }
         }
-        "loop" => {
-            let entries = if args.len() >= 3 {
-                // Single entry loop
-                let name = &args[2];
-                match db.get_entry_by_name(name)? {
-                    Some(entry) => vec![entry],
-                    None => {
-                        eprintln!("❌ Entry not found: {}", name);
-                        return Ok(());
-                    }
-                }
-            } else {
-                // All entries loop
-                db.get_all_entries()?
-            };
+        Err(e) => {
+            println!("{}Database: {}", style::icon("❌ "), e);
+            critical_failures += 1;
+        }
+    }
 
-            if entries.is_empty() {
-                println!("📭 No TOTP entries found");
-                return Ok(());
-            }
+    match &config.path {
+        Some(path) => println!("{}Config: {}", style::icon("✅ "), path.display()),
+        None => println!("{}Config: no config.toml, using built-in defaults", style::icon("✅ ")),
+    }
 
-            println!("🔄 Live TOTP Mode (Press Ctrl+C to stop)");
-            println!("=========================================");
+    // Optional integrations only warn, since every other command works without them
+    match arboard::Clipboard::new() {
+        Ok(_) => println!("{}Clipboard: available", style::icon("✅ ")),
+        Err(e) if osc52::over_ssh() => {
+            println!("{}Clipboard: unavailable over SSH, use `copy --osc52` instead ({})", style::icon("⚠️  "), e);
+        }
+        Err(e) => println!("{}Clipboard: unavailable, `copy` won't work ({})", style::icon("⚠️  "), e),
+    }
 
-            let mut last_codes: HashMap<String, String> = HashMap::new();
+    match config.cloudflare_kv() {
+        Some(_) => println!("{}Cloudflare KV: configured", style::icon("✅ ")),
+        None => println!("{}Cloudflare KV: not configured, `sync` and `load` won't work", style::icon("⚠️  ")),
+    }
 
-            loop {
-                // Clear screen
-                print!("\x1B[2J\x1B[1;1H");
+    if critical_failures > 0 {
+        anyhow::bail!("{} critical check(s) failed", critical_failures);
+    }
+    Ok(())
+}
 
-                println!("🔄 Live TOTP Codes - {}", chrono::Utc::now().format("%H:%M:%S"));
-                println!("==========================================");
+// Our clock minus the `Date` header of a well-known HTTPS server, or None when offline
+async fn clock_skew(now: u64) -> Option<i64> {
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(5))
+        .build()
+        .ok()?;
+    let response = client.head("https://api.cloudflare.com").send().await.ok()?;
+    let date = response.headers().get(reqwest::header::DATE)?.to_str().ok()?;
+    let server_time = chrono::DateTime::parse_from_rfc2822(date).ok()?.timestamp();
+    Some(now as i64 - server_time)
+}
 
-                for entry in &entries {
-                    let secret = base32_decode(&entry.secret)?;
-                    let totp = Totp::new(secret);
-                    let code = totp.generate()?;
-                    let remaining = totp.time_remaining();
+// Where to read a secret from instead of argv, which leaks into shell history and process listings
+enum SecretSource<'a> {
+    /// Hidden prompt on a terminal, or a single line when stdin is piped
+    Prompt,
+    /// All of stdin
+    Stdin,
+    File(&'a str),
+}
 
-                    let status = if last_codes.get(&entry.name) != Some(&code) {
-                        "🆕"
-                    } else {
-                        "  "
-                    };
+impl SecretSource<'_> {
+    // Surrounding whitespace (such as a trailing newline) is never part of a base32 secret
+    fn read(&self, prompt: &str) -> anyhow::Result<String> {
+        let secret = match self {
+            Self::Prompt if std::io::stdin().is_terminal() => rpassword::prompt_password(prompt)?,
+            Self::Prompt => {
+                let mut line = String::new();
+                std::io::stdin().read_line(&mut line)?;
+                line
+            }
+            Self::Stdin => std::io::read_to_string(std::io::stdin())?,
+            Self::File(path) => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read secret from {}", path))?,
+        };
+        Ok(secret.trim().to_string())
+    }
+}
 
-                    let remaining_string = if env::var("NO_COLOR").is_ok() || remaining > 5u64 {
-                        remaining.to_string() + "s"
-                    } else {
-                        format!("\x1b[31m{}s\x1b[0m", remaining) // Red color for low time
-                    };
+// Splits converted backup entries into importable ones, reporting and counting the rest
+fn importable_entries(results: Vec<Result<TotpEntry, String>>, skipped: &mut usize) -> Vec<TotpEntry> {
+    let mut entries = Vec::new();
+    for result in results {
+        match result {
+            Ok(entry) => entries.push(entry),
+            Err(reason) => {
+                eprintln!("{}Skipped {}", style::icon("⚠️  "), reason);
+                *skipped += 1;
+            }
+        }
+    }
+    entries
+}
 
-                    println!("{} 🔑 {:20} | {} | {}",
-                             status, entry.name, code, remaining_string);
+// `add --epoch`: a T0 in the future would leave the entry without a code until then
+fn parse_epoch(value: &str) -> anyhow::Result<u64> {
+    let Ok(epoch) = value.parse::<u64>() else {
+        eprintln!("{}--epoch requires a Unix timestamp in seconds", style::icon("❌ "));
+        return Err(Failure::InvalidInput.into());
+    };
+    if epoch > unix_time(std::time::SystemTime::now())? {
+        eprintln!("{}--epoch {} is in the future; no code could be generated before then", style::icon("❌ "), epoch);
+        return Err(Failure::InvalidInput.into());
+    }
+    Ok(epoch)
+}
 
-                    last_codes.insert(entry.name.clone(), code);
-                }
+// The name for a new entry: cleaned up with `--sanitize`, otherwise rejected if it would break
+// table output
+fn checked_name(name: &str, sanitize: bool) -> Result<String, String> {
+    let name = if sanitize { database::sanitize_name(name) } else { name.to_string() };
+    database::validate_name(&name)?;
+    Ok(name)
+}
 
-                println!("\nPress Ctrl+C to exit live mode");
-                tokio::time::sleep(std::time::Duration::from_millis(1_000)).await;
-            }
-        }
-        "sync" => {
-            match get_cloudflare_kv() {
-                Some(kv) => {
-                    let entries = db.get_all_entries()?;
-                    kv.sync_to_kv(&entries).await?;
-                }
-                None => {
-                    eprintln!("❌ Cloudflare KV not configured. Set CF_ACCOUNT_ID, CF_NAMESPACE_ID, and CF_API_TOKEN environment variables.");
-                }
-            }
+/// Checks that a secret is valid base32 and long enough to be a real seed. Secrets shorter than
+/// [`MIN_SECRET_LEN`] bytes are rejected unless `force` is set, which downgrades that to a warning.
+/// Secrets that look like placeholders only get a warning, which `force` silences.
+fn validate_secret(secret: &str, force: bool) -> Result<(), String> {
+    let bytes = base32_decode(secret).map_err(|_| "invalid base32".to_string())?;
+    if bytes.is_empty() {
+        return Err("secret is empty".to_string());
+    }
+
+    if bytes.len() < MIN_SECRET_LEN {
+        let reason = format!("only {} bits long, at least {} expected", bytes.len() * 8, MIN_SECRET_LEN * 8);
+        if !force {
+            return Err(format!("{}; use --force to store it anyway", reason));
         }
+        eprintln!("{}Warning: secret is {}", style::icon("⚠️  "), reason);
+    }
 
-        "load" => {
-            match get_cloudflare_kv() {
-                Some(kv) => {
-                    let entries = kv.load_from_kv().await?;
-                    let mut added = 0;
+    if !force && let Some(pattern) = weak_secret(&bytes) {
+        eprintln!(
+            "{}Warning: secret is {}, which looks like a placeholder rather than a real seed (--force hides this)",
+            style::icon("⚠️  "),
+            pattern
+        );
+    }
 
-                    for entry in entries {
-                        match db.add_entry(&entry) {
-                            Ok(_) => {
-                                added += 1;
-                                println!("✅ Added: {}", entry.name);
-                            }
-                            Err(_) => {
-                                println!("⚠️  Skipped (already exists): {}", entry.name);
-                            }
-                        }
-                    }
+    Ok(())
+}
 
-                    println!("📥 Loaded {} new entries from Cloudflare KV", added);
-                }
-                None => {
-                    eprintln!("❌ Cloudflare KV not configured. Set CF_ACCOUNT_ID, CF_NAMESPACE_ID, and CF_API_TOKEN environment variables.");
-                }
-            }
-        }
-        "export" => {
-            if args.len() < 3 {
-                eprintln!("❌ Usage: totp-console export <file_path>");
-                return Ok(());
-            }
+// Prints the current (or `--at`) code for one entry in the style `get` was asked for
+fn print_code(entry: &TotpEntry, at: Option<u64>, only_code: bool, template: Option<&Template>,
+              group: Option<&str>) -> anyhow::Result<()> {
+    let totp = Totp::from_entry(entry)?;
 
-            let file_path = &args[2];
-            let entries = db.get_all_entries()?;
+    // Just the digits for piping into other tools
+    if only_code || style::quiet() {
+        let code = match at {
+            Some(time) => totp.generate_at_time(time)?,
+            None => totp.generate()?,
+        };
+        println!("{}", style::group_code(&code, group));
+        return Ok(());
+    }
 
-            if entries.is_empty() {
-                eprintln!("⚠️  No entries to export");
-                return Ok(());
-            }
+    if let Some(template) = template {
+        let time = match at {
+            Some(time) => time,
+            None => unix_time(std::time::SystemTime::now())?,
+        };
+        let code = style::group_code(&totp.generate_at_time(time)?, group);
+        let remaining = totp.time_remaining_at(time);
 
-            let json = serde_json::to_string_pretty(&entries)?;
-            std::fs::write(file_path, json)?;
+        println!("{}", template.render(|placeholder| match placeholder {
+            "name" => entry.name.clone(),
+            "code" => code.clone(),
+            "issuer" => entry.issuer.clone().unwrap_or_default(),
+            "remaining" => remaining.to_string(),
+            _ => unreachable!("placeholders are validated by Template::parse"),
+        }));
+        return Ok(());
+    }
 
-            println!("✅ Exported {} entries to {}", entries.len(), file_path);
+    match at {
+        Some(time) => {
+            let code = style::group_code(&totp.generate_at_time(time)?, group);
+            let instant = chrono::DateTime::from_timestamp(time as i64, 0)
+                .map(|t| t.to_rfc3339())
+                .unwrap_or_else(|| time.to_string());
+
+            println!("{}{} | Code: {} | At: {}", style::icon("🔑 "),
+                     display_name(entry), code, instant);
         }
-        "import" => {
-            if args.len() < 3 {
-                eprintln!("❌ Usage: totp-console import <file_path>");
-                return Ok(());
-            }
+        None => {
+            let code = style::group_code(&totp.generate()?, group);
+            let remaining = totp.time_remaining()?;
 
-            let file_path = &args[2];
-            let json = std::fs::read_to_string(file_path)?;
-            let entries: Vec<TotpEntry> = serde_json::from_str(&json)?;
+            println!("{}{} | Code: {} | Expires in: {}s", style::icon("🔑 "),
+                     display_name(entry), code, remaining);
+        }
+    }
 
-            let mut added = 0;
-            let mut skipped = 0;
+    Ok(())
+}
 
-            for entry in entries {
-                // Validate secret
-                if base32_decode(&entry.secret).is_err() {
-                    eprintln!("⚠️  Skipped {} (invalid secret)", entry.name);
-                    skipped += 1;
-                    continue;
-                }
+// "SHA1 (12), SHA256 (2)" for `info`
+fn breakdown<T>(counts: &[(T, usize)], label: impl Fn(&T) -> String) -> String {
+    counts.iter().map(|(value, count)| format!("{} ({})", label(value), count)).collect::<Vec<_>>().join(", ")
+}
 
-                match db.add_entry(&entry) {
-                    Ok(_) => {
-                        added += 1;
-                    }
-                    Err(_) => {
-                        println!("⚠️  Skipped (already exists): {}", entry.name);
-                        skipped += 1;
-                    }
-                }
-            }
+// Bytes on disk, counting the WAL file that holds changes not yet checkpointed into the database
+fn database_size(path: &std::path::Path) -> Option<u64> {
+    let size = std::fs::metadata(path).ok()?.len();
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    Some(size + std::fs::metadata(wal).map_or(0, |metadata| metadata.len()))
+}
 
-            println!("📥 Imported {} entries, skipped {}", added, skipped);
-        }
-        "search" => {
-            if args.len() < 3 {
-                eprintln!("❌ Usage: totp-console search <query>");
-                return Ok(());
-            }
+fn human_size(bytes: u64) -> String {
+    match bytes {
+        0..1024 => format!("{} B", bytes),
+        1024..1_048_576 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MiB", bytes as f64 / 1_048_576.0),
+    }
+}
 
-            let query = &args[2];
-            let entries = db.search_entries(query)?;
+// Rows shown by `history` without --limit
+const HISTORY_LIMIT: usize = 20;
 
-            if entries.is_empty() {
-                println!("🔍 No entries found matching '{}'", query);
-                return Ok(());
-            }
+// Appends to the usage log when `history = true` in config.toml. A failed write only warns, so
+// the log never stands between the user and a code
+fn record_usage(db: &TotpDatabase, config: &config::Config, name: &str, action: &str) {
+    if config.history
+        && let Err(e) = db.log_usage(name, action)
+    {
+        eprintln!("{}Could not record history: {}", style::icon("⚠️  "), e);
+    }
+}
 
-            println!("🔍 Search Results for '{}':", query);
-            println!("================");
+// `get --adjacent`: the codes for the windows before and after the current one (or the one
+// containing `at`), for verifiers whose clock is a step behind or ahead
+fn print_adjacent_codes(entry: &TotpEntry, at: Option<u64>, group: Option<&str>) -> anyhow::Result<()> {
+    let totp = Totp::from_entry(entry)?;
+    let time = match at {
+        Some(time) => time,
+        None => unix_time(std::time::SystemTime::now())?,
+    };
+    let period = totp.period();
+    let start = time.saturating_add(totp.time_remaining_at(time)).saturating_sub(period);
 
-            for entry in entries {
-                println!("🔑 {}", entry.name);
-                if let Some(issuer) = entry.issuer {
-                    println!("   Issuer: {}", issuer);
-                }
-                println!("   Created: {}", entry.created_at);
-                println!();
-            }
-        }
-        "update" => {
-            if args.len() < 3 {
-                eprintln!("❌ Usage: totp-console update <name> [--secret <secret>] [--issuer <issuer>]");
-                return Ok(());
-            }
+    // There is no window before the token's epoch
+    let mut windows = Vec::new();
+    if let Some(previous) = start.checked_sub(period)
+        && previous >= totp.epoch()
+    {
+        windows.push(("Previous", previous));
+    }
+    windows.push(("Current", start));
+    windows.push(("Next", start.saturating_add(period)));
 
-            let name = &args[2];
-            let mut new_secret: Option<&str> = None;
-            let mut new_issuer: Option<&str> = None;
+    if style::quiet() {
+        for (_, window) in windows {
+            println!("{}", style::group_code(&totp.generate_at_time(window)?, group));
+        }
+        return Ok(());
+    }
 
-            let mut i = 3;
-            while i < args.len() {
-                match args[i].as_str() {
-                    "--secret" => {
-                        if i + 1 < args.len() {
-                            new_secret = Some(&args[i + 1]);
-                            i += 2;
-                        } else {
-                            eprintln!("❌ --secret requires a value");
-                            return Ok(());
-                        }
-                    }
-                    "--issuer" => {
-                        if i + 1 < args.len() {
-                            new_issuer = Some(&args[i + 1]);
-                            i += 2;
-                        } else {
-                            eprintln!("❌ --issuer requires a value");
-                            return Ok(());
-                        }
-                    }
-                    _ => {
-                        eprintln!("❌ Unknown flag: {}", args[i]);
-                        return Ok(());
-                    }
-                }
-            }
+    let clock = |time: u64| {
+        chrono::DateTime::from_timestamp(time as i64, 0)
+            .map(|t| t.format("%H:%M:%S").to_string())
+            .unwrap_or_else(|| time.to_string())
+    };
+    println!("{}{}", style::icon("🔑 "), display_name(entry));
+    let mut table = Table::new(&["Window", "Code", "Valid (UTC)"]);
+    for (label, window) in windows {
+        let valid = format!("{}-{}", clock(window), clock(window.saturating_add(period)));
+        table.add_row(vec![label.to_string(), style::group_code(&totp.generate_at_time(window)?, group), valid]);
+    }
+    print!("{}", table.render());
+    Ok(())
+}
 
-            if new_secret.is_none() && new_issuer.is_none() {
-                eprintln!("❌ Please specify at least one field to update (--secret or --issuer)");
-                return Ok(());
-            }
+// otpauth URLs (and the QR codes made from them) have no way to carry a fixed truncation offset
+// or a custom epoch, so another app would show different codes for such an entry
+fn warn_if_not_portable(entry: &TotpEntry) {
+    if entry.truncation_offset.is_some() {
+        eprintln!("{}{} uses a fixed truncation offset, which otpauth URLs can't carry; other apps will show different codes", style::icon("⚠️  "), entry.name);
+    }
+    if entry.epoch != 0 {
+        eprintln!("{}{} counts time from a custom epoch, which otpauth URLs can't carry; other apps will show different codes", style::icon("⚠️  "), entry.name);
+    }
+}
 
-            // Validate new secret if provided
-            if let Some(secret) = new_secret
-                && base32_decode(secret).is_err() {
-                    eprintln!("❌ Invalid base32 secret");
-                    return Ok(());
-                }
+// `--group` or `--group=<sep>` on `get`, `copy --watch` and `loop`
+fn is_group_flag(flag: &str) -> bool {
+    flag == "--group" || flag.starts_with("--group=")
+}
 
-            if db.update_entry(name, new_secret, new_issuer)? {
-                println!("✅ Updated entry: {}", name);
-            } else {
-                eprintln!("❌ Entry not found: {}", name);
-            }
+// The separator a `--group` flag asks for: a space unless one is given after `=`
+fn group_separator(flag: &str) -> anyhow::Result<String> {
+    match flag.strip_prefix("--group=") {
+        None => Ok(" ".to_string()),
+        Some("") => {
+            eprintln!("{}--group= requires a separator, such as --group=-", style::icon("❌ "));
+            Err(Failure::InvalidInput.into())
         }
-        "info" => {
-            let (count, oldest) = db.get_stats()?;
+        Some(separator) => Ok(separator.to_string()),
+    }
+}
 
-            println!("📊 Database Statistics");
-            println!("=====================");
-            println!("Total entries: {}", count);
+/// Finds the entry meant by `query`: an exact (case-insensitive) name match wins, otherwise the
+/// query must match exactly one entry by name or issuer substring. `issuer` narrows both steps
+/// to that issuer. When the query is still ambiguous, an interactive session picks from a
+/// numbered list; otherwise the candidates are listed and this fails with [`Failure::InvalidInput`].
+fn resolve_entry(db: &TotpDatabase, query: &str, issuer: Option<&str>) -> anyhow::Result<Option<TotpEntry>> {
+    let exact = match issuer {
+        Some(issuer) => db.get_entry_by_name_and_issuer(query, issuer)?,
+        None => db.get_entry_by_name(query)?,
+    };
+    if exact.is_some() {
+        return Ok(exact);
+    }
 
-            if let Some(oldest_date) = oldest {
-                println!("Oldest entry: {}", oldest_date);
-            }
+    let mut matches = db.search_entries(query, SearchMode::Contains)?;
+    if let Some(issuer) = issuer {
+        matches.retain(|entry| entry.issuer.as_deref().is_some_and(|candidate| candidate.eq_ignore_ascii_case(issuer)));
+    }
+    if matches.len() > 1 {
+        if is_interactive() {
+            return choose_entry(query, matches).map(Some);
+        }
 
-            if count > 0 {
-                println!();
-                println!("Database file: totp.db");
+        eprintln!("{}'{}' matches {} entries:", style::icon("❌ "), query, matches.len());
+        for entry in &matches {
+            match &entry.issuer {
+                Some(issuer) => eprintln!("   {} ({})", entry.name, issuer),
+                None => eprintln!("   {}", entry.name),
             }
         }
-        _ => {
-            eprintln!("❌ Unknown command: {}", args[1]);
-            print_usage();
+        eprintln!("Please use a more specific name, or add --issuer <issuer>.");
+        return Err(Failure::InvalidInput.into());
+    }
+
+    Ok(matches.pop())
+}
+
+// The interactive half of `resolve_entry`: numbered candidates and a prompt for one of them
+fn choose_entry(query: &str, mut matches: Vec<TotpEntry>) -> anyhow::Result<TotpEntry> {
+    println!("'{}' matches {} entries:", query, matches.len());
+    for (index, entry) in matches.iter().enumerate() {
+        match &entry.issuer {
+            Some(issuer) => println!("  {}) {} ({})", index + 1, entry.name, issuer),
+            None => println!("  {}) {}", index + 1, entry.name),
         }
     }
+    print!("Which one? [1-{}] ", matches.len());
+    std::io::stdout().flush()?;
 
-    Ok(())
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    match answer.trim().parse::<usize>() {
+        Ok(choice) if (1..=matches.len()).contains(&choice) => Ok(matches.swap_remove(choice - 1)),
+        _ => {
+            eprintln!("{}No entry chosen", style::icon("❌ "));
+            Err(Failure::InvalidInput.into())
+        }
+    }
 }
 
 fn print_usage() {
-    println!("🔐 TOTP Console Manager");
+    println!("{}TOTP Console Manager", style::icon("🔐 "));
     println!("Usage: totp-console <command> [args]");
     println!();
     println!("Commands:");
     println!("  add <name> <secret> [issuer]     Add a new TOTP entry");
-    println!("  list                             List all entries");
-    println!("  get <name>                       Get TOTP code for specific entry");
-    println!("  copy <name>                      Copy TOTP code to clipboard");
-    println!("  delete <name>                    Delete an entry");
+    println!("  add-url <otpauth_url>            Add an entry from an otpauth:// URL");
+    println!("  list [options]                   List all entries");
+    println!("  get <name>... [options]          Get TOTP codes for one or more entries");
+    println!("  copy <name> [--watch | --osc52]  Copy TOTP code to clipboard, keep it fresh, or copy over SSH");
+    println!("  open <name>                      Open an entry's login page and copy its code");
+    println!("  url <name> | --all               Print the otpauth:// URL of an entry, or of every entry");
+    println!("  qr <name> [--output <output>]    Show an entry's QR code, or save it as PNG or SVG");
+    println!("  delete <name> [--yes]            Move an entry to the recycle bin");
+    println!("  restore <name>                   Restore a deleted entry");
+    println!("  purge [name] [--yes]             Permanently remove deleted entries");
     println!("  update <name> [options]          Update an existing entry");
-    println!("  search <query>                   Search entries by name or issuer");
-    println!("  loop [name]                      Continuous refresh mode");
+    println!("  move <name> --up|--down|--to <n> Change where an entry appears in `list --sort position`");
+    println!("  favorite <name>                  Pin an entry to the top of lists (unfavorite to undo)");
+    println!("  search <query> [options]         Search entries by name or issuer");
+    println!("  loop [name] [options]            Continuous refresh mode");
+    println!("  tui                              Interactive dashboard with search and copy");
     println!("  info                             Show database statistics");
-    println!("  read <image_path>                Read TOTP from QR code image");
-    println!("  export <file_path>               Export entries to JSON file");
-    println!("  import <file_path>               Import entries from JSON file");
-    println!("  sync                             Sync to Cloudflare KV");
-    println!("  load                             Load from Cloudflare KV");
+    println!("  history [options]                Show recently generated and copied codes (opt-in)");
+    println!("  doctor                           Check the clock, database, clipboard and KV setup");
+    println!("  gen [options]                    Generate a new random secret");
+    println!("  read <image_path>                Read TOTP from a QR code image (or PDF)");
+    println!("  read --dir <folder>              Read every QR code image in a folder");
+    println!("  read --screen [--region <geom>]  Read a QR code shown on the screen (X11), optionally only within WxH+X+Y");
+    println!("  export <file_path> [--format]    Export entries to a JSON or CSV file (`-` or --stdout for stdout)");
+    println!("  export --qr-dir <dir>            Write one QR code PNG per entry to a directory");
+    println!("  import <file_path> [--format]    Import entries from JSON, CSV or another app's backup (`-` for stdin)");
+    println!("  dump <file_path>                 Write the whole database, every column and table, as SQL (`-` for stdout)");
+    println!("  restore --dump <file_path>       Replace the database with a SQL dump (`-` for stdin) [--yes]");
+    println!("  sync [options]                   Sync to Cloudflare KV");
+    println!("  load [options]                   Load from Cloudflare KV");
+    println!("  kv-status [options]              Show what differs between local and Cloudflare KV");
+    println!("  profiles                         List the vaults selectable with --profile");
+    println!("  kv-login [--stdin | --remove]    Store the Cloudflare API token in the OS keychain");
+    println!();
+    println!("Global Options:");
+    println!("  --color <auto|always|never>      Control colors and icons (default: auto)");
+    println!("  --quiet, -q                      Suppress confirmations; print only codes and secrets");
+    println!("  --ephemeral                      Use a throwaway in-memory database instead of the usual file");
+    println!("  --profile <name>                 Use a separate vault (also $TOTP_PROFILE; default: default)");
+    println!("  --verbose, -v                    Log what happens to stderr (-vv for more detail)");
+    println!();
+    println!("Add Options:");
+    println!("  --account <label>                Account label (usually an email) shown by apps");
+    println!("  --url <url>                      Login page for `open`");
+    println!("  --expires <time>                 Hide the entry from `list` from this RFC 3339 time or date on");
+    println!("  --backup-codes <file>            Keep recovery codes (one per line, - for stdin) with the entry");
+    println!("  --prompt                         Type the secret at a hidden prompt instead");
+    println!("  --secret-stdin                   Read the secret from stdin instead");
+    println!("  --secret-file <path>             Read the secret from a file instead");
+    println!("  --force                          Accept a secret shorter than 80 bits; also hides placeholder warnings");
+    println!("  --sanitize                       Strip control characters from the name and shorten it instead of failing");
+    println!("  --encoding <encoding>            How the secret is written: base32 (default), base32hex or hex");
+    println!("  --truncation-offset <n>          Legacy tokens only: read the code from a fixed HMAC offset");
+    println!("  --epoch <unix_time>              Count time steps from this T0 instead of 1970 (rarely needed)");
+    println!();
+    println!("List Options:");
+    println!("  --issuer <issuer>                Only show entries from this issuer");
+    println!("  --limit <n>                      Show at most n entries (also for search)");
+    println!("  --offset <n>                     Skip the first n entries (also for search)");
+    println!("  --group-by-issuer                Group entries under issuer headers");
+    println!("  --since <time>                   Only show entries created since an RFC 3339 time or a date");
+    println!("  --all                            Also show expired entries");
+    println!("  --deleted                        Show the recycle bin instead");
+    println!("  --sort <order>                   name (default), created or position (set with move)");
+    println!();
+    println!("Search Options:");
+    println!("  --exact                          Match the whole name or issuer instead of any part");
+    println!("  --prefix                         Match names or issuers that start with the query");
+    println!();
+    println!("Get Options:");
+    println!("  --at <unix_time>                 Generate the code for a Unix timestamp");
+    println!("  --at-rfc3339 <time>              Generate the code for an RFC 3339 timestamp");
+    println!("  --all                            Print the current code of every entry once");
+    println!("  --issuer <issuer>                Only match entries from this issuer (also for copy, open and delete)");
+    println!("  --backup-codes                   Show the entry's stored recovery codes instead");
+    println!("  --adjacent                       Also print the previous and next codes with their validity times");
+    println!("  --algorithm, --digits, --period  Try other code parameters without changing the entry");
+    println!("  --group[=<sep>]                  Split the code in the middle, e.g. 123 456 (also for copy --watch and loop)");
+    println!("  --only-code                      Print just the code");
+    println!("  --format <template>              Print a template, e.g. \"{{name}}: {{code}}\"");
+    println!("                                   Placeholders: {{name}} {{code}} {{issuer}} {{remaining}}");
+    println!();
+    println!("History Options:");
+    println!("  --name <name>                    Only show this entry");
+    println!("  --limit <n>                      Show at most n rows (default: 20)");
+    println!();
+    println!("QR Options:");
+    println!("  --output <output>                utf8 (default) prints to the terminal; png or svg write <file_path>");
+    println!();
+    println!("Gen Options:");
+    println!("  --bytes <n>                      Secret length in bytes (default: 20)");
+    println!("  --name <name>                    Store the new secret under this name");
+    println!("  --issuer <issuer>                Issuer for the URL and stored entry");
+    println!("  --qr                             Print the otpauth URL as a QR code");
     println!();
     println!("Update Options:");
-    println!("  --secret <secret>                Update the secret key");
+    println!("  --secret [secret]                Update the secret key (prompts when no value is given)");
+    println!("  --secret-stdin                   Read the new secret from stdin");
+    println!("  --secret-file <path>             Read the new secret from a file");
     println!("  --issuer <issuer>                Update the issuer");
+    println!("  --url <url>                      Set the login page for `open` (\"\" to remove it)");
+    println!("  --expires <time>                 Set when the entry expires (\"\" to remove it)");
+    println!("  --backup-codes <file>            Replace the recovery codes (an empty file removes them)");
+    println!("  --truncation-offset <n|dynamic>  Set or clear a fixed truncation offset");
+    println!("  --force                          Accept a secret shorter than 80 bits; also hides placeholder warnings");
+    println!();
+    println!("Loop Options:");
+    println!("  --count <n>                      Stop after n refreshes");
+    println!("  --no-clear                       Append output instead of redrawing the screen");
+    println!("  --copy                           Copy each new code to the clipboard (single entry)");
+    println!("  --clear-on-exit                  With --copy, wipe the clipboard when the loop stops");
+    println!("  --group[=<sep>]                  Show codes split in the middle; the clipboard gets the plain code");
+    println!();
+    println!("Export Options:");
+    println!("  --format <format>                json (default) or csv");
+    println!("  --compact                        Write JSON on a single line instead of pretty-printed");
+    println!("  --name <entry>                   Only export this entry (repeatable, also for --qr-dir)");
+    println!("  --since <time>                   Only export entries created since an RFC 3339 time or a date");
+    println!();
+    println!("Import Options:");
+    println!("  --format <format>                json (default), csv, aegis, andotp or freeotp");
+    println!("  --force                          Accept secrets shorter than 80 bits; also hides placeholder warnings");
+    println!("  --encoding <encoding>            How the secrets are written: base32 (default), base32hex or hex");
+    println!("  --overwrite                      Replace entries with the same name instead of skipping them");
+    println!("  --sanitize                       Clean up unusable names instead of skipping those entries");
+    println!("  --dry-run                        Show what would be added or skipped without importing");
+    println!();
+    println!("Sync/Load Options:");
+    println!("  --dry-run                        With sync, only show what would change (as kv-status)");
+    println!("  --verify                         With sync, read everything back and check it matches");
+    println!("  --timeout <seconds>              Give up on a request after this long (default: 10)");
+    println!("  --retries <n>                    Retries after network or 5xx errors (default: 2)");
+    println!("  --kv-key <key>                   Vault key, to keep several vaults in one namespace (default: totp, or totp-<profile>)");
+    println!();
+    println!("Configuration:");
+    println!("  Defaults are read from config.toml in the user config directory");
+    println!("  (~/.config/totp-console/config.toml on Linux); flags override them:");
+    println!("    database = \"/path/to/totp.db\"");
+    println!("    color = \"auto\"");
+    println!("    [defaults]");
+    println!("    algorithm = \"SHA1\"");
+    println!("    digits = 6");
+    println!("    period = 30");
+    println!("    [kv]");
+    println!("    account_id = \"your_account_id\"");
+    println!("    namespace_id = \"your_namespace_id\"");
+    println!("    api_token = \"your_api_token\"        # or leave out and use kv-login");
+    println!("    timeout = 10");
+    println!("    retries = 2");
+    println!("  A legacy kv.json with account_id, namespace_id and api_token is still read");
+    println!("  when config.toml has no [kv] section.");
     println!();
-    println!("Cloudflare KV Configuration:");
-    println!("  Create a `kv.json` file with the following structure:");
-    println!("  {{");
-    println!("    \"account_id\": \"your_account_id\",");
-    println!("    \"namespace_id\": \"your_namespace_id\",");
-    println!("    \"api_token\": \"your_api_token\"");
-    println!("  }}");
+    println!("Environment Variables:");
+    println!("  NO_COLOR                         Disable colors and icons (same as --color never)");
+    println!("  TOTP_CONFIG                      Read the config file from this path instead");
     println!();
     println!("Environment Variables (for Cloudflare KV):");
     println!("  CF_ACCOUNT_ID                    Cloudflare account ID");
     println!("  CF_NAMESPACE_ID                  KV namespace ID");
     println!("  CF_API_TOKEN                     API token");
+    println!("  CF_TIMEOUT                       Request timeout in seconds (default: 10)");
+    println!("  CF_RETRIES                       Retries after transient errors (default: 2)");
     println!();
     println!("Examples:");
     println!("  totp add github 0123456789ABCDEF GitHub");
@@ -455,6 +3552,7 @@ fn print_usage() {
     println!("  totp import backup.json");
     println!("  totp loop");
     println!("  totp loop github");
+    println!("  totp loop --count 5 --no-clear");
     println!("  totp sync");
     println!("  totp load");
 }
\ No newline at end of file
@@ -0,0 +1,73 @@
+use std::io::Write;
+
+// `copy --osc52`: asks the terminal itself to set the clipboard with an OSC 52 escape sequence.
+// The sequence travels over SSH like any other output, so the code lands on the clipboard of
+// the machine the user is sitting at even when the remote host has no display for `arboard`.
+
+/// Whether this looks like an SSH session, where `arboard` has no display to talk to.
+pub(crate) fn over_ssh() -> bool {
+    std::env::var_os("SSH_CONNECTION").is_some() || std::env::var_os("SSH_TTY").is_some()
+}
+
+/// Sends `text` to the terminal's clipboard. The terminal gives no answer, so success only
+/// means the sequence was written. It goes to the controlling terminal when there is one, so
+/// it still reaches the screen when stdout is redirected.
+pub(crate) fn copy(text: &str) -> std::io::Result<()> {
+    let sequence = sequence(text);
+    match std::fs::OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => tty.write_all(sequence.as_bytes()),
+        Err(_) => {
+            let mut stdout = std::io::stdout().lock();
+            stdout.write_all(sequence.as_bytes())?;
+            stdout.flush()
+        }
+    }
+}
+
+// `c` selects the clipboard (rather than the primary selection); BEL ends the sequence, which
+// more terminals accept than the two-byte string terminator
+fn sequence(text: &str) -> String {
+    format!("\x1b]52;c;{}\x07", base64(text.as_bytes()))
+}
+
+const BASE64_ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// Standard padded base64 (RFC 4648), the only encoding OSC 52 takes
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], chunk.get(1).copied().unwrap_or(0), chunk.get(2).copied().unwrap_or(0)];
+        let group = u32::from(bytes[0]) << 16 | u32::from(bytes[1]) << 8 | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64_ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64() {
+        // RFC 4648 section 10
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(base64(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn test_sequence() {
+        assert_eq!(sequence("123456"), "\x1b]52;c;MTIzNDU2\x07");
+    }
+}
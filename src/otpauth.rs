@@ -0,0 +1,353 @@
+use url::Url;
+use crate::TotpEntry;
+use crate::base32::{base32_decode, base32_encode};
+use crate::totp::{validate_params, HashAlgorithm, DEFAULT_DIGITS, DEFAULT_PERIOD};
+
+// otpauth:// URLs (https://github.com/google/google-authenticator/wiki/Key-Uri-Format)
+/// The kind of one-time password an `otpauth://` URL describes, taken from its host.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpType {
+    Totp,
+    /// Counter-based codes, with the required `counter` parameter
+    Hotp { counter: u64 },
+}
+
+/// Reads the type of an `otpauth://` URL without parsing the rest of it.
+pub fn otp_type(content: &str) -> anyhow::Result<OtpType> {
+    let url = Url::parse(content).map_err(|_| anyhow::anyhow!("Not a valid otpauth URL: {}", content))?;
+    if url.scheme() != "otpauth" {
+        anyhow::bail!("Not an otpauth URL: {}", content);
+    }
+
+    match url.host_str().unwrap_or("") {
+        kind if kind.eq_ignore_ascii_case("totp") => Ok(OtpType::Totp),
+        kind if kind.eq_ignore_ascii_case("hotp") => {
+            let counter = url
+                .query_pairs()
+                .find(|(key, _)| key == "counter")
+                .ok_or_else(|| anyhow::anyhow!("The HOTP URL does not contain a counter"))?
+                .1;
+            let counter = counter.parse().map_err(|_| anyhow::anyhow!("Invalid counter in HOTP URL: {}", counter))?;
+            Ok(OtpType::Hotp { counter })
+        }
+        "" => anyhow::bail!("The otpauth URL does not name an OTP type (expected totp)"),
+        kind => anyhow::bail!("Unsupported OTP type in otpauth URL: {} (expected totp)", kind),
+    }
+}
+
+/// Parses an `otpauth://totp/` URL, as found in QR codes, setup pages and URI exports.
+/// Other types are rejected with an error naming the type.
+pub fn parse(content: &str) -> anyhow::Result<TotpEntry> {
+    if let OtpType::Hotp { counter } = otp_type(content)? {
+        anyhow::bail!("This is an HOTP (counter-based) code at counter {}; only TOTP (time-based) codes are supported", counter);
+    }
+
+    // otpauth://totp/issuer:account?secret=secret&issuer=issuer
+    let url = Url::parse(content)?;
+    let label = percent_decode(url.path().trim_start_matches('/'))?;
+
+    // The label is "account" or "issuer:account"; the colon may itself have been percent-encoded
+    let (label_issuer, account) = match label.split_once(':') {
+        Some((issuer, account)) => (Some(issuer.trim()), account.trim()),
+        None => (None, label.trim()),
+    };
+
+    let query_pairs = url.query_pairs();
+    let mut secret = String::new();
+    let mut issuer = None;
+    let mut algorithm = HashAlgorithm::default();
+    let mut digits = DEFAULT_DIGITS;
+    let mut period = DEFAULT_PERIOD;
+    for (key, value) in query_pairs {
+        match key.as_ref() {
+            "secret" => secret = value.to_string(),
+            "issuer" => issuer = Some(value.to_string()),
+            "algorithm" => {
+                algorithm = HashAlgorithm::parse(&value)
+                    .ok_or_else(|| anyhow::anyhow!("Unsupported algorithm in TOTP URL: {}", value))?
+            }
+            "digits" => digits = value.parse().map_err(|_| anyhow::anyhow!("Invalid digits in TOTP URL: {}", value))?,
+            "period" => period = value.parse().map_err(|_| anyhow::anyhow!("Invalid period in TOTP URL: {}", value))?,
+            _ => {}
+        }
+    }
+    // QR generators may pad the secret, write it in lowercase or group it with spaces. Store the
+    // canonical form so exports and URLs built from it are clean; a secret that doesn't decode is
+    // kept as given for validation to report.
+    if let Ok(bytes) = base32_decode(&secret) {
+        secret = base32_encode(&bytes);
+    }
+    if secret.is_empty() {
+        anyhow::bail!("The TOTP URL does not contain a valid secret");
+    }
+
+    // The `issuer` parameter is authoritative; the label prefix is only a fallback
+    let issuer = issuer.or_else(|| label_issuer.filter(|issuer| !issuer.is_empty()).map(str::to_string));
+    let label_account = (!account.is_empty()).then(|| account.to_string());
+    let name = match (account, &issuer) {
+        ("", Some(issuer)) => issuer.clone(),
+        ("", None) => anyhow::bail!("The TOTP URL does not contain an account name"),
+        (account, _) => account.to_string(),
+    };
+    validate_params(digits, period).map_err(anyhow::Error::msg)?;
+
+    let created_at = chrono::Utc::now().to_rfc3339(); // Use current timestamp
+    Ok(TotpEntry {
+        id: None,
+        name,
+        secret,
+        issuer,
+        created_at, // You can set this to the current timestamp if needed
+        algorithm,
+        digits,
+        period,
+        account: label_account,
+        truncation_offset: None,
+        favorite: false,
+        login_url: None,
+        backup_codes: Vec::new(),
+        epoch: 0,
+        expires_at: None,
+    })
+}
+
+/// Builds the `otpauth://totp/` URL authenticator apps expect for an entry.
+///
+/// The secret is decoded and re-encoded, so spaces, lowercase letters, padding and stray
+/// trailing bits are normalized away and another app derives exactly the key we use.
+pub fn build(entry: &TotpEntry) -> String {
+    let secret = match base32_decode(&entry.secret) {
+        Ok(bytes) => base32_encode(&bytes),
+        // Stored secrets are validated, but never drop one silently
+        Err(_) => entry.secret.chars().filter(|c| c.is_ascii_alphanumeric()).collect::<String>().to_uppercase(),
+    };
+
+    // Apps show the account part of the label, so prefer it over our own name
+    let account = entry.account.as_deref().unwrap_or(&entry.name);
+    let mut url = match &entry.issuer {
+        Some(issuer) => format!(
+            "otpauth://totp/{}:{}?secret={}&issuer={}",
+            percent_encode(issuer),
+            percent_encode(account),
+            secret,
+            percent_encode(issuer)
+        ),
+        None => format!("otpauth://totp/{}?secret={}", percent_encode(account), secret),
+    };
+
+    // Only spell out parameters that differ from what every app assumes
+    if entry.algorithm != HashAlgorithm::default() {
+        url.push_str(&format!("&algorithm={}", entry.algorithm));
+    }
+    if entry.digits != DEFAULT_DIGITS {
+        url.push_str(&format!("&digits={}", entry.digits));
+    }
+    if entry.period != DEFAULT_PERIOD {
+        url.push_str(&format!("&period={}", entry.period));
+    }
+
+    url
+}
+
+fn percent_decode(value: &str) -> anyhow::Result<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let byte = value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| anyhow::anyhow!("Invalid percent-encoding in TOTP URL label: {}", value))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|_| anyhow::anyhow!("TOTP URL label is not valid UTF-8: {}", value))
+}
+
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let entry = parse("otpauth://totp/github?secret=JBSWY3DPEHPK3PXP&issuer=GitHub&digits=8&period=60&algorithm=sha256").unwrap();
+        assert_eq!(entry.name, "github");
+        assert_eq!(entry.secret, "JBSWY3DPEHPK3PXP");
+        assert_eq!(entry.issuer.as_deref(), Some("GitHub"));
+        assert_eq!(entry.algorithm, HashAlgorithm::Sha256);
+        assert_eq!(entry.digits, 8);
+        assert_eq!(entry.period, 60);
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_urls() {
+        assert!(parse("https://example.com/?secret=JBSWY3DPEHPK3PXP").is_err());
+        assert!(parse("otpauth://hotp/github?secret=JBSWY3DPEHPK3PXP&counter=1").is_err());
+        assert!(parse("otpauth://totp/github?issuer=GitHub").is_err());
+        assert!(parse("otpauth://totp/github?secret=JBSWY3DPEHPK3PXP&digits=12").is_err());
+    }
+
+    #[test]
+    fn test_parse_algorithm_spellings() {
+        for (value, algorithm) in [("SHA1", HashAlgorithm::Sha1), ("sha256", HashAlgorithm::Sha256), ("SHA-512", HashAlgorithm::Sha512)] {
+            let url = format!("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm={}", value);
+            assert_eq!(parse(&url).unwrap().algorithm, algorithm, "{}", value);
+        }
+
+        let error = parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP&algorithm=MD5").unwrap_err();
+        assert!(error.to_string().contains("MD5"));
+    }
+
+    #[test]
+    fn test_otp_type() {
+        let totp = "otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub";
+        assert_eq!(otp_type(totp).unwrap(), OtpType::Totp);
+        assert_eq!(otp_type("otpauth://TOTP/alice?secret=JBSWY3DPEHPK3PXP").unwrap(), OtpType::Totp);
+
+        let hotp = "otpauth://hotp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&counter=42";
+        assert_eq!(otp_type(hotp).unwrap(), OtpType::Hotp { counter: 42 });
+        assert!(otp_type("otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP").is_err());
+        assert!(otp_type("otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP&counter=-1").is_err());
+
+        let error = otp_type("otpauth://steam/alice?secret=JBSWY3DPEHPK3PXP").unwrap_err();
+        assert!(error.to_string().contains("steam"));
+        assert!(otp_type("https://example.com/?secret=JBSWY3DPEHPK3PXP").is_err());
+    }
+
+    #[test]
+    fn test_parse_explains_hotp() {
+        let error = parse("otpauth://hotp/alice?secret=JBSWY3DPEHPK3PXP&counter=7").unwrap_err();
+        assert!(error.to_string().contains("HOTP"));
+        assert!(error.to_string().contains("counter 7"));
+    }
+
+    #[test]
+    fn test_parse_label_without_issuer() {
+        let entry = parse("otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(entry.name, "alice");
+        assert_eq!(entry.issuer, None);
+        assert_eq!(entry.algorithm, HashAlgorithm::Sha1);
+        assert_eq!(entry.digits, DEFAULT_DIGITS);
+        assert_eq!(entry.period, DEFAULT_PERIOD);
+    }
+
+    #[test]
+    fn test_parse_label_with_issuer_prefix() {
+        let entry = parse("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub").unwrap();
+        assert_eq!(entry.name, "alice");
+        assert_eq!(entry.issuer.as_deref(), Some("GitHub"));
+
+        // Without the parameter the prefix becomes the issuer
+        let entry = parse("otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(entry.issuer.as_deref(), Some("GitHub"));
+
+        // The parameter wins when the two disagree
+        let entry = parse("otpauth://totp/Old%20Name:alice?secret=JBSWY3DPEHPK3PXP&issuer=New%20Name").unwrap();
+        assert_eq!(entry.issuer.as_deref(), Some("New Name"));
+    }
+
+    #[test]
+    fn test_parse_percent_encoded_labels() {
+        let entry = parse("otpauth://totp/GitHub:alice%40example.com?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(entry.name, "alice@example.com");
+        assert_eq!(entry.account.as_deref(), Some("alice@example.com"));
+        assert_eq!(entry.issuer.as_deref(), Some("GitHub"));
+
+        // Encoded colon, and the optional space after it
+        let entry = parse("otpauth://totp/Acme%20Co%3A%20bob?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(entry.name, "bob");
+        assert_eq!(entry.issuer.as_deref(), Some("Acme Co"));
+
+        let entry = parse("otpauth://totp/%E5%B7%A5%E4%BD%9C?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(entry.name, "工作");
+
+        assert!(parse("otpauth://totp/bad%zzlabel?secret=JBSWY3DPEHPK3PXP").is_err());
+    }
+
+    #[test]
+    fn test_parse_issuer_only_label() {
+        let entry = parse("otpauth://totp/GitHub:?secret=JBSWY3DPEHPK3PXP").unwrap();
+        assert_eq!(entry.name, "GitHub");
+        assert_eq!(entry.account, None);
+        assert!(parse("otpauth://totp/?secret=JBSWY3DPEHPK3PXP").is_err());
+    }
+
+    #[test]
+    fn test_build_parse_round_trip() {
+        let entry = TotpEntry {
+            name: "alice@example.com".to_string(),
+            secret: "JBSWY3DPEHPK3PXP".to_string(),
+            issuer: Some("Acme Co".to_string()),
+            ..TotpEntry::default()
+        };
+        let parsed = parse(&build(&entry)).unwrap();
+        assert_eq!(parsed.name, entry.name);
+        assert_eq!(parsed.issuer, entry.issuer);
+
+        // A renamed entry keeps its original account in the label
+        let renamed = TotpEntry { name: "work".to_string(), account: Some("alice@example.com".to_string()), ..entry };
+        assert_eq!(parse(&build(&renamed)).unwrap().account.as_deref(), Some("alice@example.com"));
+    }
+
+    #[test]
+    fn test_parse_normalizes_secret() {
+        let entry = parse("otpauth://totp/alice?secret=gezdgnbvgy3tqojqge======").unwrap();
+        assert_eq!(entry.secret, "GEZDGNBVGY3TQOJQGE");
+        let entry = parse("otpauth://totp/alice?secret=jbsw%20y3dp%20ehpk%203pxp").unwrap();
+        assert_eq!(entry.secret, "JBSWY3DPEHPK3PXP");
+
+        // Left alone for the caller's validation to reject
+        assert_eq!(parse("otpauth://totp/alice?secret=not-base32!").unwrap().secret, "not-base32!");
+        assert!(parse("otpauth://totp/alice?secret=%20%20").is_err());
+    }
+
+    #[test]
+    fn test_parse_missing_secret() {
+        let error = parse("otpauth://totp/alice?issuer=GitHub").unwrap_err();
+        assert!(error.to_string().contains("secret"));
+        assert!(parse("otpauth://totp/alice?secret=").is_err());
+    }
+
+    #[test]
+    fn test_build() {
+        let entry = TotpEntry {
+            name: "alice@example.com".to_string(),
+            secret: "jbsw y3dp ehpk 3pxp".to_string(),
+            issuer: Some("Acme Co".to_string()),
+            digits: 8,
+            ..TotpEntry::default()
+        };
+        assert_eq!(
+            build(&entry),
+            "otpauth://totp/Acme%20Co:alice%40example.com?secret=JBSWY3DPEHPK3PXP&issuer=Acme%20Co&digits=8"
+        );
+
+        let plain = TotpEntry { name: "alice".to_string(), secret: "JBSWY3DPEHPK3PXP".to_string(), ..TotpEntry::default() };
+        assert_eq!(build(&plain), "otpauth://totp/alice?secret=JBSWY3DPEHPK3PXP");
+
+        // Padding is dropped and every parameter that differs from the defaults is spelled out
+        let padded = TotpEntry {
+            secret: "GEZDGNBV====".to_string(),
+            algorithm: HashAlgorithm::Sha512,
+            period: 60,
+            ..plain
+        };
+        assert_eq!(build(&padded), "otpauth://totp/alice?secret=GEZDGNBV&algorithm=SHA512&period=60");
+    }
+}
@@ -1,63 +1,440 @@
-use image::{DynamicImage};
+use image::{AnimationDecoder, DynamicImage, ImageError, ImageFormat, ImageReader, ImageResult};
 use rqrr::PreparedImage;
-use std::path::Path;
-use url::Url;
-use crate::database::TotpEntry;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use crate::otpauth;
+use crate::TotpEntry;
+
+/// Why no QR code could be read, carried inside the `anyhow::Error` of the read functions so
+/// callers can tell a wrong path from a bad image (`error.downcast_ref::<QrError>()`). Problems
+/// with the QR code's content, such as a non-otpauth link, are reported as plain errors.
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum QrError {
+    #[error("No such file: {}", .0.display())]
+    FileNotFound(PathBuf),
+    /// The file exists but can't be read, e.g. for lack of permission or because it's a directory
+    #[error("Could not read {}: {source}", path.display())]
+    Unreadable { path: PathBuf, source: std::io::Error },
+    #[error("The file is empty")]
+    EmptyFile,
+    #[error("The file exists but isn't a recognized image format (expected PNG, JPEG, GIF, WebP, BMP, TIFF or PDF)")]
+    UnsupportedFormat,
+    /// The format was recognized but the data doesn't decode, typically a truncated download
+    #[error("The file looks like a {format} image but could not be decoded; it may be truncated or damaged ({reason})")]
+    CorruptImage { format: &'static str, reason: String },
+    /// The image decoded fine but contains nothing that looks like a QR code
+    #[error("No QR codes found {0}; try a sharper image, cropped closer to the code")]
+    NoQrCode(String),
+    /// Something QR-shaped was found but its modules don't decode
+    #[error("Found a QR code but could not decode it ({0}); try a larger or sharper image")]
+    Undecodable(String),
+}
 
 pub fn read_totp_qr(image: DynamicImage) -> anyhow::Result<TotpEntry> {
-    // Load and convert image to grayscale
-    let gray_img = image.to_luma8();
+    match decode_qr(&image)? {
+        Some(content) => parse_totp_content(&content),
+        None => Err(QrError::NoQrCode("in the image".to_string()).into()),
+    }
+}
+
+pub fn read_totp_qr_from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<TotpEntry> {
+    let path = path.as_ref();
+    let data = std::fs::read(path).map_err(|source| match source.kind() {
+        std::io::ErrorKind::NotFound => QrError::FileNotFound(path.to_path_buf()),
+        _ => QrError::Unreadable { path: path.to_path_buf(), source },
+    })?;
+    read_totp_qr_from_bytes(&data)
+}
 
-    // Prepare image for QR detection
-    let mut prepared = PreparedImage::prepare(gray_img);
+// What `read --dir` picks up: common screenshot and photo formats, plus PDF setup sheets
+const SCANNABLE_EXTENSIONS: [&str; 9] = ["png", "jpg", "jpeg", "gif", "webp", "bmp", "tif", "tiff", "pdf"];
 
-    // Detect QR grids
+/// Whether `path` has the extension of an image or PDF that `read_totp_qr_from_file` can scan.
+pub fn is_scannable_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| SCANNABLE_EXTENSIONS.iter().any(|known| extension.eq_ignore_ascii_case(known)))
+}
+
+/// Reads a TOTP entry from an image or document held in memory. Animated GIF, PNG and WebP
+/// files are scanned frame by frame, and PDFs page by page (with the `pdf` feature), until a
+/// QR code decodes.
+pub fn read_totp_qr_from_bytes(data: &[u8]) -> anyhow::Result<TotpEntry> {
+    if data.is_empty() {
+        return Err(QrError::EmptyFile.into());
+    }
+    if data.starts_with(b"%PDF-") {
+        return read_totp_qr_from_pdf(data);
+    }
+
+    let Some(format) = ImageReader::new(Cursor::new(data)).with_guessed_format()?.format() else {
+        return Err(QrError::UnsupportedFormat.into());
+    };
+    let mut frames = decode_frames(data, format).map_err(|e| match e {
+        ImageError::Unsupported(_) => QrError::UnsupportedFormat,
+        e => QrError::CorruptImage { format: format_name(format), reason: e.to_string() },
+    })?;
+
+    if frames.len() == 1 {
+        return read_totp_qr(frames.remove(0));
+    }
+    read_totp_qr_from_frames(frames, "frame")
+}
+
+// Every frame of an animation, or the single image of anything else
+fn decode_frames(data: &[u8], format: ImageFormat) -> ImageResult<Vec<DynamicImage>> {
+    match format {
+        ImageFormat::Gif => collect_frames(image::codecs::gif::GifDecoder::new(Cursor::new(data))?),
+        ImageFormat::Png => {
+            let decoder = image::codecs::png::PngDecoder::new(Cursor::new(data))?;
+            if decoder.is_apng()? {
+                collect_frames(decoder.apng()?)
+            } else {
+                Ok(vec![image::load_from_memory_with_format(data, format)?])
+            }
+        }
+        ImageFormat::WebP => {
+            let decoder = image::codecs::webp::WebPDecoder::new(Cursor::new(data))?;
+            if decoder.has_animation() {
+                collect_frames(decoder)
+            } else {
+                Ok(vec![image::load_from_memory_with_format(data, format)?])
+            }
+        }
+        _ => Ok(vec![image::load_from_memory_with_format(data, format)?]),
+    }
+}
+
+// "PNG", "JPG" and so on, for messages
+fn format_name(format: ImageFormat) -> &'static str {
+    match format {
+        ImageFormat::Png => "PNG",
+        ImageFormat::Jpeg => "JPEG",
+        ImageFormat::Gif => "GIF",
+        ImageFormat::WebP => "WebP",
+        ImageFormat::Bmp => "BMP",
+        ImageFormat::Tiff => "TIFF",
+        _ => "known",
+    }
+}
+
+// Returns the first QR code that decodes; the caller decides what it should contain
+fn decode_qr(image: &DynamicImage) -> anyhow::Result<Option<String>> {
+    let mut prepared = PreparedImage::prepare(image.to_luma8());
     let grids = prepared.detect_grids();
+    log::debug!("Detected {} QR grid(s) in a {}x{} image", grids.len(), image.width(), image.height());
 
-    if grids.is_empty() {
-        anyhow::bail!("No QR codes found in the image");
-    }
-
-    // Decode first QR code
-    let (_, content) = grids[0].decode()?;
-
-    // Check if it's a TOTP URL
-    if content.starts_with("otpauth://totp/") {
-        // otpauth://totp/name:user?secret=secret&issuer=issuer
-        let url = Url::parse(content.as_str())?;
-        let name = url.path_segments().and_then(|mut segments| segments.next_back()).unwrap_or("unknown").to_string();
-        let name = name.split(':').collect::<Vec<&str>>()[0];
-        
-        let query_pairs = url.query_pairs();
-        let mut secret = String::new();
-        let mut issuer = None;
-        for (key, value) in query_pairs {
-            match key.as_ref() {
-                "secret" => secret = value.to_string(),
-                "issuer" => issuer = Some(value.to_string()),
-                _ => {}
+    let mut last_error = None;
+    for (index, grid) in grids.into_iter().enumerate() {
+        match grid.decode() {
+            Ok((meta, content)) => {
+                log::debug!("Decoded grid {} (QR version {})", index + 1, meta.version.0);
+                return Ok(Some(content));
+            }
+            Err(e) => {
+                log::debug!("Grid {} failed to decode: {}", index + 1, e);
+                last_error = Some(e);
             }
         }
-        if secret.is_empty() {
-            anyhow::bail!("The TOTP URL does not contain a valid secret");
+    }
+
+    match last_error {
+        Some(e) => Err(QrError::Undecodable(e.to_string()).into()),
+        None => Ok(None),
+    }
+}
+
+fn parse_totp_content(content: &str) -> anyhow::Result<TotpEntry> {
+    // Website links and the like; otpauth URLs of the wrong type get a more specific error below
+    if !content.starts_with("otpauth://") {
+        anyhow::bail!("The QR code does not contain an otpauth URL");
+    }
+    otpauth::parse(content)
+}
+
+fn collect_frames<'a, D: AnimationDecoder<'a>>(decoder: D) -> ImageResult<Vec<DynamicImage>> {
+    let frames = decoder.into_frames().collect_frames()?;
+    Ok(frames.into_iter().map(|frame| DynamicImage::ImageRgba8(frame.into_buffer())).collect())
+}
+
+// Tries every frame (or page) in order and stops at the first one with a readable QR code
+fn read_totp_qr_from_frames(frames: Vec<DynamicImage>, unit: &str) -> anyhow::Result<TotpEntry> {
+    let count = frames.len();
+    let mut undecodable = None;
+
+    for (index, frame) in frames.iter().enumerate() {
+        match decode_qr(frame) {
+            Ok(Some(content)) => return parse_totp_content(&content),
+            Ok(None) => {}
+            Err(e) => {
+                let reason = match e.downcast::<QrError>() {
+                    Ok(QrError::Undecodable(reason)) => reason,
+                    Ok(other) => other.to_string(),
+                    Err(e) => e.to_string(),
+                };
+                undecodable.get_or_insert(format!("{} {}: {}", unit, index + 1, reason));
+            }
         }
-        let created_at = chrono::Utc::now().to_rfc3339(); // Use current timestamp
-        Ok(TotpEntry {
-            id: None,
-            name: name.to_string(),
-            secret,
-            issuer,
-            created_at, // You can set this to the current timestamp if needed
-        })
-    } else {
-        anyhow::bail!("❌ The QR code does not contain a valid TOTP URL")
+    }
+
+    match undecodable {
+        Some(reason) => Err(QrError::Undecodable(reason).into()),
+        None => Err(QrError::NoQrCode(format!("in any of the {} {}s", count, unit)).into()),
     }
 }
 
-pub fn read_totp_qr_from_file<P: AsRef<Path>>(path: P) -> anyhow::Result<TotpEntry> {
-    // Load image from file
-    let image = image::open(path)?;
+#[cfg(feature = "pdf")]
+fn read_totp_qr_from_pdf(data: &[u8]) -> anyhow::Result<TotpEntry> {
+    use hayro::hayro_interpret::InterpreterSettings;
+    use hayro::hayro_syntax::Pdf;
+    use hayro::vello_cpu::color::palette::css::WHITE;
+    use hayro::{PixmapSettings, RenderCache, RenderSettings};
+
+    // PDF units are 1/72 inch; 3x gives roughly 216 dpi, plenty for rqrr
+    const PDF_SCALE: f32 = 3.0;
+
+    let pdf = Pdf::new(data.to_vec()).map_err(|e| anyhow::anyhow!("Could not read the PDF: {:?}", e))?;
+    let cache = RenderCache::new();
+    let settings = PixmapSettings { x_scale: PDF_SCALE, y_scale: PDF_SCALE, bg_color: WHITE };
+
+    let mut pages = Vec::new();
+    for page in pdf.pages().iter() {
+        let pixmap = hayro::render(page, &cache, &InterpreterSettings::default(), &RenderSettings::default(), &settings);
+        let (width, height) = (pixmap.width() as u32, pixmap.height() as u32);
+        // The background is opaque, so premultiplied and straight alpha are the same here
+        let rgba = image::RgbaImage::from_raw(width, height, pixmap.data_as_u8_slice().to_vec())
+            .ok_or_else(|| anyhow::anyhow!("Rendered PDF page has an unexpected size"))?;
+        pages.push(DynamicImage::ImageRgba8(rgba));
+    }
+
+    read_totp_qr_from_frames(pages, "page")
+}
+
+#[cfg(not(feature = "pdf"))]
+fn read_totp_qr_from_pdf(_data: &[u8]) -> anyhow::Result<TotpEntry> {
+    anyhow::bail!("PDF support is not included in this build; rebuild with `--features pdf`, or save the QR code as an image")
+}
+
+/// How `qr --output` renders an entry's QR code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QrOutput {
+    /// Unicode half blocks printed to the terminal
+    Utf8,
+    /// A bitmap for printing
+    Png,
+    /// A scalable image
+    Svg,
+}
+
+impl QrOutput {
+    pub const ALL: [Self; 3] = [Self::Utf8, Self::Png, Self::Svg];
+
+    /// Parses an `--output` value, ignoring case.
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|output| output.as_str().eq_ignore_ascii_case(name))
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf8",
+            Self::Png => "png",
+            Self::Svg => "svg",
+        }
+    }
+
+    /// Whether the code is written to a file rather than printed.
+    pub fn writes_file(self) -> bool {
+        self != Self::Utf8
+    }
+}
+
+/// Renders `data` as a QR code made of Unicode half blocks for display in a terminal.
+pub fn render_qr_terminal(data: &str) -> anyhow::Result<String> {
+    let code = qrcode::QrCode::new(data.as_bytes())?;
+    Ok(code
+        .render::<qrcode::render::unicode::Dense1x2>()
+        .dark_color(qrcode::render::unicode::Dense1x2::Light)
+        .light_color(qrcode::render::unicode::Dense1x2::Dark)
+        .build())
+}
+
+/// Writes `data` as a black-on-white QR code PNG.
+pub fn write_qr_png<P: AsRef<Path>>(data: &str, path: P) -> anyhow::Result<()> {
+    let code = qrcode::QrCode::new(data.as_bytes())?;
+    let image = code.render::<image::Luma<u8>>().min_dimensions(256, 256).build();
+    image.save(path)?;
+    Ok(())
+}
+
+/// Renders `data` as a black-on-white QR code SVG document.
+pub fn render_qr_svg(data: &str) -> anyhow::Result<String> {
+    let code = qrcode::QrCode::new(data.as_bytes())?;
+    Ok(code.render::<qrcode::render::svg::Color>().min_dimensions(256, 256).build())
+}
+
+/// Writes `data` as a black-on-white QR code SVG.
+pub fn write_qr_svg<P: AsRef<Path>>(data: &str, path: P) -> anyhow::Result<()> {
+    std::fs::write(path, render_qr_svg(data)?)?;
+    Ok(())
+}
+
+/// Turns an entry name into a file stem that's safe on every filesystem: anything outside
+/// `[A-Za-z0-9._-]` becomes `_`, and leading dots are dropped so files aren't hidden.
+pub fn safe_file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') { c } else { '_' })
+        .collect();
+    let stem = stem.trim_start_matches('.');
+
+    if stem.is_empty() { "entry".to_string() } else { stem.to_string() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const URL: &str = "otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub";
+
+    fn animated_gif(frames: Vec<image::RgbaImage>) -> Vec<u8> {
+        let mut data = Vec::new();
+        let mut encoder = image::codecs::gif::GifEncoder::new(&mut data);
+        encoder.encode_frames(frames.into_iter().map(image::Frame::new)).unwrap();
+        drop(encoder);
+        data
+    }
+
+    fn qr_frame() -> image::RgbaImage {
+        let code = qrcode::QrCode::new(URL.as_bytes()).unwrap();
+        let image = code.render::<image::Luma<u8>>().min_dimensions(256, 256).build();
+        DynamicImage::ImageLuma8(image).to_rgba8()
+    }
+
+    #[test]
+    fn test_read_qr_from_later_gif_frame() {
+        let qr = qr_frame();
+        let blank = image::RgbaImage::from_pixel(qr.width(), qr.height(), image::Rgba([255, 255, 255, 255]));
+        let data = animated_gif(vec![blank, qr]);
+
+        let entry = read_totp_qr_from_bytes(&data).unwrap();
+        assert_eq!(entry.name, "alice");
+        assert_eq!(entry.issuer.as_deref(), Some("GitHub"));
+    }
 
-    // Read TOTP entry from the QR code in the image
-    read_totp_qr(image)
+    #[test]
+    fn test_read_qr_normalizes_padded_lowercase_secret() {
+        let code = qrcode::QrCode::new(b"otpauth://totp/alice?secret=gezdgnbvgy3tqojqge======").unwrap();
+        let image = code.render::<image::Luma<u8>>().min_dimensions(256, 256).build();
+
+        let entry = read_totp_qr(DynamicImage::ImageLuma8(image)).unwrap();
+        assert_eq!(entry.secret, "GEZDGNBVGY3TQOJQGE");
+    }
+
+    #[test]
+    fn test_gif_without_qr_reports_frame_count() {
+        let blank = image::RgbaImage::from_pixel(64, 64, image::Rgba([255, 255, 255, 255]));
+        let data = animated_gif(vec![blank.clone(), blank]);
+
+        let error = read_totp_qr_from_bytes(&data).unwrap_err();
+        assert!(matches!(error.downcast_ref(), Some(QrError::NoQrCode(place)) if place == "in any of the 2 frames"), "{}", error);
+    }
+
+    #[test]
+    fn test_unreadable_files_say_why() {
+        let kind = |result: anyhow::Result<TotpEntry>| result.unwrap_err().downcast::<QrError>().unwrap();
+
+        let missing = std::env::temp_dir().join("totp-no-such-file.png");
+        assert!(matches!(kind(read_totp_qr_from_file(&missing)), QrError::FileNotFound(path) if path == missing));
+        assert!(matches!(kind(read_totp_qr_from_file(std::env::temp_dir())), QrError::Unreadable { .. }));
+        assert!(matches!(kind(read_totp_qr_from_bytes(b"")), QrError::EmptyFile));
+        assert!(matches!(kind(read_totp_qr_from_bytes(b"name,secret\ngithub,JBSWY3DPEHPK3PXP\n")), QrError::UnsupportedFormat));
+
+        // A PNG cut off halfway through, as after an interrupted download
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(qr_frame()).write_to(&mut Cursor::new(&mut png), ImageFormat::Png).unwrap();
+        let error = kind(read_totp_qr_from_bytes(&png[..png.len() / 2]));
+        assert!(matches!(error, QrError::CorruptImage { format: "PNG", .. }), "{}", error);
+
+        let blank = DynamicImage::ImageRgba8(image::RgbaImage::from_pixel(64, 64, image::Rgba([255, 255, 255, 255])));
+        assert!(matches!(kind(read_totp_qr(blank)), QrError::NoQrCode(_)));
+    }
+
+    #[cfg(feature = "pdf")]
+    #[test]
+    fn test_read_qr_from_pdf() {
+        // One page drawing the QR modules as filled squares, 4pt each, inside a quiet zone
+        let code = qrcode::QrCode::new(URL.as_bytes()).unwrap();
+        let width = code.width();
+        let mut content = String::new();
+        for (index, color) in code.to_colors().iter().enumerate() {
+            if *color == qrcode::Color::Dark {
+                let (x, y) = (index % width, index / width);
+                content.push_str(&format!("{} {} 4 4 re\n", 20 + x * 4, 20 + (width - 1 - y) * 4));
+            }
+        }
+        content.push_str("f\n");
+        let size = 40 + width * 4;
+
+        let objects = [
+            "<< /Type /Catalog /Pages 2 0 R >>".to_string(),
+            "<< /Type /Pages /Kids [3 0 R] /Count 1 >>".to_string(),
+            format!("<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Contents 4 0 R >>", size, size),
+            format!("<< /Length {} >>\nstream\n{}endstream", content.len(), content),
+        ];
+        let mut pdf = String::from("%PDF-1.4\n");
+        let mut offsets = Vec::new();
+        for (index, object) in objects.iter().enumerate() {
+            offsets.push(pdf.len());
+            pdf.push_str(&format!("{} 0 obj\n{}\nendobj\n", index + 1, object));
+        }
+        let xref = pdf.len();
+        pdf.push_str(&format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1));
+        for offset in offsets {
+            pdf.push_str(&format!("{:010} 00000 n \n", offset));
+        }
+        pdf.push_str(&format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref));
+
+        let entry = read_totp_qr_from_bytes(pdf.as_bytes()).unwrap();
+        assert_eq!(entry.name, "alice");
+    }
+
+    #[test]
+    fn test_qr_outputs() {
+        assert_eq!(QrOutput::parse("PNG"), Some(QrOutput::Png));
+        assert_eq!(QrOutput::parse("utf8"), Some(QrOutput::Utf8));
+        assert_eq!(QrOutput::parse("jpeg"), None);
+        assert!(!QrOutput::Utf8.writes_file());
+        assert!(QrOutput::Svg.writes_file());
+    }
+
+    #[test]
+    fn test_written_qr_codes_scan_back() {
+        let png = std::env::temp_dir().join(format!("totp-qr-{}.png", std::process::id()));
+        write_qr_png(URL, &png).unwrap();
+        let entry = read_totp_qr_from_file(&png);
+        std::fs::remove_file(&png).unwrap();
+        assert_eq!(entry.unwrap().name, "alice");
+
+        let svg = render_qr_svg(URL).unwrap();
+        assert!(svg.contains("<svg") && svg.ends_with("</svg>"), "{}", svg);
+    }
+
+    #[test]
+    fn test_is_scannable_file() {
+        assert!(is_scannable_file(Path::new("shots/github.png")));
+        assert!(is_scannable_file(Path::new("IMG_0001.JPEG")));
+        assert!(is_scannable_file(Path::new("setup.pdf")));
+        assert!(!is_scannable_file(Path::new("notes.txt")));
+        assert!(!is_scannable_file(Path::new("README")));
+    }
+
+    #[test]
+    fn test_safe_file_stem() {
+        assert_eq!(safe_file_stem("github"), "github");
+        assert_eq!(safe_file_stem("alice@example.com"), "alice_example.com");
+        assert_eq!(safe_file_stem("../etc/passwd"), "_etc_passwd");
+        assert_eq!(safe_file_stem("Work: VPN"), "Work__VPN");
+        assert_eq!(safe_file_stem("..."), "entry");
+    }
 }
@@ -0,0 +1,124 @@
+use image::DynamicImage;
+
+// `read --screen`: grabs the screen (or part of it) from the X server so a QR code shown in a
+// browser can be scanned without saving a screenshot first. X11 is spoken directly through
+// `x11rb`, which the clipboard support already builds on. Wayland compositors don't let clients
+// read the screen this way, and there is nothing to capture on a headless machine.
+
+/// A rectangle of the screen, in pixels from its top left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub x: i16,
+    pub y: i16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Region {
+    /// Reads an X11 geometry, `<width>x<height>+<x>+<y>`, as printed by region pickers such as
+    /// `slop` and by `xwininfo`.
+    pub fn parse(geometry: &str) -> Option<Self> {
+        let (size, offset) = geometry.trim().split_once('+')?;
+        let (width, height) = size.split_once(['x', 'X'])?;
+        let (x, y) = offset.split_once('+')?;
+
+        let region = Self { x: x.parse().ok()?, y: y.parse().ok()?, width: width.parse().ok()?, height: height.parse().ok()? };
+        (region.x >= 0 && region.y >= 0 && region.width > 0 && region.height > 0).then_some(region)
+    }
+}
+
+impl std::fmt::Display for Region {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}x{}+{}+{}", self.width, self.height, self.x, self.y)
+    }
+}
+
+/// Captures `region` of the screen, or all of it, as an RGB image.
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+pub fn capture_screen(region: Option<Region>) -> anyhow::Result<DynamicImage> {
+    use x11rb::connection::Connection;
+    use x11rb::image::{Image, PixelLayout};
+
+    if std::env::var_os("DISPLAY").is_none() {
+        if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+            anyhow::bail!("Screen capture needs X11, and this Wayland session has no X display ($DISPLAY isn't set); take a screenshot and use `read <image_path>` instead");
+        }
+        anyhow::bail!("No screen to capture: $DISPLAY isn't set, as on a headless machine or over SSH; take a screenshot and use `read <image_path>` instead");
+    }
+
+    let (conn, screen_number) = x11rb::connect(None).map_err(|e| anyhow::anyhow!("Could not connect to the X display: {}", e))?;
+    let screen = &conn.setup().roots[screen_number];
+    let (screen_width, screen_height) = (screen.width_in_pixels, screen.height_in_pixels);
+
+    let region = region.unwrap_or(Region { x: 0, y: 0, width: screen_width, height: screen_height });
+    let fits = |start: i16, length: u16, limit: u16| start as u32 + length as u32 <= limit as u32;
+    if !fits(region.x, region.width, screen_width) || !fits(region.y, region.height, screen_height) {
+        anyhow::bail!("The region {} doesn't fit on the {}x{} screen", region, screen_width, screen_height);
+    }
+
+    let (image, visual_id) = Image::get(&conn, screen.root, region.x, region.y, region.width, region.height)
+        .map_err(|e| anyhow::anyhow!("Could not capture the screen: {}", e))?;
+    let visual = screen
+        .allowed_depths
+        .iter()
+        .flat_map(|depth| &depth.visuals)
+        .find(|visual| visual.visual_id == visual_id)
+        .ok_or_else(|| anyhow::anyhow!("The X server captured the screen in an unknown format"))?;
+    let layout = PixelLayout::from_visual_type(*visual)
+        .map_err(|_| anyhow::anyhow!("Screen capture needs a true color display; palette and grayscale displays aren't supported"))?;
+
+    Ok(to_rgb(&image, layout))
+}
+
+/// Captures `region` of the screen, or all of it, as an RGB image.
+#[cfg(not(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten")))))]
+pub fn capture_screen(_region: Option<Region>) -> anyhow::Result<DynamicImage> {
+    anyhow::bail!("Screen capture is only supported on X11 desktops; take a screenshot and use `read <image_path>` instead")
+}
+
+// Pixel values mean whatever the visual says, e.g. 5-6-5 bits on 16-bit displays; the image
+// crate wants 8 bits per channel
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+fn to_rgb(image: &x11rb::image::Image, layout: x11rb::image::PixelLayout) -> DynamicImage {
+    let rgb = image::RgbImage::from_fn(image.width().into(), image.height().into(), |x, y| {
+        let (red, green, blue) = layout.decode(image.get_pixel(x as u16, y as u16));
+        image::Rgb([(red >> 8) as u8, (green >> 8) as u8, (blue >> 8) as u8])
+    });
+    DynamicImage::ImageRgb8(rgb)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_region() {
+        assert_eq!(Region::parse("200x100+30+40"), Some(Region { x: 30, y: 40, width: 200, height: 100 }));
+        assert_eq!(Region::parse(" 640X480+0+0\n"), Some(Region { x: 0, y: 0, width: 640, height: 480 }));
+        assert_eq!(Region::parse("200x100+30+40").unwrap().to_string(), "200x100+30+40");
+        for geometry in ["200x100", "200x100+30", "0x100+0+0", "200x100+-5+0", "200x100-5-0", "axb+1+2", ""] {
+            assert_eq!(Region::parse(geometry), None, "{}", geometry);
+        }
+    }
+
+    #[cfg(all(unix, not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))))]
+    #[test]
+    fn test_captured_pixels_scan() {
+        use x11rb::image::{BitsPerPixel, ColorComponent, Image, ImageOrder, PixelLayout, ScanlinePad};
+
+        // A QR code as a 16-bit (5-6-5) display would hand it over
+        let code = qrcode::QrCode::new(b"otpauth://totp/GitHub:alice?secret=JBSWY3DPEHPK3PXP&issuer=GitHub").unwrap();
+        let qr = code.render::<image::Luma<u8>>().min_dimensions(256, 256).build();
+        let layout = PixelLayout::new(ColorComponent::new(5, 11).unwrap(), ColorComponent::new(6, 5).unwrap(), ColorComponent::new(5, 0).unwrap());
+        let mut captured = Image::allocate(qr.width() as u16, qr.height() as u16, ScanlinePad::Pad32, 16, BitsPerPixel::B16, ImageOrder::LsbFirst);
+        for (x, y, pixel) in qr.enumerate_pixels() {
+            let level = u16::from(pixel.0[0]) * 0x101;
+            captured.put_pixel(x as u16, y as u16, layout.encode((level, level, level)));
+        }
+
+        let rgb = to_rgb(&captured, layout);
+        assert_eq!(rgb.as_rgb8().unwrap().get_pixel(0, 0).0, [255, 255, 255]);
+        let entry = crate::qrcode::read_totp_qr(rgb).unwrap();
+        assert_eq!((entry.name.as_str(), entry.issuer.as_deref()), ("alice", Some("GitHub")));
+    }
+}
@@ -0,0 +1,136 @@
+use std::env;
+use std::io::IsTerminal;
+use std::sync::OnceLock;
+
+// Output styling
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ColorChoice {
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(Self::Auto),
+            "always" => Some(Self::Always),
+            "never" => Some(Self::Never),
+            _ => None,
+        }
+    }
+
+    fn resolve(self) -> bool {
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            // Plain output when NO_COLOR is set or stdout is piped
+            Self::Auto => env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal(),
+        }
+    }
+}
+
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// Fix the color decision for the rest of the process. Only the first call has any effect.
+pub(crate) fn init(choice: ColorChoice) {
+    let _ = ENABLED.set(choice.resolve());
+}
+
+pub(crate) fn enabled() -> bool {
+    *ENABLED.get_or_init(|| ColorChoice::Auto.resolve())
+}
+
+/// Returns the decorative prefix when styling is enabled, or nothing for plain output.
+pub(crate) fn icon(icon: &'static str) -> &'static str {
+    if enabled() { icon } else { "" }
+}
+
+pub(crate) fn red(text: &str) -> String {
+    paint(text, "31")
+}
+
+/// Seconds left before a code expires at which it is flagged.
+pub(crate) const LOW_TIME: u64 = 5;
+
+/// Formats the seconds left on a code as `12s`, in red once it is about to expire.
+pub(crate) fn remaining(seconds: u64) -> String {
+    let text = format!("{}s", seconds);
+    if seconds <= LOW_TIME { red(&text) } else { text }
+}
+
+fn paint(text: &str, code: &str) -> String {
+    if enabled() {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+static QUIET: OnceLock<bool> = OnceLock::new();
+
+/// Suppress confirmations and decoration for scripting (`--quiet`). Only the first call has any effect.
+pub(crate) fn init_quiet(quiet: bool) {
+    let _ = QUIET.set(quiet);
+}
+
+pub(crate) fn quiet() -> bool {
+    *QUIET.get_or_init(|| false)
+}
+
+/// Splits a code in the middle so it is easier to read and type, such as `123 456` or
+/// `1234 5678`. Odd lengths put the shorter half first (`123 4567`). `None` leaves it as is.
+pub(crate) fn group_code(code: &str, separator: Option<&str>) -> String {
+    match separator {
+        Some(separator) if code.len() > 1 => {
+            let (first, second) = code.split_at(code.len() / 2);
+            format!("{}{}{}", first, separator, second)
+        }
+        _ => code.to_string(),
+    }
+}
+
+const BAR_WIDTH: usize = 10;
+
+/// Renders a fixed-width countdown bar such as `[██████░░░░] ` for the time left in the period,
+/// given the elapsed fraction from [`Totp::progress`](console_totp::Totp::progress).
+/// Plain output gets no bar at all, so callers can prefix it unconditionally.
+pub(crate) fn countdown_bar(progress: f64) -> String {
+    if !enabled() {
+        return String::new();
+    }
+
+    let filled = filled_cells(progress, BAR_WIDTH);
+    format!("[{}{}] ", "█".repeat(filled), "░".repeat(BAR_WIDTH - filled))
+}
+
+// Rounds up, so the bar only empties completely at the rollover
+fn filled_cells(progress: f64, width: usize) -> usize {
+    let remaining = (1.0 - progress).clamp(0.0, 1.0);
+    ((remaining * width as f64).ceil() as usize).min(width)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filled_cells() {
+        assert_eq!(filled_cells(0.0, 10), 10);
+        assert_eq!(filled_cells(0.4, 10), 6);
+        assert_eq!(filled_cells(0.45, 10), 6);
+        assert_eq!(filled_cells(29.9 / 30.0, 10), 1);
+        assert_eq!(filled_cells(1.0, 10), 0);
+        assert_eq!(filled_cells(-0.5, 10), 10);
+    }
+
+    #[test]
+    fn test_group_code() {
+        assert_eq!(group_code("123456", Some(" ")), "123 456");
+        assert_eq!(group_code("12345678", Some(" ")), "1234 5678");
+        assert_eq!(group_code("1234567", Some("-")), "123-4567");
+        assert_eq!(group_code("123456", Some(" · ")), "123 · 456");
+        assert_eq!(group_code("123456", None), "123456");
+        assert_eq!(group_code("7", Some(" ")), "7");
+    }
+}
@@ -0,0 +1,107 @@
+use unicode_width::UnicodeWidthStr;
+
+// Column-aligned output for list, search and get --all
+const COLUMN_GAP: &str = "  ";
+
+/// A simple text table. Column widths come from the widest cell, measured in terminal columns
+/// so that CJK and other wide characters line up and color codes take no space.
+pub(crate) struct Table {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub(crate) fn new(headers: &[&str]) -> Self {
+        Self {
+            headers: headers.iter().map(|header| header.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Adds a row. Missing trailing cells are left blank.
+    pub(crate) fn add_row(&mut self, row: Vec<String>) {
+        self.rows.push(row);
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let mut widths: Vec<usize> = self.headers.iter().map(|header| display_width(header)).collect();
+        for row in &self.rows {
+            for (index, cell) in row.iter().enumerate().take(widths.len()) {
+                widths[index] = widths[index].max(display_width(cell));
+            }
+        }
+
+        let mut output = render_line(&self.headers, &widths);
+        let separator: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        output.push_str(&render_line(&separator, &widths));
+        for row in &self.rows {
+            output.push_str(&render_line(row, &widths));
+        }
+        output
+    }
+}
+
+fn render_line(cells: &[String], widths: &[usize]) -> String {
+    let mut line = String::new();
+    for (index, width) in widths.iter().enumerate() {
+        let cell = cells.get(index).map(String::as_str).unwrap_or("");
+        if index > 0 {
+            line.push_str(COLUMN_GAP);
+        }
+        line.push_str(cell);
+        // `format!("{:width$}")` pads by chars, not display columns
+        line.push_str(&" ".repeat(width.saturating_sub(display_width(cell))));
+    }
+
+    // No trailing whitespace when the last column is short
+    let mut line = line.trim_end().to_string();
+    line.push('\n');
+    line
+}
+
+// Terminal columns taken by `text`, skipping ANSI color sequences such as `\x1b[31m`
+fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut rest = text;
+    while let Some(start) = rest.find('\x1b') {
+        width += rest[..start].width();
+        rest = &rest[start..];
+        match rest.find('m') {
+            Some(end) => rest = &rest[end + 1..],
+            None => rest = "",
+        }
+    }
+    width + rest.width()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_aligns_wide_characters() {
+        let mut table = Table::new(&["Name", "Code"]);
+        table.add_row(vec!["github".to_string(), "123456".to_string()]);
+        table.add_row(vec!["微软".to_string(), "654321".to_string()]);
+        table.add_row(vec!["x".to_string()]);
+
+        assert_eq!(
+            table.render(),
+            "Name    Code\n\
+             ------  ------\n\
+             github  123456\n\
+             微软    654321\n\
+             x\n"
+        );
+    }
+
+    #[test]
+    fn test_render_ignores_color_codes() {
+        let mut table = Table::new(&["Code", "Expires in"]);
+        table.add_row(vec!["123456".to_string(), "\x1b[31m3s\x1b[0m".to_string()]);
+        table.add_row(vec!["654321".to_string(), "25s".to_string()]);
+
+        assert_eq!(display_width("\x1b[31m3s\x1b[0m"), 2);
+        assert!(table.render().starts_with("Code    Expires in\n------  ----------\n"));
+    }
+}
@@ -0,0 +1,101 @@
+// Output templates such as "{name}: {code}" for scripting
+const PLACEHOLDERS: [&str; 4] = ["name", "code", "issuer", "remaining"];
+
+#[derive(Debug, PartialEq)]
+enum Segment {
+    Literal(String),
+    Placeholder(String),
+}
+
+/// A parsed output template. `{placeholder}` is substituted and `{{` / `}}` produce literal braces.
+#[derive(Debug)]
+pub(crate) struct Template {
+    segments: Vec<Segment>,
+}
+
+impl Template {
+    /// Parses `source`, rejecting unknown placeholders and unbalanced braces up front so a typo
+    /// fails before anything is printed.
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = source.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(format!("unclosed placeholder: {{{}", name)),
+                        }
+                    }
+                    if !PLACEHOLDERS.contains(&name.as_str()) {
+                        return Err(format!(
+                            "unknown placeholder: {{{}}} (expected one of {})",
+                            name,
+                            PLACEHOLDERS.map(|placeholder| format!("{{{}}}", placeholder)).join(", ")
+                        ));
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Segment::Placeholder(name));
+                }
+                '}' => return Err("unmatched '}' (use '}}' for a literal brace)".to_string()),
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Self { segments })
+    }
+
+    /// Renders the template, asking `value` for each placeholder.
+    pub(crate) fn render(&self, value: impl Fn(&str) -> String) -> String {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Literal(text) => text.clone(),
+                Segment::Placeholder(name) => value(name),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_placeholders_and_escapes() {
+        let template = Template::parse("{{{name}}} {code} ({remaining}s)").unwrap();
+        let rendered = template.render(|name| match name {
+            "name" => "github".to_string(),
+            "code" => "123456".to_string(),
+            "remaining" => "17".to_string(),
+            _ => String::new(),
+        });
+        assert_eq!(rendered, "{github} 123456 (17s)");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Template::parse("{code}").is_ok());
+        assert!(Template::parse("{secret}").unwrap_err().starts_with("unknown placeholder: {secret}"));
+        assert!(Template::parse("{code").is_err());
+        assert!(Template::parse("code}").is_err());
+    }
+}
@@ -1,54 +1,267 @@
+use std::fmt;
+use std::marker::PhantomData;
 use std::time::{SystemTime, UNIX_EPOCH};
-use sha1::{Digest, Sha1};
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha1::digest::core_api::BlockSizeUser;
+use sha1::digest::Digest;
+use sha2::{Sha256, Sha512};
+use zeroize::Zeroizing;
+use crate::base32::base32_decode;
+use crate::error::{Error, Result};
+use crate::TotpEntry;
+
+/// Decoded secrets shorter than this (80 bits) are almost certainly typos or truncated copies.
+pub const MIN_SECRET_LEN: usize = 10;
+
+pub const DEFAULT_DIGITS: u32 = 6;
+pub const DEFAULT_PERIOD: u64 = 30;
+
+/// Hash function used for the HMAC, as named by the otpauth `algorithm` parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    #[default]
+    #[serde(rename = "SHA1")]
+    Sha1,
+    #[serde(rename = "SHA256")]
+    Sha256,
+    #[serde(rename = "SHA512")]
+    Sha512,
+}
+
+impl HashAlgorithm {
+    /// Reads an algorithm name the way providers write it: any casing, with or without the
+    /// hyphen (`SHA1`, `sha1`, `SHA-1`).
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_ascii_uppercase().as_str() {
+            "SHA1" | "SHA-1" => Some(Self::Sha1),
+            "SHA256" | "SHA-256" => Some(Self::Sha256),
+            "SHA512" | "SHA-512" => Some(Self::Sha512),
+            _ => None,
+        }
+    }
+
+    /// HMAC output size in bytes.
+    pub fn output_len(self) -> usize {
+        match self {
+            Self::Sha1 => 20,
+            Self::Sha256 => 32,
+            Self::Sha512 => 64,
+        }
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Sha1 => "SHA1",
+            Self::Sha256 => "SHA256",
+            Self::Sha512 => "SHA512",
+        }
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Checks code parameters before they are stored or used. RFC 4226 allows 6 to 8 digits.
+pub fn validate_params(digits: u32, period: u64) -> Result<(), String> {
+    if !(6..=8).contains(&digits) {
+        return Err(format!("digits must be between 6 and 8, got {}", digits));
+    }
+    if period == 0 {
+        return Err("period must be at least 1 second".to_string());
+    }
+    Ok(())
+}
+
+/// Checks a fixed truncation offset: the four bytes read from it must fit in the HMAC.
+pub fn validate_truncation_offset(algorithm: HashAlgorithm, offset: u8) -> Result<(), String> {
+    let max = algorithm.output_len() - 4;
+    if offset as usize > max {
+        return Err(format!("truncation offset must be between 0 and {} for {}, got {}", max, algorithm, offset));
+    }
+    Ok(())
+}
+
+/// Says what is wrong with decoded secret bytes that look like a placeholder rather than a
+/// random seed: all zero, one byte repeated, a counting sequence or a run of bytes repeated.
+/// A real seed of [`MIN_SECRET_LEN`] bytes or more matches none of these by chance.
+pub fn weak_secret(bytes: &[u8]) -> Option<&'static str> {
+    let (first, rest) = bytes.split_first()?;
+    if *first == 0 && rest.iter().all(|&byte| byte == 0) {
+        return Some("all zero bytes");
+    }
+    if rest.iter().all(|byte| byte == first) {
+        return Some("one byte repeated");
+    }
+
+    // Steps of any size, wrapping at 256, e.g. 01 02 03... or ff fe fd...
+    let step = |pair: &[u8]| pair[1].wrapping_sub(pair[0]);
+    if bytes.len() >= 4 && bytes.windows(2).all(|pair| step(pair) == step(bytes)) {
+        return Some("a counting sequence");
+    }
+
+    let repeats = |period: usize| bytes.iter().skip(period).zip(bytes).all(|(byte, earlier)| byte == earlier);
+    if (2..=bytes.len() / 2).any(repeats) {
+        return Some("the same few bytes repeated");
+    }
+
+    None
+}
+
+/// Seconds since the Unix epoch, or a clear error instead of a panic when the clock is set
+/// before 1970 (codes generated from such a clock would be wrong anyway).
+pub fn unix_time(time: SystemTime) -> Result<u64> {
+    since_epoch(time).map(|elapsed| elapsed.as_secs())
+}
+
+/// Milliseconds since the Unix epoch, for sub-second countdowns.
+pub fn unix_time_ms(time: SystemTime) -> Result<u64> {
+    since_epoch(time).map(|elapsed| elapsed.as_millis() as u64)
+}
+
+fn since_epoch(time: SystemTime) -> Result<std::time::Duration> {
+    time.duration_since(UNIX_EPOCH).map_err(|_| Error::Clock)
+}
 
 // TOTP implementation
+/// A code generator for one secret. The secret is decoded and padded into an HMAC key once
+/// when the generator is built, so keep a `Totp` around to produce many codes cheaply.
 #[derive(Debug, Clone)]
-pub(crate) struct Totp {
-    secret: Vec<u8>,
+pub struct Totp {
+    key: HmacKey,
     time_step: u64,
     digits: usize,
+    algorithm: HashAlgorithm,
+    truncation_offset: Option<usize>,
+    // RFC 6238 T0: the Unix time at which the first time step starts
+    epoch: u64,
 }
 
 impl Totp {
-    pub(crate) fn new(secret: Vec<u8>) -> Self {
+    /// Takes ownership of `secret` so it can be wiped as soon as the HMAC key is derived from it.
+    pub fn new(secret: Vec<u8>) -> Self {
+        let secret = Zeroizing::new(secret);
         Self {
-            secret,
-            time_step: 30,
-            digits: 6,
+            key: HmacKey::new(HashAlgorithm::Sha1, &secret),
+            time_step: DEFAULT_PERIOD,
+            digits: DEFAULT_DIGITS as usize,
+            algorithm: HashAlgorithm::Sha1,
+            truncation_offset: None,
+            epoch: 0,
         }
     }
 
-    pub(crate) fn generate(&self) -> anyhow::Result<String> {
-        let time = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
-        let counter = time / self.time_step;
+    pub fn with_params(secret: Vec<u8>, algorithm: HashAlgorithm, digits: u32, period: u64) -> Result<Self> {
+        Self::from_secret(&Zeroizing::new(secret), algorithm, digits, period)
+    }
 
-        let counter_bytes = counter.to_be_bytes();
+    fn from_secret(secret: &[u8], algorithm: HashAlgorithm, digits: u32, period: u64) -> Result<Self> {
+        validate_params(digits, period).map_err(Error::InvalidParameters)?;
+        // HMAC accepts an empty key, but the codes it gives are the same for everyone
+        if secret.is_empty() {
+            return Err(Error::InvalidParameters("secret is empty".to_string()));
+        }
 
-        // HMAC-SHA1
-        let mut hasher = Sha1::new();
-        let mut ipad = [0x36; 64];
-        let mut opad = [0x5c; 64];
+        Ok(Self {
+            key: HmacKey::new(algorithm, secret),
+            time_step: period,
+            digits: digits as usize,
+            algorithm,
+            truncation_offset: None,
+            epoch: 0,
+        })
+    }
 
-        let mut key = self.secret.clone();
-        if key.len() > 64 {
-            key = Sha1::digest(&key).to_vec();
+    /// Reads the code from a fixed byte offset of the HMAC instead of using dynamic
+    /// truncation. Only some legacy tokens need this; `None` keeps the RFC 4226 behaviour.
+    pub fn with_truncation_offset(mut self, offset: Option<u8>) -> Result<Self> {
+        if let Some(offset) = offset {
+            validate_truncation_offset(self.algorithm, offset).map_err(Error::InvalidParameters)?;
         }
-        key.resize(64, 0);
+        self.truncation_offset = offset.map(usize::from);
+        Ok(self)
+    }
 
-        for i in 0..64 {
-            ipad[i] ^= key[i];
-            opad[i] ^= key[i];
-        }
+    /// Counts time steps from `epoch` (RFC 6238's T0, in seconds since the Unix epoch) instead
+    /// of from 0, as a few systems do. Codes can't be generated for times before it.
+    pub fn with_epoch(mut self, epoch: u64) -> Self {
+        self.epoch = epoch;
+        self
+    }
+
+    /// Decodes a stored entry's secret and applies its algorithm, digits, period, truncation
+    /// and epoch.
+    pub fn from_entry(entry: &TotpEntry) -> Result<Self> {
+        let secret = base32_decode(&entry.secret)?;
+        Ok(Self::from_secret(&secret, entry.algorithm, entry.digits, entry.period)?
+            .with_truncation_offset(entry.truncation_offset)?
+            .with_epoch(entry.epoch))
+    }
 
-        hasher.update(ipad);
-        hasher.update(counter_bytes);
-        let inner_hash = hasher.finalize_reset();
+    pub fn generate(&self) -> Result<String> {
+        self.generate_at_time(unix_time(SystemTime::now())?)
+    }
+
+    pub fn period(&self) -> u64 {
+        self.time_step
+    }
 
-        hasher.update(opad);
-        hasher.update(inner_hash);
-        let hmac = hasher.finalize();
+    pub fn epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn time_remaining(&self) -> Result<u64> {
+        Ok(self.time_remaining_at(unix_time(SystemTime::now())?))
+    }
+
+    /// Seconds until the code for `unix_time` rolls over.
+    pub fn time_remaining_at(&self, unix_time: u64) -> u64 {
+        self.time_step - elapsed_in_step(unix_time.into(), self.epoch.into(), self.time_step)
+    }
+
+    pub fn time_remaining_ms(&self) -> Result<u64> {
+        Ok(self.time_remaining_ms_at(unix_time_ms(SystemTime::now())?))
+    }
+
+    /// Milliseconds until the code for `unix_time_ms` rolls over.
+    pub fn time_remaining_ms_at(&self, unix_time_ms: u64) -> u64 {
+        let period_ms = self.period_ms();
+        period_ms - elapsed_in_step(unix_time_ms.into(), i128::from(self.epoch) * 1000, period_ms)
+    }
+
+    /// How much of the current period has elapsed, from 0.0 right after a rollover towards
+    /// 1.0 just before the next one. Handy for drawing progress indicators.
+    pub fn progress(&self) -> Result<f64> {
+        Ok(self.progress_at(unix_time_ms(SystemTime::now())?))
+    }
+
+    pub fn progress_at(&self, unix_time_ms: u64) -> f64 {
+        let period_ms = self.period_ms();
+        elapsed_in_step(unix_time_ms.into(), i128::from(self.epoch) * 1000, period_ms) as f64 / period_ms as f64
+    }
+
+    fn period_ms(&self) -> u64 {
+        self.time_step.saturating_mul(1000)
+    }
 
-        let offset = (hmac[19] & 0xf) as usize;
+    /// Generates the code for the time step containing `unix_time` (seconds since the Unix
+    /// epoch). Fails for times before the token's [epoch](Self::with_epoch).
+    pub fn generate_at_time(&self, unix_time: u64) -> Result<String> {
+        let elapsed = unix_time.checked_sub(self.epoch).ok_or_else(|| {
+            Error::InvalidParameters(format!("time {} is before the token's epoch {}", unix_time, self.epoch))
+        })?;
+        self.generate_for_counter(elapsed / self.time_step)
+    }
+
+    // Single HOTP (RFC 4226) implementation shared by every entry point
+    fn generate_for_counter(&self, counter: u64) -> Result<String> {
+        let hmac = self.key.sign(&counter.to_be_bytes());
+
+        // Dynamic truncation uses the low nibble of the last byte
+        let offset = self.truncation_offset.unwrap_or((hmac[hmac.len() - 1] & 0xf) as usize);
         let code = ((hmac[offset] & 0x7f) as u32) << 24
             | (hmac[offset + 1] as u32) << 16
             | (hmac[offset + 2] as u32) << 8
@@ -57,49 +270,83 @@ impl Totp {
         let otp = code % 10_u32.pow(self.digits as u32);
         Ok(format!("{:0width$}", otp, width = self.digits))
     }
+}
 
-    pub(crate) fn time_remaining(&self) -> u64 {
-        let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
-        self.time_step - (time % self.time_step)
-    }
+// How far `time` is into its time step when steps start at `epoch`. Times before the epoch still
+// fall on the same grid, so countdowns line up with the first real step
+fn elapsed_in_step(time: i128, epoch: i128, step: u64) -> u64 {
+    (time - epoch).rem_euclid(step.into()) as u64
+}
 
-    #[cfg(test)]
-    pub(crate) fn generate_at_time(&self, unix_time: u64) -> anyhow::Result<String> {
-        let counter = unix_time / self.time_step;
-        let counter_bytes = counter.to_be_bytes();
+// HMAC (RFC 2104) key for the entry's algorithm
+#[derive(Debug, Clone)]
+enum HmacKey {
+    Sha1(PaddedKey<Sha1>),
+    Sha256(PaddedKey<Sha256>),
+    Sha512(PaddedKey<Sha512>),
+}
 
-        // HMAC-SHA1
-        let mut hasher = Sha1::new();
-        let mut ipad = [0x36; 64];
-        let mut opad = [0x5c; 64];
+impl HmacKey {
+    fn new(algorithm: HashAlgorithm, secret: &[u8]) -> Self {
+        match algorithm {
+            HashAlgorithm::Sha1 => Self::Sha1(PaddedKey::new(secret)),
+            HashAlgorithm::Sha256 => Self::Sha256(PaddedKey::new(secret)),
+            HashAlgorithm::Sha512 => Self::Sha512(PaddedKey::new(secret)),
+        }
+    }
 
-        let mut key = self.secret.clone();
-        if key.len() > 64 {
-            key = Sha1::digest(&key).to_vec();
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Sha1(key) => key.sign(message),
+            Self::Sha256(key) => key.sign(message),
+            Self::Sha512(key) => key.sign(message),
         }
-        key.resize(64, 0);
+    }
+}
+
+// The secret padded (or hashed, when longer) to one block of the digest. It is as good as the
+// secret for producing codes, so it is wiped on drop like it. Signing derives the inner and outer
+// keys from it each time, which costs two extra block compressions per code.
+#[derive(Clone)]
+struct PaddedKey<D> {
+    key: Zeroizing<Vec<u8>>,
+    digest: PhantomData<D>,
+}
+
+impl<D: Digest + BlockSizeUser> PaddedKey<D> {
+    fn new(secret: &[u8]) -> Self {
+        let block_size = D::block_size();
 
-        for i in 0..64 {
-            ipad[i] ^= key[i];
-            opad[i] ^= key[i];
+        let mut key = Zeroizing::new(Vec::with_capacity(block_size));
+        if secret.len() > block_size {
+            key.extend_from_slice(&D::digest(secret));
+        } else {
+            key.extend_from_slice(secret);
         }
+        key.resize(block_size, 0);
+        Self { key, digest: PhantomData }
+    }
 
-        hasher.update(ipad);
-        hasher.update(counter_bytes);
-        let inner_hash = hasher.finalize_reset();
+    fn sign(&self, message: &[u8]) -> Vec<u8> {
+        let ipad = Zeroizing::new(self.key.iter().map(|b| b ^ 0x36).collect::<Vec<u8>>());
+        let opad = Zeroizing::new(self.key.iter().map(|b| b ^ 0x5c).collect::<Vec<u8>>());
 
-        hasher.update(opad);
-        hasher.update(inner_hash);
-        let hmac = hasher.finalize();
+        let mut inner = D::new();
+        inner.update(&ipad);
+        inner.update(message);
+        let inner_hash = inner.finalize();
 
-        let offset = (hmac[19] & 0xf) as usize;
-        let code = ((hmac[offset] & 0x7f) as u32) << 24
-            | (hmac[offset + 1] as u32) << 16
-            | (hmac[offset + 2] as u32) << 8
-            | (hmac[offset + 3] as u32);
+        let mut outer = D::new();
+        outer.update(&opad);
+        outer.update(&inner_hash);
+        outer.finalize().to_vec()
+    }
+}
 
-        let otp = code % 10_u32.pow(self.digits as u32);
-        Ok(format!("{:0width$}", otp, width = self.digits))
+// Keeps the key out of debug output
+impl<D> fmt::Debug for PaddedKey<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PaddedKey").finish_non_exhaustive()
     }
 }
 
@@ -107,6 +354,21 @@ impl Totp {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_weak_secret() {
+        assert_eq!(weak_secret(&[0; 20]), Some("all zero bytes"));
+        assert_eq!(weak_secret(&[0x41; 10]), Some("one byte repeated"));
+        assert_eq!(weak_secret(&(1..=20).collect::<Vec<u8>>()), Some("a counting sequence"));
+        assert_eq!(weak_secret(&(0..20).map(|i: u8| 250u8.wrapping_sub(i * 3)).collect::<Vec<u8>>()), Some("a counting sequence"));
+        // The RFC 6238 test secret: fine for test vectors, not for a real account
+        assert_eq!(weak_secret(b"12345678901234567890"), Some("the same few bytes repeated"));
+        assert_eq!(weak_secret(b"abcabcabcab"), Some("the same few bytes repeated"));
+
+        assert_eq!(weak_secret(&base32_decode("JBSWY3DPEHPK3PXPJBSWY3DQ").unwrap()), None);
+        assert_eq!(weak_secret(b"\x8f\x12\xc4\x5e\x90\x3b\xd7\x61\x0a\xee"), None);
+        assert_eq!(weak_secret(&[]), None);
+    }
+
     #[test]
     fn test_totp_generation() {
         // Test vector from RFC 6238
@@ -143,13 +405,165 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_totp_generation_sha256_sha512() {
+        // Test vectors from RFC 6238 (8 digits, seeds sized to each hash)
+        let sha256 = Totp::with_params(b"12345678901234567890123456789012".to_vec(), HashAlgorithm::Sha256, 8, 30).unwrap();
+        let sha512 = Totp::with_params(
+            b"1234567890123456789012345678901234567890123456789012345678901234".to_vec(),
+            HashAlgorithm::Sha512,
+            8,
+            30,
+        ).unwrap();
+
+        let test_cases = vec![
+            (59, "46119246", "90693936"),
+            (1111111109, "68084774", "25091201"),
+            (1111111111, "67062674", "99943326"),
+            (1234567890, "91819424", "93441116"),
+            (2000000000, "90698825", "38618901"),
+            (20000000000, "77737706", "47863826"),
+        ];
+
+        for (time, expected_sha256, expected_sha512) in test_cases {
+            assert_eq!(sha256.generate_at_time(time).unwrap(), expected_sha256, "SHA256 failed at time {}", time);
+            assert_eq!(sha512.generate_at_time(time).unwrap(), expected_sha512, "SHA512 failed at time {}", time);
+        }
+    }
+
+    #[test]
+    fn test_totp_custom_period_and_digits() {
+        let secret = b"12345678901234567890".to_vec();
+        let totp = Totp::with_params(secret, HashAlgorithm::Sha1, 8, 60).unwrap();
+
+        // Counter 1 with a 60s period covers times 60..120; RFC 4226 HOTP value for counter 1
+        assert_eq!(totp.generate_at_time(60).unwrap(), "94287082");
+        assert_eq!(totp.generate_at_time(119).unwrap(), "94287082");
+        assert_eq!(totp.period(), 60);
+    }
+
+    #[test]
+    fn test_invalid_params() {
+        assert!(Totp::with_params(vec![1; 20], HashAlgorithm::Sha1, 5, 30).is_err());
+        assert!(Totp::with_params(vec![1; 20], HashAlgorithm::Sha1, 9, 30).is_err());
+        assert!(Totp::with_params(vec![1; 20], HashAlgorithm::Sha1, 6, 0).is_err());
+        assert!(Totp::with_params(Vec::new(), HashAlgorithm::Sha1, 6, 30).is_err());
+        assert!(matches!(
+            Totp::with_params(vec![1; 20], HashAlgorithm::Sha1, 10, 30),
+            Err(Error::InvalidParameters(_))
+        ));
+    }
+
+    #[test]
+    fn test_hmac_key_is_reusable() {
+        // RFC 2202 test case 6: a key longer than the block size is hashed first
+        let key = HmacKey::new(HashAlgorithm::Sha1, &[0xaa; 80]);
+        let message = b"Test Using Larger Than Block-Size Key - Hash Key First";
+        let expected = [
+            0xaa, 0x4a, 0xe5, 0xe1, 0x52, 0x72, 0xd0, 0x0e, 0x95, 0x70,
+            0x56, 0x37, 0xce, 0x8a, 0x3b, 0x55, 0xed, 0x40, 0x21, 0x12,
+        ];
+        assert_eq!(key.sign(message), expected);
+        assert_eq!(key.sign(message), expected);
+
+        // A kept generator gives the same codes as one built per call
+        let entry = TotpEntry { secret: "JBSWY3DPEHPK3PXP".to_string(), algorithm: HashAlgorithm::Sha512, ..TotpEntry::default() };
+        let totp = Totp::from_entry(&entry).unwrap();
+        for time in [59, 1111111109, 2000000000] {
+            assert_eq!(totp.generate_at_time(time).unwrap(), Totp::from_entry(&entry).unwrap().generate_at_time(time).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_algorithm_casing_and_aliases() {
+        for value in ["SHA1", "sha1", "Sha1", "SHA-1", "sha-1"] {
+            assert_eq!(HashAlgorithm::parse(value), Some(HashAlgorithm::Sha1), "{}", value);
+        }
+        assert_eq!(HashAlgorithm::parse("sha-256"), Some(HashAlgorithm::Sha256));
+        assert_eq!(HashAlgorithm::parse("SHA-512"), Some(HashAlgorithm::Sha512));
+        assert_eq!(HashAlgorithm::parse("MD5"), None);
+        assert_eq!(HashAlgorithm::parse("SHA_1"), None);
+    }
+
+    #[test]
+    fn test_fixed_truncation_offset() {
+        // RFC 4226 appendix D, counter 0: the HMAC-SHA1 is cc93cf18508d94934c64b65d8ba7667fb7cde4b0
+        // and dynamic truncation picks offset 0; reading from offset 4 gives 0x508d9493
+        let secret = b"12345678901234567890".to_vec();
+        let dynamic = Totp::new(secret.clone());
+        assert_eq!(dynamic.generate_at_time(0).unwrap(), "755224");
+
+        let fixed_zero = Totp::new(secret.clone()).with_truncation_offset(Some(0)).unwrap();
+        assert_eq!(fixed_zero.generate_at_time(0).unwrap(), "755224");
+
+        let fixed = Totp::new(secret.clone()).with_truncation_offset(Some(4)).unwrap();
+        assert_eq!(fixed.generate_at_time(0).unwrap(), format!("{:06}", 0x508d9493u32 % 1_000_000));
+
+        assert!(Totp::new(secret.clone()).with_truncation_offset(Some(16)).is_ok());
+        assert!(Totp::new(secret).with_truncation_offset(Some(17)).is_err());
+        assert!(validate_truncation_offset(HashAlgorithm::Sha512, 60).is_ok());
+    }
+
+    #[test]
+    fn test_custom_epoch() {
+        let secret = b"12345678901234567890".to_vec();
+        let plain = Totp::new(secret.clone());
+        let shifted = Totp::new(secret).with_epoch(1_000_000_007);
+
+        // Each step starts 7 seconds later than usual, with counters counted from T0
+        assert_eq!(shifted.generate_at_time(1_000_000_007).unwrap(), plain.generate_at_time(0).unwrap());
+        assert_eq!(shifted.generate_at_time(1_000_000_066).unwrap(), plain.generate_at_time(59).unwrap());
+        assert_eq!(shifted.generate_at_time(1_111_111_116).unwrap(), plain.generate_at_time(111_111_109).unwrap());
+        assert_eq!(shifted.time_remaining_at(1_000_000_007), 30);
+        assert_eq!(shifted.time_remaining_at(1_000_000_036), 1);
+        assert_eq!(shifted.time_remaining_ms_at(1_000_000_036_500), 500);
+        assert_eq!(shifted.progress_at(1_000_000_022_000), 0.5);
+
+        assert!(matches!(shifted.generate_at_time(1_000_000_006), Err(Error::InvalidParameters(_))));
+        assert_eq!(shifted.time_remaining_at(1_000_000_006), 1);
+    }
+
     #[test]
     fn test_time_remaining() {
         let secret = vec![1, 2, 3, 4, 5];
         let totp = Totp::new(secret);
 
-        let remaining = totp.time_remaining();
+        let remaining = totp.time_remaining().unwrap();
         assert!(remaining > 0 && remaining <= 30);
+
+        assert_eq!(totp.time_remaining_at(0), 30);
+        assert_eq!(totp.time_remaining_at(59), 1);
+    }
+
+    #[test]
+    fn test_progress_and_remaining_ms() {
+        let totp = Totp::new(b"12345678901234567890".to_vec());
+
+        assert_eq!(totp.progress_at(0), 0.0);
+        assert_eq!(totp.progress_at(15_000), 0.5);
+        assert_eq!(totp.progress_at(29_999), 29_999.0 / 30_000.0);
+        assert_eq!(totp.progress_at(30_000), 0.0);
+
+        assert_eq!(totp.time_remaining_ms_at(0), 30_000);
+        assert_eq!(totp.time_remaining_ms_at(29_999), 1);
+        assert_eq!(totp.time_remaining_ms_at(59_250), 750);
+
+        let progress = totp.progress().unwrap();
+        assert!((0.0..1.0).contains(&progress));
+        assert!((1..=30_000).contains(&totp.time_remaining_ms().unwrap()));
+    }
+
+    #[test]
+    fn test_clock_before_epoch_is_an_error() {
+        let before_epoch = UNIX_EPOCH - std::time::Duration::from_secs(1);
+        let error = unix_time(before_epoch).unwrap_err();
+        assert!(matches!(error, Error::Clock));
+        assert!(error.to_string().starts_with("System clock is invalid"));
+
+        // The epoch itself is the earliest time codes can be generated for
+        let totp = Totp::new(b"12345678901234567890".to_vec());
+        assert_eq!(unix_time(UNIX_EPOCH).unwrap(), 0);
+        assert_eq!(totp.generate_at_time(0).unwrap(), "755224");
     }
 
     #[test]
@@ -165,7 +579,8 @@ mod tests {
 
 #[cfg(test)]
 mod base32_tests {
-    use crate::base32::base32_decode;
+    use crate::base32::{base32_decode, base32_encode, base32hex_decode};
+    use crate::Error;
 
     #[test]
     fn test_base32_decode_valid() {
@@ -173,20 +588,106 @@ mod base32_tests {
         // "JBSWY3DPEBLW64TMMQ======" decodes to "Hello World"
         let result = base32_decode("JBSWY3DPEBLW64TMMQ");
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), b"Hello World");
+        assert_eq!(*result.unwrap(), b"Hello World");
     }
 
     #[test]
     fn test_base32_decode_invalid() {
         // Test with invalid characters
         let result = base32_decode("INVALID123!@#");
-        assert!(result.is_err());
+        assert!(matches!(result, Err(Error::InvalidBase32 { alphabet: "base32", .. })));
+    }
+
+    #[test]
+    fn test_base32_decode_whitespace_and_case() {
+        let expected = base32_decode("JBSWY3DPEBLW64TMMQ").unwrap();
+
+        let variants = [
+            "jbswy3dpeblw64tmmq",
+            "JbSwY3dPeBlW64TmMq",
+            "JBSW Y3DP EBLW 64TM MQ",
+            "jbsw y3dp eblw 64tm mq",
+            "JBSW-Y3DP-EBLW-64TM-MQ",
+            " JBSWY3DP\tEBLW64TMMQ\n",
+        ];
+
+        for input in variants {
+            assert_eq!(base32_decode(input).unwrap(), expected, "Failed for {:?}", input);
+        }
+    }
+
+    #[test]
+    fn test_base32_decode_padded() {
+        assert_eq!(*base32_decode("JBSWY3DPEBLW64TMMQ======").unwrap(), b"Hello World");
+        assert_eq!(*base32_decode("MZXW6===").unwrap(), b"foo");
+        assert_eq!(*base32_decode("MZXW6YQ=").unwrap(), b"foob");
+        assert_eq!(*base32_decode("MY======").unwrap(), b"f");
+    }
+
+    #[test]
+    fn test_base32_decode_invalid_length() {
+        // Final blocks of 1, 3 or 6 characters can't come from any byte sequence
+        assert!(base32_decode("M").is_err());
+        assert!(base32_decode("MZX").is_err());
+        assert!(base32_decode("MZXW6Y").is_err());
+        assert!(base32_decode("MZXW6YTBM").is_err());
+    }
+
+    #[test]
+    fn test_base32_decode_nonzero_trailing_bits() {
+        // "MY" is canonical for "f"; "MZ" sets one of the two leftover bits
+        assert!(base32_decode("MY").is_ok());
+        assert!(base32_decode("MZ").is_err());
+        assert!(base32_decode("MZXW7").is_err());
+    }
+
+    #[test]
+    fn test_base32_decode_invalid_padding() {
+        assert!(base32_decode("MZXW6==").is_err());
+        assert!(base32_decode("MZ=XW6==").is_err());
+        assert!(base32_decode("MZXW6YTB========").is_err());
     }
 
     #[test]
     fn test_base32_decode_empty() {
         let result = base32_decode("");
         assert!(result.is_ok());
-        assert_eq!(result.unwrap(), b"");
+        assert_eq!(*result.unwrap(), b"");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_base32_encode() {
+        // Test vectors from RFC 4648, without padding
+        assert_eq!(base32_encode(b""), "");
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_encode(b"fo"), "MZXQ");
+        assert_eq!(base32_encode(b"foo"), "MZXW6");
+        assert_eq!(base32_encode(b"foob"), "MZXW6YQ");
+        assert_eq!(base32_encode(b"fooba"), "MZXW6YTB");
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+    }
+
+    #[test]
+    fn test_base32hex_decode() {
+        // Test vectors from RFC 4648 section 10
+        assert_eq!(*base32hex_decode("").unwrap(), b"");
+        assert_eq!(*base32hex_decode("CO======").unwrap(), b"f");
+        assert_eq!(*base32hex_decode("CPNG").unwrap(), b"fo");
+        assert_eq!(*base32hex_decode("CPNMU").unwrap(), b"foo");
+        assert_eq!(*base32hex_decode("cpnm uoj1").unwrap(), b"fooba");
+        assert_eq!(*base32hex_decode("CPNMUOJ1E8======").unwrap(), b"foobar");
+
+        // The same text means different bytes (or nothing) in the other alphabet
+        assert_eq!(*base32_decode("MZXW6YTBOI").unwrap(), b"foobar");
+        assert_ne!(base32_decode("ABCDEFGH").unwrap(), base32hex_decode("ABCDEFGH").unwrap());
+        assert!(base32hex_decode("MZXW6YTBOI").is_err());
+        assert!(base32_decode("CPNMUOJ1E8").is_err());
+        assert!(base32hex_decode("CPNMW").is_err());
+    }
+
+    #[test]
+    fn test_base32_round_trip() {
+        let secret: Vec<u8> = (0..=255).collect();
+        assert_eq!(*base32_decode(&base32_encode(&secret)).unwrap(), secret);
+    }
+}
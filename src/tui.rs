@@ -0,0 +1,242 @@
+use std::time::Duration;
+use console_totp::{Totp, TotpEntry};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::widgets::{Block, Borders, Cell, Paragraph, Row, Table, TableState};
+use ratatui::{DefaultTerminal, Frame};
+use crate::style;
+
+// Full-screen dashboard for `totp-console tui`
+
+/// How often the countdowns are redrawn when no key is pressed.
+const TICK: Duration = Duration::from_millis(250);
+
+struct App {
+    entries: Vec<TotpEntry>,
+    // One per entry, built once so redraws only hash the current time step. An entry whose
+    // secret or parameters don't work keeps the reason, shown when it is copied.
+    totps: Vec<Result<Totp, String>>,
+    filter: String,
+    /// Keys go to the search box instead of moving the selection
+    editing_filter: bool,
+    table: TableState,
+    status: String,
+    // Opened on the first copy, so browsing works without a display
+    clipboard: Option<arboard::Clipboard>,
+    quit: bool,
+}
+
+/// Runs the dashboard until the user quits. The terminal is restored even if drawing fails.
+pub(crate) fn run(entries: Vec<TotpEntry>) -> anyhow::Result<()> {
+    let totps = entries.iter().map(|entry| Totp::from_entry(entry).map_err(|e| e.to_string())).collect();
+    let mut app = App {
+        entries,
+        totps,
+        filter: String::new(),
+        editing_filter: false,
+        table: TableState::default().with_selected(Some(0)),
+        status: String::new(),
+        clipboard: None,
+        quit: false,
+    };
+
+    let mut terminal = ratatui::try_init()?;
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> anyhow::Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.render(frame))?;
+
+            if event::poll(TICK)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                self.handle_key(key);
+            }
+        }
+        Ok(())
+    }
+
+    fn visible(&self) -> Vec<(&TotpEntry, &Result<Totp, String>)> {
+        self.entries.iter().zip(&self.totps).filter(|(entry, _)| matches_filter(entry, &self.filter)).collect()
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.quit = true;
+            return;
+        }
+
+        if self.editing_filter {
+            match key.code {
+                KeyCode::Enter => self.editing_filter = false,
+                KeyCode::Esc => {
+                    self.editing_filter = false;
+                    self.filter.clear();
+                }
+                KeyCode::Backspace => {
+                    self.filter.pop();
+                }
+                KeyCode::Char(c) => self.filter.push(c),
+                _ => {}
+            }
+            // The old selection may no longer exist in the filtered list
+            self.table.select(Some(0));
+            return;
+        }
+
+        let count = self.visible().len();
+        match key.code {
+            KeyCode::Char('q') => self.quit = true,
+            KeyCode::Esc if !self.filter.is_empty() => {
+                self.filter.clear();
+                self.table.select(Some(0));
+            }
+            KeyCode::Esc => self.quit = true,
+            KeyCode::Char('/') => self.editing_filter = true,
+            KeyCode::Down | KeyCode::Char('j') if count > 0 => {
+                let next = self.table.selected().map_or(0, |index| (index + 1).min(count - 1));
+                self.table.select(Some(next));
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                let previous = self.table.selected().map_or(0, |index| index.saturating_sub(1));
+                self.table.select(Some(previous));
+            }
+            KeyCode::Enter => self.copy_selected(),
+            _ => {}
+        }
+    }
+
+    fn copy_selected(&mut self) {
+        let Some((name, code)) = self.table.selected().and_then(|index| {
+            let (entry, totp) = self.visible().get(index).copied()?;
+            let code = match totp {
+                Ok(totp) => totp.generate().map_err(|e| e.to_string()),
+                Err(reason) => Err(reason.clone()),
+            };
+            Some((entry.name.clone(), code))
+        }) else {
+            return;
+        };
+        let code = match code {
+            Ok(code) => code,
+            Err(e) => {
+                self.status = format!("Cannot generate a code for {}: {}", name, e);
+                return;
+            }
+        };
+
+        if self.clipboard.is_none() {
+            match arboard::Clipboard::new() {
+                Ok(clipboard) => self.clipboard = Some(clipboard),
+                Err(e) => {
+                    self.status = format!("Clipboard unavailable: {}", e);
+                    return;
+                }
+            }
+        }
+        if let Some(clipboard) = self.clipboard.as_mut() {
+            self.status = match clipboard.set_text(code) {
+                Ok(()) => format!("Copied the code for {}", name),
+                Err(e) => format!("Could not copy: {}", e),
+            };
+        }
+    }
+
+    fn render(&mut self, frame: &mut Frame) {
+        let [search_area, table_area, status_area] =
+            Layout::vertical([Constraint::Length(3), Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+
+        let search_title = if self.editing_filter { " Search (Enter to keep, Esc to clear) " } else { " Search (/) " };
+        let cursor = if self.editing_filter { "_" } else { "" };
+        frame.render_widget(
+            Paragraph::new(format!("{}{}", self.filter, cursor))
+                .block(Block::default().borders(Borders::ALL).title(search_title)),
+            search_area,
+        );
+
+        let visible = self.visible();
+        let rows: Vec<Row> = visible.iter().map(|(entry, totp)| entry_row(entry, totp.as_ref().ok())).collect();
+        let title = format!(" TOTP Codes ({} of {}) ", visible.len(), self.entries.len());
+        let table = Table::new(
+            rows,
+            [Constraint::Length(2), Constraint::Fill(2), Constraint::Fill(1), Constraint::Length(10), Constraint::Length(5)],
+        )
+        .header(Row::new(["", "Name", "Issuer", "Code", "Left"]).style(Style::default().add_modifier(Modifier::BOLD)))
+        .block(Block::default().borders(Borders::ALL).title(title))
+        .row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(table, table_area, &mut self.table);
+
+        let help = "↑/↓ select  Enter copy  / search  q quit";
+        let status = if self.status.is_empty() { help.to_string() } else { format!("{}  |  {}", self.status, help) };
+        frame.render_widget(Paragraph::new(status), status_area);
+    }
+}
+
+fn entry_row(entry: &TotpEntry, totp: Option<&Totp>) -> Row<'static> {
+    let (code, remaining, progress) = match totp {
+        Some(totp) => (
+            totp.generate().unwrap_or_else(|_| "error".to_string()),
+            totp.time_remaining().unwrap_or_default(),
+            totp.progress().unwrap_or_default(),
+        ),
+        None => ("error".to_string(), 0, 1.0),
+    };
+
+    // Same threshold as `loop`: flag codes that are about to expire
+    let urgent = style::enabled() && remaining <= style::LOW_TIME;
+    let time_style = if urgent { Style::default().fg(Color::Red) } else { Style::default() };
+
+    Row::new([
+        Cell::from(ring(progress)).style(time_style),
+        Cell::from(crate::name_cell(entry)),
+        Cell::from(entry.issuer.clone().unwrap_or_default()),
+        Cell::from(code),
+        Cell::from(format!("{}s", remaining)).style(time_style),
+    ])
+}
+
+// A pie that empties as the period runs out
+fn ring(progress: f64) -> &'static str {
+    const RINGS: [&str; 5] = ["●", "◕", "◑", "◔", "○"];
+    let index = (progress.clamp(0.0, 1.0) * RINGS.len() as f64) as usize;
+    RINGS[index.min(RINGS.len() - 1)]
+}
+
+// Case-insensitive match on name, issuer or account
+fn matches_filter(entry: &TotpEntry, filter: &str) -> bool {
+    let filter = filter.to_lowercase();
+    [Some(&entry.name), entry.issuer.as_ref(), entry.account.as_ref()]
+        .into_iter()
+        .flatten()
+        .any(|field| field.to_lowercase().contains(&filter))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_and_ring() {
+        let entry = TotpEntry {
+            name: "work".to_string(),
+            issuer: Some("GitHub".to_string()),
+            account: Some("alice@example.com".to_string()),
+            ..TotpEntry::default()
+        };
+        assert!(matches_filter(&entry, ""));
+        assert!(matches_filter(&entry, "git"));
+        assert!(matches_filter(&entry, "ALICE"));
+        assert!(!matches_filter(&entry, "gitlab"));
+
+        assert_eq!(ring(0.0), "●");
+        assert_eq!(ring(0.5), "◑");
+        assert_eq!(ring(0.99), "○");
+        assert_eq!(ring(1.0), "○");
+    }
+}
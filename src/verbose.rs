@@ -0,0 +1,81 @@
+use std::sync::OnceLock;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+// Diagnostics for `--verbose`: the library logs through the `log` facade, and this writes those
+// records to stderr so normal output on stdout stays clean. Nothing is logged without the flag.
+
+struct StderrLogger;
+
+// Dependencies such as reqwest are only heard from at -vv; -v is about our own decisions
+static DEPENDENCIES: OnceLock<bool> = OnceLock::new();
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let ours = metadata.target().starts_with(env!("CARGO_CRATE_NAME"));
+        metadata.level() <= log::max_level() && (ours || DEPENDENCIES.get().copied().unwrap_or(false))
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            eprintln!("[{}] {}: {}", level_name(record.level()), record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Turns on logging for `-v` (debug) or `-vv` and more (trace, including dependencies).
+/// Without the flag nothing is installed, so log statements cost next to nothing.
+pub(crate) fn init(verbosity: u8) {
+    let level = match verbosity {
+        0 => return,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+    let _ = DEPENDENCIES.set(verbosity > 1);
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// How many times `-v`/`--verbose` appears in `arg`, or None if it isn't a verbosity flag.
+/// Short flags may be stacked (`-vv`).
+pub(crate) fn count_flag(arg: &str) -> Option<u8> {
+    if arg == "--verbose" {
+        return Some(1);
+    }
+    let vs = arg.strip_prefix('-')?;
+    if !vs.is_empty() && vs.chars().all(|c| c == 'v') {
+        Some(vs.len().min(u8::MAX as usize) as u8)
+    } else {
+        None
+    }
+}
+
+fn level_name(level: Level) -> &'static str {
+    match level {
+        Level::Error => "error",
+        Level::Warn => "warn",
+        Level::Info => "info",
+        Level::Debug => "debug",
+        Level::Trace => "trace",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_flag() {
+        assert_eq!(count_flag("--verbose"), Some(1));
+        assert_eq!(count_flag("-v"), Some(1));
+        assert_eq!(count_flag("-vvv"), Some(3));
+        assert_eq!(count_flag("-"), None);
+        assert_eq!(count_flag("-vq"), None);
+        assert_eq!(count_flag("--version"), None);
+        assert_eq!(count_flag("v"), None);
+    }
+}
@@ -0,0 +1,35 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Runs the binary on a throwaway database with `stdin` piped in, so it never sees a terminal
+fn run(args: &[&str], stdin: &str) -> (Option<i32>, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_console-totp"))
+        .arg("--ephemeral")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("run console-totp");
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (output.status.code(), String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+#[test]
+fn test_restoring_a_dump_without_a_terminal_needs_yes() {
+    let dump = console_totp::TotpDatabase::in_memory().unwrap().dump().unwrap();
+    let path = std::env::temp_dir().join(format!("totp-cli-test-{}.sql", std::process::id()));
+    std::fs::write(&path, &dump).unwrap();
+
+    for args in [vec!["restore", "--dump", path.to_str().unwrap()], vec!["restore", "--dump", "-"]] {
+        let (code, stderr) = run(&args, &dump);
+        assert_eq!(code, Some(2), "{:?}: {}", args, stderr);
+        assert!(stderr.contains("pass --yes"), "{:?}: {}", args, stderr);
+    }
+
+    let (code, stderr) = run(&["restore", "--dump", "-", "--yes"], &dump);
+    assert_eq!(code, Some(0), "{}", stderr);
+
+    let _ = std::fs::remove_file(&path);
+}
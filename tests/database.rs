@@ -0,0 +1,520 @@
+use console_totp::database::{is_duplicate, EntryFilter, Move, Page, SearchMode, SortOrder, VaultStats};
+use console_totp::{Error, HashAlgorithm, Totp, TotpDatabase, TotpEntry};
+
+fn open() -> TotpDatabase {
+    TotpDatabase::in_memory().expect("in-memory database")
+}
+
+fn entry(name: &str, issuer: Option<&str>) -> TotpEntry {
+    TotpEntry {
+        name: name.to_string(),
+        secret: "JBSWY3DPEHPK3PXP".to_string(),
+        issuer: issuer.map(str::to_string),
+        ..TotpEntry::default()
+    }
+}
+
+#[test]
+fn test_add_and_get_entry() {
+    let db = open();
+    db.add_entry(&TotpEntry {
+        algorithm: HashAlgorithm::Sha256,
+        digits: 8,
+        period: 60,
+        account: Some("alice@example.com".to_string()),
+        ..entry("github", Some("GitHub"))
+    }).unwrap();
+
+    let stored = db.get_entry_by_name("github").unwrap().unwrap();
+    assert!(stored.id.is_some());
+    assert!(!stored.created_at.is_empty());
+    assert_eq!(stored.issuer.as_deref(), Some("GitHub"));
+    assert_eq!(stored.algorithm, HashAlgorithm::Sha256);
+    assert_eq!(stored.digits, 8);
+    assert_eq!(stored.period, 60);
+    assert_eq!(stored.account.as_deref(), Some("alice@example.com"));
+
+    assert!(db.get_entry_by_name("gitlab").unwrap().is_none());
+}
+
+#[test]
+fn test_lookup_is_case_insensitive() {
+    let db = open();
+    db.add_entry(&entry("GitHub", None)).unwrap();
+
+    let stored = db.get_entry_by_name("github").unwrap().unwrap();
+    assert_eq!(stored.name, "GitHub");
+}
+
+#[test]
+fn test_duplicate_names_are_rejected() {
+    let db = open();
+    db.add_entry(&entry("github", None)).unwrap();
+
+    let error = db.add_entry(&entry("github", Some("Other"))).unwrap_err();
+    assert!(is_duplicate(&error));
+    assert!(matches!(error, Error::DuplicateName(ref name) if name == "github"), "{:?}", error);
+    assert_eq!(db.get_all_entries().unwrap().len(), 1);
+}
+
+#[test]
+fn test_update_entry_changes_secret_and_issuer() {
+    let db = open();
+    db.add_entry(&entry("github", None)).unwrap();
+
+    db.update_entry("GITHUB", Some("GEZDGNBVGY3TQOJQ"), Some("GitHub")).unwrap();
+    let stored = db.get_entry_by_name("github").unwrap().unwrap();
+    assert_eq!(stored.secret, "GEZDGNBVGY3TQOJQ");
+    assert_eq!(stored.issuer.as_deref(), Some("GitHub"));
+
+    // Leaving a field out keeps its current value
+    db.update_entry("github", None, None).unwrap();
+    assert_eq!(db.get_entry_by_name("github").unwrap().unwrap().secret, "GEZDGNBVGY3TQOJQ");
+
+    let error = db.update_entry("missing", Some("GEZDGNBVGY3TQOJQ"), None).unwrap_err();
+    assert!(matches!(error, Error::EntryNotFound(ref name) if name == "missing"), "{:?}", error);
+}
+
+#[test]
+fn test_delete_restore_and_purge() {
+    let db = open();
+    db.add_entry(&TotpEntry { account: Some("alice".to_string()), ..entry("github", None) }).unwrap();
+    db.add_entry(&entry("gitlab", None)).unwrap();
+
+    db.delete_entry("github").unwrap();
+    assert!(matches!(db.delete_entry("github"), Err(Error::EntryNotFound(_))));
+    assert!(db.get_entry_by_name("github").unwrap().is_none());
+    assert_eq!(db.get_deleted_entries().unwrap().len(), 1);
+
+    assert_eq!(db.restore_entry("GitHub").unwrap(), "github");
+    assert_eq!(db.get_entry_by_name("github").unwrap().unwrap().account.as_deref(), Some("alice"));
+    assert!(db.get_deleted_entries().unwrap().is_empty());
+
+    // A live entry holding the name blocks the restore
+    db.delete_entry("gitlab").unwrap();
+    db.add_entry(&entry("gitlab", None)).unwrap();
+    assert!(matches!(db.restore_entry("gitlab"), Err(Error::DuplicateName(ref name)) if name == "gitlab"));
+
+    db.delete_entry("github").unwrap();
+    db.delete_entry("gitlab").unwrap();
+    assert_eq!(db.purge_deleted(Some("gitlab")).unwrap(), 2);
+    assert_eq!(db.purge_deleted(None).unwrap(), 1);
+    assert!(matches!(db.restore_entry("github"), Err(Error::EntryNotFound(_))));
+}
+
+#[test]
+fn test_truncation_offset_survives_update_and_restore() {
+    let db = open();
+    db.add_entry(&TotpEntry { truncation_offset: Some(4), ..entry("legacy", None) }).unwrap();
+    assert_eq!(db.get_entry_by_name("legacy").unwrap().unwrap().truncation_offset, Some(4));
+
+    db.delete_entry("legacy").unwrap();
+    db.restore_entry("legacy").unwrap();
+    assert_eq!(db.get_entry_by_name("legacy").unwrap().unwrap().truncation_offset, Some(4));
+
+    db.set_truncation_offset("LEGACY", None).unwrap();
+    assert_eq!(db.get_entry_by_name("legacy").unwrap().unwrap().truncation_offset, None);
+    assert!(matches!(db.set_truncation_offset("missing", Some(0)), Err(Error::EntryNotFound(_))));
+}
+
+#[test]
+fn test_epoch_survives_restore_and_overwrite() {
+    let db = open();
+    db.add_entry(&TotpEntry { epoch: 1_700_000_007, ..entry("shifted", None) }).unwrap();
+    db.delete_entry("shifted").unwrap();
+    db.restore_entry("shifted").unwrap();
+    let stored = db.get_entry_by_name("shifted").unwrap().unwrap();
+    assert_eq!(stored.epoch, 1_700_000_007);
+    assert_eq!(Totp::from_entry(&stored).unwrap().generate_at_time(1_700_000_036).unwrap(),
+               Totp::from_entry(&entry("plain", None)).unwrap().generate_at_time(29).unwrap());
+
+    db.overwrite_entry(&entry("shifted", None)).unwrap();
+    assert_eq!(db.get_entry_by_name("shifted").unwrap().unwrap().epoch, 0);
+}
+
+#[test]
+fn test_expired_entries_can_be_filtered_out() {
+    let now = chrono::Utc::now();
+    let db = open();
+    db.add_entry(&TotpEntry { expires_at: Some(now - chrono::Duration::days(1)), ..entry("contractor", None) }).unwrap();
+    db.add_entry(&TotpEntry { expires_at: Some(now + chrono::Duration::days(1)), ..entry("trial", None) }).unwrap();
+    db.add_entry(&entry("github", None)).unwrap();
+
+    let names = |filter: &EntryFilter| -> Vec<String> {
+        db.get_entries_page(filter, SortOrder::Name, Page::default()).unwrap().entries.into_iter().map(|e| e.name).collect()
+    };
+    assert_eq!(names(&EntryFilter::default()), ["contractor", "github", "trial"]);
+    assert_eq!(names(&EntryFilter { unexpired_at: Some(now), ..EntryFilter::default() }), ["github", "trial"]);
+    assert_eq!(db.count_expired(&EntryFilter::default(), now).unwrap(), 1);
+    assert_eq!(db.count_expired(&EntryFilter { unexpired_at: Some(now), ..EntryFilter::default() }, now).unwrap(), 1);
+    assert_eq!(db.count_expired(&EntryFilter { issuer: Some("other"), ..EntryFilter::default() }, now).unwrap(), 0);
+    assert_eq!(db.count_expired(&EntryFilter::default(), now + chrono::Duration::days(2)).unwrap(), 2);
+
+    let contractor = db.get_entry_by_name("contractor").unwrap().unwrap();
+    assert!(contractor.is_expired_at(now));
+    assert!(!db.get_entry_by_name("trial").unwrap().unwrap().is_expired_at(now));
+
+    db.delete_entry("contractor").unwrap();
+    db.restore_entry("contractor").unwrap();
+    assert_eq!(db.get_entry_by_name("contractor").unwrap().unwrap().expires_at, contractor.expires_at);
+
+    db.set_expires_at("CONTRACTOR", None).unwrap();
+    assert_eq!(names(&EntryFilter { unexpired_at: Some(now), ..EntryFilter::default() }), ["contractor", "github", "trial"]);
+}
+
+#[test]
+fn test_failed_transaction_keeps_nothing() {
+    let db = open();
+    db.add_entry(&entry("github", None)).unwrap();
+
+    let result = db.transaction(|db| {
+        db.set_login_url("github", Some("https://github.com/login"))?;
+        db.set_favorite("missing", true)
+    });
+    assert!(matches!(result, Err(Error::EntryNotFound(_))));
+    assert_eq!(db.get_entry_by_name("github").unwrap().unwrap().login_url, None);
+
+    db.transaction(|db| db.set_login_url("github", Some("https://github.com/login"))).unwrap();
+    assert!(db.get_entry_by_name("github").unwrap().unwrap().login_url.is_some());
+}
+
+#[test]
+fn test_overwrite_entry_keeps_id_and_created_at() {
+    let db = open();
+    db.add_entry(&entry("github", Some("GitHub"))).unwrap();
+    let original = db.get_entry_by_name("github").unwrap().unwrap();
+
+    let imported = TotpEntry {
+        secret: "GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ".to_string(),
+        issuer: Some("GitHub Inc".to_string()),
+        algorithm: HashAlgorithm::Sha256,
+        digits: 8,
+        account: Some("alice@example.com".to_string()),
+        favorite: true,
+        ..entry("github", None)
+    };
+    db.overwrite_entry(&imported).unwrap();
+
+    let updated = db.get_entry_by_name("github").unwrap().unwrap();
+    assert_eq!((updated.id, &updated.created_at), (original.id, &original.created_at));
+    assert_eq!(updated.secret, imported.secret);
+    assert_eq!(updated.issuer.as_deref(), Some("GitHub Inc"));
+    assert_eq!((updated.algorithm, updated.digits), (HashAlgorithm::Sha256, 8));
+    assert_eq!(updated.account.as_deref(), Some("alice@example.com"));
+    assert!(updated.favorite);
+
+    assert!(matches!(db.overwrite_entry(&entry("missing", None)), Err(Error::EntryNotFound(_))));
+}
+
+#[test]
+fn test_get_entry_by_name_and_issuer() {
+    let db = open();
+    db.add_entry(&entry("work", Some("GitHub"))).unwrap();
+    db.add_entry(&entry("Work", Some("GitLab"))).unwrap();
+
+    assert_eq!(db.get_entry_by_name_and_issuer("WORK", "gitlab").unwrap().unwrap().name, "Work");
+    assert_eq!(db.get_entry_by_name_and_issuer("work", "GitHub").unwrap().unwrap().name, "work");
+    assert!(db.get_entry_by_name_and_issuer("work", "Google").unwrap().is_none());
+}
+
+#[test]
+fn test_search_and_filter_by_issuer() {
+    let db = open();
+    db.add_entry(&entry("github", Some("GitHub"))).unwrap();
+    db.add_entry(&entry("work-mail", Some("Google"))).unwrap();
+    db.add_entry(&entry("personal-mail", Some("Google"))).unwrap();
+
+    let names = |entries: Vec<TotpEntry>| entries.into_iter().map(|e| e.name).collect::<Vec<_>>();
+    assert_eq!(names(db.search_entries("mail", SearchMode::Contains).unwrap()), ["personal-mail", "work-mail"]);
+    assert_eq!(names(db.search_entries("git", SearchMode::Contains).unwrap()), ["github"]);
+    assert_eq!(names(db.get_entries_by_issuer("google").unwrap()), ["personal-mail", "work-mail"]);
+}
+
+#[test]
+fn test_search_modes() {
+    let db = open();
+    db.add_entry(&entry("github", Some("GitHub"))).unwrap();
+    db.add_entry(&entry("digit-bank", None)).unwrap();
+    db.add_entry(&entry("git", None)).unwrap();
+    db.add_entry(&entry("100%_bank", None)).unwrap();
+
+    let names = |entries: Vec<TotpEntry>| entries.into_iter().map(|e| e.name).collect::<Vec<_>>();
+    assert_eq!(names(db.search_entries("git", SearchMode::Contains).unwrap()), ["digit-bank", "git", "github"]);
+    assert_eq!(names(db.search_entries("GIT", SearchMode::Prefix).unwrap()), ["git", "github"]);
+    assert_eq!(names(db.search_entries("git", SearchMode::Exact).unwrap()), ["git"]);
+    assert_eq!(names(db.search_entries("github", SearchMode::Exact).unwrap()), ["github"]);
+
+    // Wildcard characters only match themselves
+    assert_eq!(names(db.search_entries("%", SearchMode::Contains).unwrap()), ["100%_bank"]);
+    assert!(db.search_entries("g_t", SearchMode::Contains).unwrap().is_empty());
+}
+
+#[test]
+fn test_pages_report_the_total() {
+    let db = open();
+    for name in ["a-mail", "b-git", "c-mail", "d-mail", "e-git"] {
+        db.add_entry(&entry(name, None)).unwrap();
+    }
+
+    let names = |page: console_totp::database::EntryPage| (page.entries.into_iter().map(|e| e.name).collect::<Vec<_>>(), page.total);
+    assert_eq!(names(db.get_entries_page(&EntryFilter::default(), SortOrder::Name, Page { limit: Some(2), offset: 1 }).unwrap()), (vec!["b-git".to_string(), "c-mail".to_string()], 5));
+    assert_eq!(names(db.search_entries_page("mail", SearchMode::Contains, Page { limit: None, offset: 2 }).unwrap()), (vec!["d-mail".to_string()], 3));
+    assert_eq!(names(db.search_entries_page("git", SearchMode::Contains, Page { limit: Some(0), offset: 0 }).unwrap()), (vec![], 2));
+    assert_eq!(db.get_entries_page(&EntryFilter::default(), SortOrder::Name, Page::default()).unwrap().total, 5);
+}
+
+#[test]
+fn test_move_entry_changes_position_order() {
+    let db = open();
+    for name in ["c", "a", "b"] {
+        db.add_entry(&entry(name, None)).unwrap();
+    }
+    let order = |sort| db.get_entries_page(&EntryFilter::default(), sort, Page::default()).unwrap().entries.into_iter().map(|e| e.name).collect::<Vec<_>>();
+    // New entries are appended, so the manual order starts out as insertion order
+    assert_eq!(order(SortOrder::Position), ["c", "a", "b"]);
+    assert_eq!(order(SortOrder::Created), ["c", "a", "b"]);
+    assert_eq!(order(SortOrder::Name), ["a", "b", "c"]);
+
+    assert_eq!(db.move_entry("B", Move::Up).unwrap(), 2);
+    assert_eq!(order(SortOrder::Position), ["c", "b", "a"]);
+    assert_eq!(db.move_entry("a", Move::To(1)).unwrap(), 1);
+    assert_eq!(order(SortOrder::Position), ["a", "c", "b"]);
+
+    // Moves past either end stop there
+    assert_eq!(db.move_entry("a", Move::Up).unwrap(), 1);
+    assert_eq!(db.move_entry("b", Move::Down).unwrap(), 3);
+    assert_eq!(db.move_entry("c", Move::To(99)).unwrap(), 3);
+    assert_eq!(order(SortOrder::Position), ["a", "b", "c"]);
+
+    // Restored entries go to the end
+    db.delete_entry("a").unwrap();
+    db.restore_entry("a").unwrap();
+    assert_eq!(order(SortOrder::Position), ["b", "c", "a"]);
+
+    assert!(matches!(db.move_entry("missing", Move::Up), Err(Error::EntryNotFound(_))));
+}
+
+#[test]
+fn test_filter_by_creation_time() {
+    let db = open();
+    db.add_entry(&entry("old", Some("GitHub"))).unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(5));
+    let since = chrono::Utc::now();
+    db.add_entry(&entry("new-github", Some("GitHub"))).unwrap();
+    db.add_entry(&entry("new-gitlab", Some("GitLab"))).unwrap();
+
+    let names = |filter: EntryFilter| {
+        db.get_entries_page(&filter, SortOrder::Name, Page::default()).unwrap().entries.into_iter().map(|e| e.name).collect::<Vec<_>>()
+    };
+    assert_eq!(names(EntryFilter { since: Some(since), ..EntryFilter::default() }), ["new-github", "new-gitlab"]);
+    assert_eq!(names(EntryFilter { issuer: Some("github"), since: Some(since), ..EntryFilter::default() }), ["new-github"]);
+    assert!(names(EntryFilter { since: Some(since + chrono::Duration::hours(1)), ..EntryFilter::default() }).is_empty());
+    assert_eq!(names(EntryFilter::default()).len(), 3);
+}
+
+#[test]
+fn test_favorites_are_listed_first() {
+    let db = open();
+    for name in ["a", "b", "c"] {
+        db.add_entry(&entry(name, None)).unwrap();
+    }
+    db.set_favorite("C", true).unwrap();
+    let names = |entries: Vec<TotpEntry>| entries.into_iter().map(|e| e.name).collect::<Vec<_>>();
+
+    let order = |sort| db.get_entries_page(&EntryFilter::default(), sort, Page::default()).unwrap().entries.into_iter().map(|e| (e.name, e.favorite)).collect::<Vec<_>>();
+    let expected = [("c".to_string(), true), ("a".to_string(), false), ("b".to_string(), false)];
+    assert_eq!(order(SortOrder::Name), expected);
+    assert_eq!(order(SortOrder::Position), expected);
+    assert_eq!(names(db.search_entries("", SearchMode::Contains).unwrap()), ["c", "a", "b"]);
+
+    // The pin survives the recycle bin
+    db.delete_entry("c").unwrap();
+    db.restore_entry("c").unwrap();
+    assert!(db.get_entry_by_name("c").unwrap().unwrap().favorite);
+
+    db.set_favorite("c", false).unwrap();
+    assert_eq!(names(db.get_all_entries().unwrap()), ["a", "b", "c"]);
+    assert!(matches!(db.set_favorite("missing", true), Err(Error::EntryNotFound(_))));
+}
+
+#[test]
+fn test_login_url_round_trips() {
+    let db = open();
+    let url = Some("https://github.com/login".to_string());
+    db.add_entry(&TotpEntry { login_url: url.clone(), ..entry("github", None) }).unwrap();
+    db.add_entry(&entry("gitlab", None)).unwrap();
+    assert_eq!(db.get_entry_by_name("github").unwrap().unwrap().login_url, url);
+
+    db.delete_entry("github").unwrap();
+    db.restore_entry("github").unwrap();
+    assert_eq!(db.get_entry_by_name("github").unwrap().unwrap().login_url, url);
+
+    db.set_login_url("GitLab", Some("https://gitlab.com/users/sign_in")).unwrap();
+    assert!(db.get_entry_by_name("gitlab").unwrap().unwrap().login_url.is_some());
+    db.set_login_url("gitlab", None).unwrap();
+    assert_eq!(db.get_entry_by_name("gitlab").unwrap().unwrap().login_url, None);
+    assert!(matches!(db.set_login_url("missing", None), Err(Error::EntryNotFound(_))));
+}
+
+#[test]
+fn test_backup_codes_round_trip() {
+    let db = open();
+    let codes = vec!["1234-5678".to_string(), "8765-4321".to_string()];
+    db.add_entry(&TotpEntry { backup_codes: codes.clone(), ..entry("github", None) }).unwrap();
+    db.add_entry(&entry("gitlab", None)).unwrap();
+    assert_eq!(db.get_entry_by_name("github").unwrap().unwrap().backup_codes, codes);
+    assert!(db.get_entry_by_name("gitlab").unwrap().unwrap().backup_codes.is_empty());
+
+    db.delete_entry("github").unwrap();
+    db.restore_entry("github").unwrap();
+    assert_eq!(db.get_entry_by_name("github").unwrap().unwrap().backup_codes, codes);
+
+    db.set_backup_codes("github", &codes[1..]).unwrap();
+    assert_eq!(db.get_entry_by_name("github").unwrap().unwrap().backup_codes, ["8765-4321"]);
+    db.set_backup_codes("github", &[]).unwrap();
+    assert!(db.get_entry_by_name("github").unwrap().unwrap().backup_codes.is_empty());
+    assert!(matches!(db.set_backup_codes("missing", &[]), Err(Error::EntryNotFound(_))));
+
+    // Only the entry named exactly so when another differs just in case
+    db.add_entry(&entry("GitHub", None)).unwrap();
+    db.set_backup_codes("github", &codes).unwrap();
+    let with_codes: Vec<String> = db.get_all_entries().unwrap().into_iter().filter(|e| !e.backup_codes.is_empty()).map(|e| e.name).collect();
+    assert_eq!(with_codes, ["github"]);
+}
+
+#[test]
+fn test_stats_count_entries() {
+    let db = open();
+    assert_eq!(db.get_stats().unwrap(), VaultStats::default());
+
+    db.add_entry(&entry("github", None)).unwrap();
+    db.add_entry(&TotpEntry { algorithm: HashAlgorithm::Sha256, digits: 8, ..entry("gitlab", None) }).unwrap();
+    db.add_entry(&TotpEntry { algorithm: HashAlgorithm::Sha256, ..entry("aws", None) }).unwrap();
+
+    let stats = db.get_stats().unwrap();
+    assert_eq!(stats.count, 3);
+    assert_eq!(stats.oldest.unwrap().0, "github");
+    assert_eq!(stats.newest.unwrap().0, "aws");
+    assert_eq!(stats.algorithms, [(HashAlgorithm::Sha256, 2), (HashAlgorithm::Sha1, 1)]);
+    assert_eq!(stats.digits, [(6, 2), (8, 1)]);
+    assert_eq!(stats.periods, [(30, 3)]);
+}
+
+#[test]
+fn test_concurrent_writers_lose_nothing() {
+    let path = std::env::temp_dir().join(format!("totp-concurrent-{}.db", std::process::id()));
+    let path = path.to_str().unwrap().to_string();
+    // Every writer opens the not-yet-created file at once, so this also races the migrations
+
+    let writers: Vec<_> = (0..4)
+        .map(|writer| {
+            let path = path.clone();
+            std::thread::spawn(move || {
+                let db = TotpDatabase::new(&path).unwrap();
+                for index in 0..25 {
+                    db.add_entry(&entry(&format!("writer{}-{}", writer, index), None)).unwrap();
+                }
+            })
+        })
+        .collect();
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    assert_eq!(TotpDatabase::new(&path).unwrap().get_all_entries().unwrap().len(), 100);
+
+    for suffix in ["", "-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", path, suffix));
+    }
+}
+
+#[test]
+fn test_usage_log_is_newest_first() {
+    let db = open();
+    db.log_usage("github", "get").unwrap();
+    db.log_usage("gitlab", "copy").unwrap();
+    db.log_usage("GitHub", "copy").unwrap();
+
+    let all = db.get_usage_log(None, None).unwrap();
+    let actions: Vec<(&str, &str)> = all.iter().map(|record| (record.name.as_str(), record.action.as_str())).collect();
+    assert_eq!(actions, [("GitHub", "copy"), ("gitlab", "copy"), ("github", "get")]);
+
+    assert_eq!(db.get_usage_log(Some("github"), None).unwrap().len(), 2);
+    assert_eq!(db.get_usage_log(None, Some(1)).unwrap()[0].name, "GitHub");
+}
+
+#[test]
+fn test_dump_restores_every_table() {
+    let db = open();
+    db.add_entry(&TotpEntry {
+        account: Some("o'neil".to_string()),
+        backup_codes: vec!["1111".to_string(), "2222".to_string()],
+        epoch: 1_000_000,
+        ..entry("github", Some("GitHub"))
+    })
+    .unwrap();
+    db.add_entry(&entry("gitlab", None)).unwrap();
+    db.set_favorite("gitlab", true).unwrap();
+    db.delete_entry("gitlab").unwrap();
+    db.log_usage("github", "copy").unwrap();
+    let dump = db.dump().unwrap();
+
+    let copy = open();
+    copy.add_entry(&entry("replaced", None)).unwrap();
+    assert_eq!(copy.restore_dump(&dump).unwrap(), 1);
+    assert!(copy.get_entry_by_name("replaced").unwrap().is_none());
+    let github = copy.get_entry_by_name("github").unwrap().unwrap();
+    assert_eq!((github.account.as_deref(), github.backup_codes.len(), github.epoch), (Some("o'neil"), 2, 1_000_000));
+    assert_eq!(copy.get_deleted_entries().unwrap()[0].entry.name, "gitlab");
+    assert_eq!(copy.get_usage_log(None, None).unwrap(), db.get_usage_log(None, None).unwrap());
+    // Every other column and table too
+    assert_eq!(copy.dump().unwrap(), dump);
+
+    // AUTOINCREMENT counters come back too, so a new entry doesn't reuse a deleted one's id
+    copy.add_entry(&entry("new", None)).unwrap();
+    assert_eq!(copy.get_entry_by_name("new").unwrap().unwrap().id, Some(3));
+}
+
+#[test]
+fn test_old_dumps_are_migrated() {
+    let db = open();
+    let dump = "CREATE TABLE totp_entries (id INTEGER PRIMARY KEY AUTOINCREMENT, name TEXT NOT NULL UNIQUE, \
+                secret TEXT NOT NULL, issuer TEXT, created_at TEXT NOT NULL);
+                INSERT INTO totp_entries VALUES(1, 'github', 'JBSWY3DPEHPK3PXP', '', '2024-01-01T00:00:00+00:00');";
+    assert_eq!(db.restore_dump(dump).unwrap(), 1);
+
+    let github = db.get_entry_by_name("github").unwrap().unwrap();
+    assert_eq!((github.digits, github.period), (6, 30));
+    db.delete_entry("github").unwrap();
+    assert_eq!(db.get_deleted_entries().unwrap().len(), 1);
+}
+
+#[test]
+fn test_invalid_dumps_leave_the_database_unchanged() {
+    let db = open();
+    db.add_entry(&entry("github", None)).unwrap();
+    let newer = db.dump().unwrap().replace("PRAGMA user_version = 11;", "PRAGMA user_version = 999;");
+    let unreadable = db.dump().unwrap().replace("'SHA1'", "'MD5'");
+
+    // Valid otherwise, but reaching for files outside the database
+    let probe = std::env::temp_dir().join(format!("totp-attach-probe-{}.db", std::process::id()));
+    let attach = format!("ATTACH DATABASE '{}' AS x; CREATE TABLE x.pwned (a);\n{}", probe.display(), db.dump().unwrap());
+    let vacuum = format!("{}VACUUM INTO '{}';", db.dump().unwrap(), probe.display());
+
+    for dump in ["not sql", "CREATE TABLE other (id INTEGER);", &newer, &unreadable, &attach, &vacuum] {
+        assert!(matches!(db.restore_dump(dump), Err(Error::InvalidDump(_))), "{}", dump);
+        assert!(db.get_entry_by_name("github").unwrap().is_some());
+        assert!(!probe.exists(), "{}", dump);
+    }
+}
+
+#[test]
+fn test_dump_may_end_in_non_ascii_text() {
+    let db = open();
+    db.add_entry(&entry("github", None)).unwrap();
+
+    let dump = format!("{}-- 工作工", db.dump().unwrap());
+    assert_eq!(open().restore_dump(&dump).unwrap(), 1);
+    let dump = format!("{}-- 工作工\n", db.dump().unwrap().trim_end().trim_end_matches("COMMIT;"));
+    assert_eq!(open().restore_dump(&dump).unwrap(), 1);
+}